@@ -0,0 +1,225 @@
+//! Headless bot client for automated play and load testing.
+//!
+//! Spawns a number of bots that connect to a server, register themselves with an `InitPacket` and
+//! then send `InputPacket`s chosen by a bot policy (see `cactuswar_client::bot`).
+//!
+//! ```text
+//! cargo run --bin bot -- --url ws://localhost:8000/ --bots 20 --policy farm
+//! ```
+
+// Bots use native sockets, so there is nothing to build for the web.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cactuswar_client::bot::{self, BotView};
+use cactuswar_client::protocol::{self, Protocol};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+
+const USAGE: &str = "Usage: bot [options]
+
+Options:
+    --url <url>         Server to connect to (default: ws://localhost:8000/)
+    --bots <n>          Number of concurrent bots (default: 1)
+    --name <name>       Bot name, `{}` is replaced by the bot number (default: Bot {})
+    --policy <policy>   wander, chase or farm (default: wander)
+    --rate <hz>         Inputs sent per second by each bot, up to 1000 (default: 30)
+    --log <mode>        Census logging: none, summary or full (default: summary)
+    --seed <n>          Seed for the bot policies (default: 1)";
+
+/// The most inputs a bot sends per second, which keeps the read timeout (a quarter of the time
+/// between inputs) above zero.
+const MAX_RATE: f64 = 1000.;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CensusLog {
+    None,
+    Summary,
+    Full,
+}
+
+#[derive(Clone)]
+struct Options {
+    url: String,
+    bots: u32,
+    name: String,
+    policy: String,
+    rate: f64,
+    log: CensusLog,
+    seed: u64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        url: String::from("ws://localhost:8000/"),
+        bots: 1,
+        name: String::from("Bot {}"),
+        policy: String::from("wander"),
+        rate: 30.,
+        log: CensusLog::Summary,
+        seed: 1,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            return Err(String::new());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        let invalid = || format!("Invalid value for {}: {}", arg, value);
+        match arg.as_str() {
+            "--url" => options.url = value.clone(),
+            "--bots" => options.bots = value.parse().map_err(|_| invalid())?,
+            "--name" => options.name = value.clone(),
+            "--policy" => options.policy = value.clone(),
+            "--rate" => options.rate = value.parse().map_err(|_| invalid())?,
+            "--seed" => options.seed = value.parse().map_err(|_| invalid())?,
+            "--log" => {
+                options.log = match value.as_str() {
+                    "none" => CensusLog::None,
+                    "summary" => CensusLog::Summary,
+                    "full" => CensusLog::Full,
+                    _ => return Err(format!("Invalid value for --log: {}", value)),
+                }
+            }
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if bot::policy_from_name(&options.policy, 1).is_none() {
+        return Err(format!("Unknown policy: {}", options.policy));
+    }
+    if !(options.rate.is_finite() && options.rate > 0. && options.rate <= MAX_RATE) {
+        return Err(format!("--rate must be above 0 and at most {}", MAX_RATE));
+    }
+    Ok(options)
+}
+
+fn send<P: Protocol>(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, packet: &P) -> bool {
+    socket
+        .send(Message::Binary(packet.encode().cursor.into_inner()))
+        .is_ok()
+}
+
+fn log_census(index: u32, view: &BotView, mode: CensusLog) {
+    let census = match &view.census {
+        Some(census) => census,
+        None => return,
+    };
+    match mode {
+        CensusLog::None => {}
+        CensusLog::Summary => {
+            let (mut tanks, mut shapes, mut bullets) = (0, 0, 0);
+            for entity in census.entities.values() {
+                match entity {
+                    protocol::Entity::Tank(_) => tanks += 1,
+                    protocol::Entity::Shape(_) => shapes += 1,
+                    protocol::Entity::Bullet(_) => bullets += 1,
+                }
+            }
+            println!(
+                "[bot {}] census: arena {} level {:.2} at ({:.0}, {:.0}), {} tanks, {} cacti, {} bullets",
+                index,
+                census.arena_size,
+                census.level,
+                view.position.x,
+                view.position.y,
+                tanks,
+                shapes,
+                bullets
+            );
+        }
+        CensusLog::Full => println!("[bot {}] {:?}", index, census),
+    }
+}
+
+fn run_bot(index: u32, options: Options) {
+    let name = options.name.replace("{}", &index.to_string());
+    let (mut socket, _) = match tungstenite::connect(options.url.as_str()) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("[bot {}] failed to connect to {}: {}", index, options.url, e);
+            return;
+        }
+    };
+
+    // Reads must not block the input loop.
+    let interval = Duration::from_secs_f64(1. / options.rate);
+    if let MaybeTlsStream::Plain(stream) = socket.get_mut() {
+        stream
+            .set_read_timeout(Some(interval / 4))
+            .expect("Failed to set read timeout");
+    }
+
+    send(&mut socket, &protocol::InitPacket { name: name.clone() });
+    println!("[bot {}] connected as {:?}", index, name);
+
+    let mut policy = bot::policy_from_name(&options.policy, options.seed + index as u64).unwrap();
+    let mut view = BotView::new();
+    let mut next_input = Instant::now();
+
+    loop {
+        match socket.read() {
+            Ok(Message::Binary(data)) => match view.handle(data) {
+                Some(protocol::Packet::Census) => log_census(index, &view, options.log),
+                Some(protocol::Packet::Death) => {
+                    println!("[bot {}] died, respawning", index);
+                    send(&mut socket, &protocol::RespawnPacket);
+                    view.alive = true;
+                }
+                _ => {}
+            },
+            Ok(Message::Close(_)) => {
+                println!("[bot {}] server closed the connection", index);
+                return;
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(ref e))
+                if e.kind() == std::io::ErrorKind::WouldBlock
+                    || e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(e) => {
+                eprintln!("[bot {}] connection error: {}", index, e);
+                return;
+            }
+        }
+
+        if Instant::now() >= next_input {
+            next_input += interval;
+            let input = policy.next_input(&view);
+            if !send(&mut socket, &protocol::InputPacket::from_input(input)) {
+                eprintln!("[bot {}] failed to send input", index);
+                return;
+            }
+        }
+    }
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("{}\n", e);
+            }
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let handles: Vec<_> = (0..options.bots)
+        .map(|index| {
+            let options = options.clone();
+            thread::spawn(move || run_bot(index, options))
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().ok();
+    }
+}
@@ -0,0 +1,308 @@
+//! Packet inspector: decodes captured packets with the `protocol` types and prints them.
+//!
+//! Takes hex dumps (one packet per line), raw packets (one per file) or replay files.
+//!
+//! ```text
+//! cargo run --bin inspect -- --packet census --entity 12 session.cwr
+//! ```
+
+// The inspector reads files, so there is nothing to build for the web.
+#![cfg_attr(target_arch = "wasm32", no_main)]
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::io::Read;
+
+use cactuswar_client::inspect::{self, Dissection};
+use cactuswar_client::protocol::{self, Protocol};
+use cactuswar_client::replay::{self, Record, Replay};
+use serde::Serialize;
+
+const USAGE: &str = "Usage: inspect [options] <file>...
+
+Reads packets from each file (`-` for stdin) and prints them decoded. Exits with 1 if any
+packet failed to decode.
+
+Options:
+    --format <format>   auto, hex, raw or replay (default: auto)
+                        hex: one packet per line, `#` starts a comment
+                        raw: the whole file is a single packet
+                        replay: a recording made with F8
+    --json              Print one JSON object per packet
+    --packet <packets>  Only show these packets, by name or id (comma separated)
+    --entity <ids>      Only show these entities of a census, and censuses that contain them
+                        (comma separated)";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Auto,
+    Hex,
+    Raw,
+    Replay,
+}
+
+struct Options {
+    format: Format,
+    json: bool,
+    packets: Vec<u8>,
+    entities: Vec<u32>,
+    files: Vec<String>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        format: Format::Auto,
+        json: false,
+        packets: Vec::new(),
+        entities: Vec::new(),
+        files: Vec::new(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => return Err(String::new()),
+            "--json" => options.json = true,
+            "--format" | "--packet" | "--entity" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("Missing value for {}", arg))?;
+                let invalid = |item: &str| format!("Invalid value for {}: {}", arg, item);
+                match arg.as_str() {
+                    "--format" => {
+                        options.format = match value.as_str() {
+                            "auto" => Format::Auto,
+                            "hex" => Format::Hex,
+                            "raw" => Format::Raw,
+                            "replay" => Format::Replay,
+                            _ => return Err(invalid(&value)),
+                        }
+                    }
+                    "--packet" => {
+                        for item in value.split(',') {
+                            let id =
+                                inspect::packet_id(item.trim()).ok_or_else(|| invalid(item))?;
+                            options.packets.push(id);
+                        }
+                    }
+                    _ => {
+                        for item in value.split(',') {
+                            let id = item.trim().parse().map_err(|_| invalid(item))?;
+                            options.entities.push(id);
+                        }
+                    }
+                }
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ => options.files.push(arg),
+        }
+    }
+
+    if options.files.is_empty() {
+        return Err(String::from("No input files"));
+    }
+    Ok(options)
+}
+
+/// A packet read from an input file.
+struct Captured {
+    /// The line number for hex dumps, or the record number for replays.
+    index: usize,
+    /// Milliseconds since the start of the recording, for replays.
+    time: Option<f64>,
+    data: Vec<u8>,
+}
+
+fn parse_hex(line: &str) -> Result<Vec<u8>, String> {
+    let digits: String = line
+        .replace("0x", "")
+        .chars()
+        .filter(|c| !c.is_whitespace() && !matches!(c, ',' | ':' | '-'))
+        .collect();
+    if !digits.len().is_multiple_of(2) {
+        return Err(String::from("odd number of hex digits"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("invalid hex {:?} at column {}", &digits[i..i + 2], i + 1))
+        })
+        .collect()
+}
+
+fn looks_like_hex(data: &[u8]) -> bool {
+    data.split(|byte| *byte == b'\n').all(|line| {
+        line.split(|byte| *byte == b'#')
+            .next()
+            .unwrap_or(&[])
+            .iter()
+            .all(|byte| {
+                byte.is_ascii_hexdigit()
+                    || byte.is_ascii_whitespace()
+                    || matches!(byte, b'x' | b',' | b':' | b'-')
+            })
+    })
+}
+
+fn read_packets(data: Vec<u8>, format: Format) -> Result<Vec<Captured>, String> {
+    let format = match format {
+        Format::Auto if data.starts_with(replay::MAGIC) => Format::Replay,
+        Format::Auto if !data.is_empty() && looks_like_hex(&data) => Format::Hex,
+        Format::Auto => Format::Raw,
+        format => format,
+    };
+
+    match format {
+        Format::Hex => {
+            let text = String::from_utf8(data).map_err(|_| String::from("not a text file"))?;
+            let mut packets = Vec::new();
+            for (index, line) in text.lines().enumerate() {
+                let line = line.split('#').next().unwrap_or("").trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let data = parse_hex(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+                packets.push(Captured {
+                    index: index + 1,
+                    time: None,
+                    data,
+                });
+            }
+            Ok(packets)
+        }
+        Format::Replay => {
+            let replay = Replay::decode(data).map_err(|e| e.to_string())?;
+            let mut packets = vec![Captured {
+                index: 0,
+                time: Some(0.),
+                data: replay.handshake.clone(),
+            }];
+            for (index, entry) in replay.entries.iter().enumerate() {
+                let data = match &entry.record {
+                    Record::Packet(data) => data.clone(),
+                    Record::Input(input) => protocol::InputPacket::from_input(*input)
+                        .encode()
+                        .cursor
+                        .into_inner(),
+                };
+                packets.push(Captured {
+                    index: index + 1,
+                    time: Some(entry.time),
+                    data,
+                });
+            }
+            Ok(packets)
+        }
+        _ => Ok(vec![Captured {
+            index: 0,
+            time: None,
+            data,
+        }]),
+    }
+}
+
+#[derive(Serialize)]
+struct JsonLine<'a> {
+    source: &'a str,
+    index: usize,
+    time: Option<f64>,
+    #[serde(flatten)]
+    dissection: &'a Dissection,
+}
+
+fn print_packet(source: &str, captured: &Captured, dissection: &Dissection, json: bool) {
+    if json {
+        let line = JsonLine {
+            source,
+            index: captured.index,
+            time: captured.time,
+            dissection,
+        };
+        println!(
+            "{}",
+            serde_json::to_string(&line).expect("Failed to serialize")
+        );
+        return;
+    }
+
+    let time = match captured.time {
+        Some(time) => format!(" @ {:.3}ms", time),
+        None => String::new(),
+    };
+    let id = match dissection.id {
+        Some(id) => id.to_string(),
+        None => String::from("-"),
+    };
+    println!(
+        "{}#{}{}: {} (id {}, {} bytes)",
+        source,
+        captured.index,
+        time,
+        dissection.name.unwrap_or("unknown"),
+        id,
+        dissection.length
+    );
+    if let Some(packet) = &dissection.packet {
+        println!("{:#?}", packet);
+    }
+    if let Some(error) = &dissection.error {
+        println!("!! decode error: {}", error);
+    }
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("{}\n", e);
+            }
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let mut failed = false;
+    for file in &options.files {
+        let mut data = Vec::new();
+        let read = if file == "-" {
+            std::io::stdin().read_to_end(&mut data).map(|_| ())
+        } else {
+            std::fs::read(file).map(|contents| data = contents)
+        };
+        if let Err(e) = read {
+            eprintln!("{}: {}", file, e);
+            failed = true;
+            continue;
+        }
+
+        let packets = match read_packets(data, options.format) {
+            Ok(packets) => packets,
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        for captured in &packets {
+            let mut dissection = inspect::dissect(&captured.data);
+            failed |= dissection.error.is_some();
+            if !options.packets.is_empty()
+                && !dissection
+                    .id
+                    .is_some_and(|id| options.packets.contains(&id))
+            {
+                continue;
+            }
+            if !options.entities.is_empty() && !dissection.retain_entities(&options.entities) {
+                continue;
+            }
+            print_packet(file, captured, &dissection, options.json);
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
@@ -5,13 +5,59 @@ use std::io::Write;
 use std::io::Cursor;
 use std::io::Read;
 
+use serde::Serialize;
+
+/// Something that went wrong while reading from a `StreamPeerBuffer`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DecodeError {
+    /// The offset of the value that could not be read.
+    pub offset: usize,
+    pub message: String,
+    /// Whether the data ended too soon, rather than holding something that can't be read.
+    #[serde(skip)]
+    pub truncated: bool,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
 pub struct StreamPeerBuffer {
     pub cursor: Cursor<Vec<u8>>,
+    /// The first decode error. Reads past the end return zeroes instead of failing, so this is
+    /// the only way to tell that a packet was malformed.
+    pub error: Option<DecodeError>,
 }
 impl StreamPeerBuffer {
     pub fn new() -> StreamPeerBuffer {
         StreamPeerBuffer {
             cursor: Cursor::new(Vec::new()),
+            error: None,
+        }
+    }
+
+    /// Record a decode error at `offset`, unless an earlier one was recorded already.
+    pub fn fail(&mut self, offset: usize, message: String) {
+        if self.error.is_none() {
+            self.error = Some(DecodeError {
+                offset,
+                message,
+                truncated: false,
+            });
+        }
+    }
+
+    fn read_into(&mut self, res: &mut [u8]) {
+        let offset = self.cursor.position() as usize;
+        let read = self.cursor.read(res).expect("Failed to read bytes");
+        if read < res.len() && self.error.is_none() {
+            self.error = Some(DecodeError {
+                offset,
+                message: format!("{} byte read runs past the end of the data", res.len()),
+                truncated: true,
+            });
         }
     }
 
@@ -84,33 +130,36 @@ impl StreamPeerBuffer {
 
     // minecraft encodings
 
-    pub fn put_varint_mc(&mut self, mut value: i32) {
-        while value != 0 {
+    pub fn put_varint_mc(&mut self, value: i32) {
+        // Shift as unsigned so negative numbers terminate, and always write at least one byte
+        // so that zero can be read back.
+        let mut value = value as u32;
+        loop {
             let mut temp: u8 = (value & 0b01111111) as u8;
-            value = value >> 7;
+            value >>= 7;
             if value != 0 {
-                temp = temp | 0b10000000;
+                temp |= 0b10000000;
             }
             self.put_u8(temp);
+            if value == 0 {
+                break;
+            }
         }
     }
 
+    /// Read a varint of at most 5 bytes. A longer one is a decode error.
     pub fn get_varint_mc(&mut self) -> i32 {
-        let mut numRead: i32 = 0;
-        let mut result: i32 = 0;
-        let mut read: u8 = 255;
-
-        while (read & 0b10000000) != 0 {
-            read = self.get_u8();
-            let value: i32 = (read & 0b01111111) as i32;
-            result = result | (value << (7 * numRead));
-
-            numRead += 1;
-            if numRead > 5 {
-                panic!("VarInt is too big");
+        let offset = self.cursor.position() as usize;
+        let mut result: u32 = 0;
+        for shift in (0..35).step_by(7) {
+            let read = self.get_u8();
+            result |= ((read & 0b01111111) as u32) << shift;
+            if read & 0b10000000 == 0 {
+                return result as i32;
             }
         }
-        result
+        self.fail(offset, String::from("varint is longer than 5 bytes"));
+        result as i32
     }
 
     pub fn put_varlong(&mut self, mut value: i64) {
@@ -123,22 +172,20 @@ impl StreamPeerBuffer {
             self.put_u8(temp);
         }
     }
+
+    /// Read a varlong of at most 10 bytes. A longer one is a decode error.
     pub fn get_varlong(&mut self) -> i64 {
-        let mut numread: i32 = 0;
-        let mut result: i64 = 0;
-        let mut read: u8 = 255;
-        while (read & 0b10000000) != 0 {
-            read = self.get_u8();
-            let value = (read & 0b01111111) as i64;
-            result = result | (value << (7 * numread));
-
-            numread += 1;
-            if numread > 10 {
-                panic!("Varlong is too big")
+        let offset = self.cursor.position() as usize;
+        let mut result: u64 = 0;
+        for shift in (0..70).step_by(7) {
+            let read = self.get_u8();
+            result |= ((read & 0b01111111) as u64) << shift;
+            if read & 0b10000000 == 0 {
+                return result as i64;
             }
         }
-
-        result
+        self.fail(offset, String::from("varlong is longer than 10 bytes"));
+        result as i64
     }
 
     pub fn put_varint_utf8(&mut self, value: String) {
@@ -150,75 +197,80 @@ impl StreamPeerBuffer {
     }
 
     pub fn get_varint_utf8(&mut self) -> String {
+        let offset = self.cursor.position() as usize;
         let length = self.get_varint_mc();
         let mut buf: Vec<u8> = vec![];
 
-        for _ in 0..length {
+        // a length longer than the data stops one byte past its end, which records the error
+        for _ in 0..length.min(self.remaining() as i32 + 1) {
             buf.push(self.get_u8());
         }
 
-        String::from_utf8(buf).expect("Invalid utf8 in buf")
+        String::from_utf8(buf).unwrap_or_else(|_| {
+            self.fail(offset, String::from("invalid utf8 in string"));
+            String::from("[Invalid Name]")
+        })
     }
 
     // end of minecraft encodings
 
     pub fn get_u8(&mut self) -> u8 {
         let mut res: [u8; 1] = [0; 1];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         res[0]
     }
 
     pub fn get_u16(&mut self) -> u16 {
         let mut res: [u8; 2] = [0; 2];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         u16::from_be_bytes(res)
     }
 
     pub fn get_u32(&mut self) -> u32 {
         let mut res: [u8; 4] = [0; 4];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         u32::from_be_bytes(res)
     }
 
     pub fn get_u64(&mut self) -> u64 {
         let mut res: [u8; 8] = [0; 8];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         u64::from_be_bytes(res)
     }
 
     pub fn get_8(&mut self) -> i8 {
         let mut res: [u8; 1] = [0; 1];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         res[0] as i8
     }
 
     pub fn get_16(&mut self) -> i16 {
         let mut res: [u8; 2] = [0; 2];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         i16::from_be_bytes(res)
     }
 
     pub fn get_32(&mut self) -> i32 {
         let mut res: [u8; 4] = [0; 4];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         i32::from_be_bytes(res)
     }
 
     pub fn get_64(&mut self) -> i64 {
         let mut res: [u8; 8] = [0; 8];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         i64::from_be_bytes(res)
     }
 
     pub fn get_float(&mut self) -> f32 {
         let mut res: [u8; 4] = [0; 4];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         f32::from_be_bytes(res)
     }
 
     pub fn get_double(&mut self) -> f64 {
         let mut res: [u8; 8] = [0; 8];
-        self.cursor.read(&mut res).expect("Failed to read bytes");
+        self.read_into(&mut res);
         f64::from_be_bytes(res)
     }
 
@@ -232,6 +284,7 @@ impl StreamPeerBuffer {
     }
 
     pub fn get_utf8(&mut self) -> String {
+        let offset = self.cursor.position() as usize;
         let length = self.get_u16();
         let mut buf: Vec<u8> = vec![];
 
@@ -239,10 +292,20 @@ impl StreamPeerBuffer {
             buf.push(self.get_u8());
         }
 
-        String::from_utf8(buf).unwrap_or(String::from("[Invalid Name]"))
+        String::from_utf8(buf).unwrap_or_else(|_| {
+            self.fail(offset, String::from("invalid utf8 in string"));
+            String::from("[Invalid Name]")
+        })
+    }
+
+    /// The amount of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        let len = self.cursor.get_ref().len() as u64;
+        len.saturating_sub(self.cursor.position()) as usize
     }
 
     pub fn set_data_array(&mut self, new_data: Vec<u8>) {
+        self.error = None;
         let cursor = Cursor::new(Vec::new());
         //drop(self.cursor);
         self.cursor = cursor;
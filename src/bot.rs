@@ -0,0 +1,240 @@
+//! Headless bots for automated play and load testing.
+//!
+//! A bot keeps a `BotView` of the arena, built from the packets the server sends it, and asks a
+//! `Policy` which `engine::Input` to send next. The networking side lives in `src/bin/bot.rs`.
+
+use crate::binary;
+use crate::engine::Input;
+use crate::protocol::{self, Protocol};
+use crate::util::{Rng, Vector2};
+use num_traits::FromPrimitive;
+
+/// Everything a bot knows about the arena.
+pub struct BotView {
+    pub id: u32,
+    pub position: Vector2<f64>,
+    pub arena_size: u16,
+    pub level: f32,
+    pub alive: bool,
+    pub mockups: Vec<protocol::TankMockup>,
+    /// The most recent census.
+    pub census: Option<protocol::Census>,
+}
+
+impl BotView {
+    pub fn new() -> Self {
+        Self {
+            id: 0,
+            position: Vector2 { x: 0., y: 0. },
+            arena_size: 0,
+            level: 1.,
+            alive: true,
+            mockups: Vec::new(),
+            census: None,
+        }
+    }
+
+    /// Decode a packet from the server and update the view.
+    /// Returns the type of the packet, or `None` if the id was unknown.
+    pub fn handle(&mut self, data: Vec<u8>) -> Option<protocol::Packet> {
+        let mut buf = binary::StreamPeerBuffer::new();
+        buf.set_data_array(data);
+        let packet = FromPrimitive::from_u8(buf.get_u8());
+        match packet {
+            Some(protocol::Packet::Census) => {
+                let census = protocol::Census::decode(&mut buf);
+                self.arena_size = census.arena_size;
+                self.level = census.level;
+                if let Some(protocol::Entity::Tank(t)) = census.entities.get(&self.id) {
+                    self.position = t.position.into();
+                }
+                self.census = Some(census);
+            }
+            Some(protocol::Packet::Handshake) => {
+                let handshake = protocol::HandshakePacket::decode(&mut buf);
+                self.id = handshake.id;
+                self.mockups = handshake.mockups;
+            }
+            Some(protocol::Packet::Death) => self.alive = false,
+            _ => {}
+        }
+        packet
+    }
+
+    /// Every tank in view apart from ourselves.
+    pub fn tanks(&self) -> impl Iterator<Item = &protocol::TankPacket> {
+        let id = self.id;
+        self.census
+            .iter()
+            .flat_map(|census| census.entities.values())
+            .filter_map(move |entity| match entity {
+                protocol::Entity::Tank(t) if t.id != id => Some(t),
+                _ => None,
+            })
+    }
+
+    /// Every cactus in view.
+    pub fn shapes(&self) -> impl Iterator<Item = &protocol::ShapePacket> {
+        self.census
+            .iter()
+            .flat_map(|census| census.entities.values())
+            .filter_map(|entity| match entity {
+                protocol::Entity::Shape(s) => Some(s),
+                _ => None,
+            })
+    }
+
+    fn distance_to(&self, position: Vector2<i16>) -> f64 {
+        Vector2::from(position).distance(self.position)
+    }
+}
+
+impl Default for BotView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decides what a bot does every time it sends input.
+pub trait Policy: Send {
+    fn next_input(&mut self, view: &BotView) -> Input;
+}
+
+/// Look up a policy by the name used on the command line.
+pub fn policy_from_name(name: &str, seed: u64) -> Option<Box<dyn Policy>> {
+    match name {
+        "wander" => Some(Box::new(Wander::new(seed))),
+        "chase" => Some(Box::new(Chase::new(seed))),
+        "farm" => Some(Box::new(Farm::new(seed))),
+        _ => None,
+    }
+}
+
+/// How close a bot has to be to its destination before it stops pressing keys.
+const DEADZONE: f64 = 50.;
+
+/// Press the keys that move us from `from` towards `to`.
+fn move_towards(input: &mut Input, from: Vector2<f64>, to: Vector2<f64>) {
+    input.W = to.y < from.y - DEADZONE;
+    input.S = to.y > from.y + DEADZONE;
+    input.A = to.x < from.x - DEADZONE;
+    input.D = to.x > from.x + DEADZONE;
+}
+
+fn aim_at(input: &mut Input, target: Vector2<i16>) {
+    input.mouse_position = target;
+}
+
+/// Walks in a random direction, picking a new one every few seconds and turning back
+/// towards the middle of the arena near the edges.
+pub struct Wander {
+    rng: Rng,
+    heading: f64,
+    ticks_left: u32,
+}
+
+impl Wander {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            heading: 0.,
+            ticks_left: 0,
+        }
+    }
+}
+
+impl Policy for Wander {
+    fn next_input(&mut self, view: &BotView) -> Input {
+        let size = view.arena_size as f64;
+        let margin = size * 0.1;
+        let near_edge = view.position.x < margin
+            || view.position.y < margin
+            || view.position.x > size - margin
+            || view.position.y > size - margin;
+
+        if near_edge {
+            self.heading = (size / 2. - view.position.y).atan2(size / 2. - view.position.x);
+            self.ticks_left = 30;
+        } else if self.ticks_left == 0 {
+            self.heading = self.rng.range(-std::f64::consts::PI, std::f64::consts::PI);
+            self.ticks_left = self.rng.range(30., 150.) as u32;
+        }
+        self.ticks_left = self.ticks_left.saturating_sub(1);
+
+        let destination = view.position + Vector2::from_angle(self.heading) * 500.;
+        let mut input = Input::new();
+        move_towards(&mut input, view.position, destination);
+        aim_at(&mut input, destination.into());
+        input.mouse_down = self.rng.next_f64() < 0.2;
+        input
+    }
+}
+
+/// Hunts the nearest tank, wandering when nobody is in view.
+pub struct Chase {
+    wander: Wander,
+}
+
+impl Chase {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            wander: Wander::new(seed),
+        }
+    }
+}
+
+impl Policy for Chase {
+    fn next_input(&mut self, view: &BotView) -> Input {
+        let target = view
+            .tanks()
+            .map(|t| t.position)
+            .min_by(|a, b| view.distance_to(*a).total_cmp(&view.distance_to(*b)));
+        match target {
+            Some(target) => {
+                let mut input = Input::new();
+                // keep some distance so the bullets have time to land
+                if view.distance_to(target) > 400. {
+                    move_towards(&mut input, view.position, target.into());
+                }
+                aim_at(&mut input, target);
+                input.mouse_down = true;
+                input
+            }
+            None => self.wander.next_input(view),
+        }
+    }
+}
+
+/// Shoots the nearest cactus for experience, wandering when none are in view.
+pub struct Farm {
+    wander: Wander,
+}
+
+impl Farm {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            wander: Wander::new(seed),
+        }
+    }
+}
+
+impl Policy for Farm {
+    fn next_input(&mut self, view: &BotView) -> Input {
+        let target = view
+            .shapes()
+            .map(|s| (s.position, s.radius))
+            .min_by(|a, b| view.distance_to(a.0).total_cmp(&view.distance_to(b.0)));
+        match target {
+            Some((position, radius)) => {
+                let mut input = Input::new();
+                if view.distance_to(position) > radius as f64 + 600. {
+                    move_towards(&mut input, view.position, position.into());
+                }
+                aim_at(&mut input, position);
+                input.mouse_down = true;
+                input
+            }
+            None => self.wander.next_input(view),
+        }
+    }
+}
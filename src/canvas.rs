@@ -0,0 +1,221 @@
+//! The Canvas 2D backend, which draws in the browser.
+
+use crate::geometry::Aabb;
+use crate::renderer::{BlendMode, LineJoin, RadialGradient, Renderer, Texture};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+pub struct CanvasRenderer {
+    ctx: CanvasRenderingContext2d,
+}
+
+impl CanvasRenderer {
+    pub fn new(ctx: CanvasRenderingContext2d) -> Self {
+        Self { ctx }
+    }
+
+    pub fn context(&self) -> &CanvasRenderingContext2d {
+        &self.ctx
+    }
+}
+
+/// The 2D context of a canvas.
+fn context_of(canvas: &HtmlCanvasElement) -> CanvasRenderingContext2d {
+    canvas
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<CanvasRenderingContext2d>()
+        .unwrap()
+}
+
+impl Renderer for CanvasRenderer {
+    fn save(&mut self) {
+        self.ctx.save();
+    }
+
+    fn restore(&mut self) {
+        self.ctx.restore();
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        self.ctx.translate(x, y);
+    }
+
+    fn rotate(&mut self, angle: f64) {
+        self.ctx.rotate(angle);
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        self.ctx.scale(x, y);
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        self.ctx.set_global_alpha(alpha);
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        let operation = match mode {
+            BlendMode::Normal => "source-over",
+            BlendMode::Lighter => "lighter",
+        };
+        self.ctx
+            .set_global_composite_operation(operation)
+            .expect("Failed to set composite operation");
+    }
+
+    fn set_fill_color(&mut self, color: &str) {
+        self.ctx.set_fill_style(v8!(color));
+    }
+
+    fn set_fill_gradient(&mut self, gradient: &RadialGradient) {
+        let grd = self
+            .ctx
+            .create_radial_gradient(
+                gradient.x0,
+                gradient.y0,
+                gradient.r0,
+                gradient.x1,
+                gradient.y1,
+                gradient.r1,
+            )
+            .unwrap();
+        for (offset, color) in &gradient.stops {
+            grd.add_color_stop(*offset as f32, color);
+        }
+        self.ctx.set_fill_style(v8!(grd));
+    }
+
+    fn set_stroke_color(&mut self, color: &str) {
+        self.ctx.set_stroke_style(v8!(color));
+    }
+
+    fn set_line_width(&mut self, width: f64) {
+        self.ctx.set_line_width(width);
+    }
+
+    fn set_line_join(&mut self, join: LineJoin) {
+        self.ctx.set_line_join(match join {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        });
+    }
+
+    fn set_shadow_blur(&mut self, blur: f64) {
+        self.ctx.set_shadow_blur(blur);
+    }
+
+    fn set_shadow_color(&mut self, color: &str) {
+        self.ctx.set_shadow_color(color);
+    }
+
+    fn set_font(&mut self, font: &str) {
+        self.ctx.set_font(font);
+    }
+
+    fn begin_path(&mut self) {
+        self.ctx.begin_path();
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.ctx.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.ctx.line_to(x, y);
+    }
+
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        self.ctx.arc(x, y, radius, start, end);
+    }
+
+    fn close_path(&mut self) {
+        self.ctx.close_path();
+    }
+
+    fn fill(&mut self) {
+        self.ctx.fill();
+    }
+
+    fn stroke(&mut self) {
+        self.ctx.stroke();
+    }
+
+    fn clip(&mut self) {
+        self.ctx.clip();
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.fill_rect(x, y, width, height);
+    }
+
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.stroke_rect(x, y, width, height);
+    }
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        self.ctx.clear_rect(x, y, width, height);
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64) {
+        self.ctx.fill_text(text, x, y);
+    }
+
+    fn stroke_text(&mut self, text: &str, x: f64, y: f64) {
+        self.ctx.stroke_text(text, x, y);
+    }
+
+    fn measure_text(&self, text: &str) -> f64 {
+        self.ctx.measure_text(text).unwrap().width()
+    }
+
+    fn create_texture(&mut self, width: u32, height: u32) -> Texture {
+        let canvas = crate::document()
+            .create_element("canvas")
+            .unwrap()
+            .dyn_into::<HtmlCanvasElement>()
+            .map_err(|_| ())
+            .unwrap();
+        canvas.set_width(width);
+        canvas.set_height(height);
+        Texture::new(width, height, canvas)
+    }
+
+    fn draw_to_texture(&mut self, texture: &Texture, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        let canvas = texture
+            .data::<HtmlCanvasElement>()
+            .expect("Texture wasn't created by a canvas");
+        draw(&mut CanvasRenderer::new(context_of(canvas)));
+    }
+
+    fn owns_texture(&self, texture: &Texture) -> bool {
+        texture.data::<HtmlCanvasElement>().is_some()
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(canvas) = texture.data::<HtmlCanvasElement>() {
+            self.ctx
+                .draw_image_with_html_canvas_element_and_dw_and_dh(canvas, x, y, width, height);
+        }
+    }
+
+    fn draw_texture_region(&mut self, texture: &Texture, source: Aabb, destination: Aabb) {
+        if let Some(canvas) = texture.data::<HtmlCanvasElement>() {
+            let (from, to) = (source.size(), destination.size());
+            self.ctx
+                .draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(
+                    canvas,
+                    source.min.x,
+                    source.min.y,
+                    from.x,
+                    from.y,
+                    destination.min.x,
+                    destination.min.y,
+                    to.x,
+                    to.y,
+                );
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Colors, and the shading the draw functions do with them.
+//!
+//! `Color` does what `pSBC` ("Shade, Blend and Convert a Web Color") used to do from JavaScript,
+//! in its linear mode, down to the last digit of its output: the same parsing, the same rounding,
+//! and a result in the notation of the color it came from (`#rrggbb[aa]` for hex colors,
+//! `rgb[a]()` otherwise).
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::cell::OnceCell;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// How a color is written out.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Notation {
+    /// `#rrggbb` or `#rrggbbaa`.
+    Hex,
+    /// `rgb(r,g,b)` or `rgba(r,g,b,a)`.
+    Rgb,
+}
+
+/// An sRGB color, with an alpha from 0 to 1 if it has one.
+#[derive(Clone)]
+pub struct Color {
+    r: u8,
+    g: u8,
+    b: u8,
+    alpha: Option<f64>,
+    notation: Notation,
+    /// `css()`, formatted the first time it's asked for.
+    css: OnceCell<String>,
+}
+
+/// `Math.round`, which rounds halves up rather than away from zero.
+fn round(x: f64) -> f64 {
+    (x + 0.5).floor()
+}
+
+/// Like `parseInt` and `parseFloat`, read as much of a number as there is after any spaces.
+fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim_start();
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(s.len());
+    s[..end].parse().ok()
+}
+
+fn parse_channel(s: &str) -> Option<u8> {
+    let channel = parse_number(s)?.trunc();
+    if (0. ..=255.).contains(&channel) {
+        Some(channel as u8)
+    } else {
+        None
+    }
+}
+
+impl Color {
+    fn new(r: u8, g: u8, b: u8, alpha: Option<f64>, notation: Notation) -> Self {
+        // keep the alpha to what the notation can hold, so that shading a color gives the same
+        // result as shading its CSS
+        let alpha = alpha.map(|alpha| match notation {
+            Notation::Hex => round(round(alpha * 255.) / 0.255) / 1000.,
+            Notation::Rgb => round(alpha * 1000.) / 1000.,
+        });
+        Self {
+            r,
+            g,
+            b,
+            alpha,
+            notation,
+            css: OnceCell::new(),
+        }
+    }
+
+    /// A color like `0x3ea832`, written as `#3ea832`.
+    pub fn hex(rgb: u32) -> Self {
+        Self::new(
+            (rgb >> 16) as u8,
+            (rgb >> 8) as u8,
+            rgb as u8,
+            None,
+            Notation::Hex,
+        )
+    }
+
+    /// A color with alpha like `0x121212aa`, written as `#121212aa`.
+    pub fn hex_with_alpha(rgba: u32) -> Self {
+        Self::new(
+            (rgba >> 24) as u8,
+            (rgba >> 16) as u8,
+            (rgba >> 8) as u8,
+            Some((rgba & 255) as f64 / 255.),
+            Notation::Hex,
+        )
+    }
+
+    /// A color written as `rgba(r,g,b,alpha)`.
+    pub fn rgba(r: u8, g: u8, b: u8, alpha: f64) -> Self {
+        Self::new(r, g, b, Some(alpha.clamp(0., 1.)), Notation::Rgb)
+    }
+
+    /// Parse `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()` or `rgba()`.
+    pub fn parse(color: &str) -> Option<Self> {
+        let color = color.trim();
+        // pSBC tells the notations apart by length
+        if color.len() > 9 {
+            let arguments = color
+                .strip_prefix("rgba(")
+                .or_else(|| color.strip_prefix("rgb("))?;
+            let parts: Vec<&str> = arguments.split(',').collect();
+            if parts.len() < 3 || parts.len() > 4 {
+                return None;
+            }
+            let alpha = match parts.get(3) {
+                Some(alpha) => Some(parse_number(alpha)?.clamp(0., 1.)),
+                None => None,
+            };
+            Some(Self::new(
+                parse_channel(parts[0])?,
+                parse_channel(parts[1])?,
+                parse_channel(parts[2])?,
+                alpha,
+                Notation::Rgb,
+            ))
+        } else {
+            let digits = color.strip_prefix('#')?;
+            if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let digits: String = match digits.len() {
+                3 | 4 => digits.chars().flat_map(|c| [c, c]).collect(),
+                6 | 8 => String::from(digits),
+                _ => return None,
+            };
+            let value = u32::from_str_radix(&digits, 16).ok()?;
+            Some(if digits.len() == 8 {
+                Self::hex_with_alpha(value)
+            } else {
+                Self::hex(value)
+            })
+        }
+    }
+
+    /// The red, green and blue channels, and the alpha (1 if the color has none).
+    pub fn channels(&self) -> (u8, u8, u8, f64) {
+        (self.r, self.g, self.b, self.alpha.unwrap_or(1.))
+    }
+
+    /// The color as CSS.
+    pub fn css(&self) -> &str {
+        self.css.get_or_init(|| {
+            let (r, g, b) = (self.r, self.g, self.b);
+            match (self.notation, self.alpha) {
+                (Notation::Hex, None) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+                (Notation::Hex, Some(alpha)) => {
+                    format!(
+                        "#{:02x}{:02x}{:02x}{:02x}",
+                        r,
+                        g,
+                        b,
+                        round(alpha * 255.) as u8
+                    )
+                }
+                (Notation::Rgb, None) => format!("rgb({},{},{})", r, g, b),
+                (Notation::Rgb, Some(alpha)) => format!("rgba({},{},{},{})", r, g, b, alpha),
+            }
+        })
+    }
+
+    /// `amount` (0 to 1) of the way from this color to `other`, in `notation`.
+    fn blend(&self, other: &Color, amount: f64, notation: Notation) -> Color {
+        let amount = amount.clamp(0., 1.);
+        let channel =
+            |from: u8, to: u8| round((1. - amount) * from as f64 + amount * to as f64) as u8;
+        let alpha = match (self.alpha, other.alpha) {
+            (None, None) => None,
+            (Some(alpha), None) | (None, Some(alpha)) => Some(alpha),
+            (Some(from), Some(to)) => Some(from * (1. - amount) + to * amount),
+        };
+        Color::new(
+            channel(self.r, other.r),
+            channel(self.g, other.g),
+            channel(self.b, other.b),
+            alpha,
+            notation,
+        )
+    }
+
+    /// Darken the color, `amount` (0 to 1) of the way to black.
+    pub fn shade(&self, amount: f64) -> Color {
+        self.blend(&Color::hex(0x000000), amount, self.notation)
+    }
+
+    /// Lighten the color, `amount` (0 to 1) of the way to white.
+    pub fn tint(&self, amount: f64) -> Color {
+        self.blend(&Color::hex(0xffffff), amount, self.notation)
+    }
+
+    /// `amount` (0 to 1) of the way from this color to `other`, alpha included, in the notation
+    /// of `other`.
+    pub fn mix(&self, other: &Color, amount: f64) -> Color {
+        self.blend(other, amount, other.notation)
+    }
+
+    /// The same color with another alpha (0 to 1).
+    pub fn with_alpha(&self, alpha: f64) -> Color {
+        Color::new(
+            self.r,
+            self.g,
+            self.b,
+            Some(alpha.clamp(0., 1.)),
+            self.notation,
+        )
+    }
+}
+
+impl PartialEq for Color {
+    fn eq(&self, other: &Self) -> bool {
+        self.css() == other.css()
+    }
+}
+
+impl Eq for Color {}
+
+impl Hash for Color {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.css().hash(state);
+    }
+}
+
+impl fmt::Debug for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Color({})", self.css())
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.css())
+    }
+}
+
+/// Colors are (de)serialized as their CSS.
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.css())
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let css = String::deserialize(deserializer)?;
+        Color::parse(&css).ok_or_else(|| de::Error::custom(format!("invalid color: {}", css)))
+    }
+}
@@ -1,111 +1,98 @@
-use crate::wrapper;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
-use web_sys::*;
+use crate::color::Color;
+use crate::geometry::Aabb;
+use crate::renderer::{LineJoin, RadialGradient, Renderer};
 
 const OUTLINE_WIDTH: f64 = 9.;
 
+/// The color to outline a shape filled with `color` with.
+fn outline_color(color: &Color) -> Color {
+    color.shade(0.4)
+}
+
 /// Draw a light.
 ///
 /// It's recommended that the light color has an opacity of 0.3.
-pub fn draw_light(ctx: &CanvasRenderingContext2d, x: f64, y: f64, r: f64, color: &str) {
+pub fn draw_light(ctx: &mut dyn Renderer, x: f64, y: f64, r: f64, color: &Color) {
     ctx.save();
-    let grd = ctx
-        .create_radial_gradient(r + x, r + y, r / 300., r + x, r + y, r)
-        .unwrap();
-    grd.add_color_stop(0., color);
+    let mut grd = RadialGradient::new(r + x, r + y, r / 300., r + x, r + y, r);
+    grd.add_color_stop(0., color.css());
     grd.add_color_stop(1., "rgba(0,0,0,0)");
-    ctx.set_fill_style(v8!(grd));
+    ctx.set_fill_gradient(&grd);
     ctx.translate(-r, -r);
     ctx.fill_rect(x, y, r * 2., r * 2.);
     ctx.restore();
 }
 
-/// Draw a light with shadows
+/// Draw a light with shadows, lighting only the `lit` polygon (see
+/// `geometry::visibility_polygon`).
 ///
 /// It's recommended that the light color has an opacity of 0.3.
 pub fn draw_light_with_shadows(
-    ctx: &CanvasRenderingContext2d,
-    off_ctx: &CanvasRenderingContext2d,
+    ctx: &mut dyn Renderer,
     x: f64,
     y: f64,
     r: f64,
-    color: &str,
-    shadows: Vec<crate::engine::Quadrilateral>,
-    map_size: u16,
+    color: &Color,
+    lit: &[crate::util::Vector2<f64>],
 ) {
-    draw_light(&off_ctx, x, y, r, color);
-
-    off_ctx.save();
-    off_ctx.begin_path();
-    for shadow in shadows {
-        off_ctx.move_to(shadow.0.x, shadow.0.y);
-        off_ctx.line_to(shadow.1.x, shadow.1.y);
-        off_ctx.line_to(shadow.3.x, shadow.3.y);
-        off_ctx.line_to(shadow.2.x, shadow.2.y);
-        off_ctx.line_to(shadow.0.x, shadow.0.y);
+    if lit.is_empty() {
+        return;
     }
-    off_ctx.clip();
-    off_ctx.clear_rect(
-        -(map_size as f64),
-        -(map_size as f64),
-        map_size as f64 * 4.,
-        map_size as f64 * 4.,
-    );
-    off_ctx.restore();
+    ctx.save();
+    ctx.begin_path();
+    ctx.move_to(lit[0].x, lit[0].y);
+    for point in &lit[1..] {
+        ctx.line_to(point.x, point.y);
+    }
+    ctx.close_path();
+    ctx.clip();
+    draw_light(ctx, x, y, r, color);
+    ctx.restore();
 }
 
 /// Draw a rectangle (with rotation).
-pub fn draw_rect(
-    ctx: &CanvasRenderingContext2d,
-    x: f64,
-    y: f64,
-    w: f64,
-    h: f64,
-    r: f64,
-    color: &str,
-) {
+pub fn draw_rect(ctx: &mut dyn Renderer, x: f64, y: f64, w: f64, h: f64, r: f64, color: &Color) {
     ctx.save();
     ctx.set_line_width(OUTLINE_WIDTH);
-    ctx.set_fill_style(v8!(color));
+    ctx.set_fill_color(color.css());
     ctx.translate(x, y);
     ctx.rotate(r);
     ctx.fill_rect(-w / 2., -h / 2., w, h);
-    ctx.set_stroke_style(&wrapper::pSBC(-0.4, color, false, true)); // mic colors using pSBC
+    ctx.set_stroke_color(outline_color(color).css());
     ctx.stroke_rect((-w + 5.) / 2., (-h + 5.) / 2., w, h);
     ctx.restore();
 }
 
 /// Draw a rectangle (with rotation).
 pub fn draw_rect_no_correction(
-    ctx: &CanvasRenderingContext2d,
+    ctx: &mut dyn Renderer,
     x: f64,
     y: f64,
     w: f64,
     h: f64,
     r: f64,
-    color: &str,
+    color: &Color,
 ) {
     ctx.save();
     ctx.set_line_width(OUTLINE_WIDTH);
-    ctx.set_fill_style(v8!(color));
+    ctx.set_fill_color(color.css());
     ctx.translate(x, y);
     ctx.rotate(r);
     ctx.fill_rect(0., 0., w, h);
-    ctx.set_stroke_style(&wrapper::pSBC(-0.4, color, false, true)); // mic colors using pSBC
+    ctx.set_stroke_color(outline_color(color).css());
     ctx.stroke_rect(-5. / 2., -5. / 2., w, h);
     ctx.restore();
 }
 
 /// Draw a curved line between 2 points
-pub fn draw_bar(ctx: &CanvasRenderingContext2d, x1: f64, x2: f64, y: f64, width: f64, color: &str) {
+pub fn draw_bar(ctx: &mut dyn Renderer, x1: f64, x2: f64, y: f64, width: f64, color: &Color) {
     ctx.save();
-    ctx.set_line_join("round");
+    ctx.set_line_join(LineJoin::Round);
     ctx.begin_path();
     ctx.line_to(x1, y);
     ctx.line_to(x2, y);
     ctx.set_line_width(width);
-    ctx.set_stroke_style(v8!(color));
+    ctx.set_stroke_color(color.css());
     ctx.close_path();
     ctx.stroke();
     ctx.restore();
@@ -113,49 +100,50 @@ pub fn draw_bar(ctx: &CanvasRenderingContext2d, x1: f64, x2: f64, y: f64, width:
 
 /// Draw a rectangle (without rotation).
 pub fn draw_rect_no_rotation(
-    ctx: &CanvasRenderingContext2d,
+    ctx: &mut dyn Renderer,
     x: f64,
     y: f64,
     w: f64,
     h: f64,
-    color: &str,
+    color: &Color,
 ) {
     ctx.save();
     ctx.set_line_width(OUTLINE_WIDTH);
-    ctx.set_fill_style(v8!(color));
+    ctx.set_fill_color(color.css());
     ctx.translate(x, y);
     ctx.fill_rect(-w / 2., -h / 2., w, h);
-    ctx.set_stroke_style(&wrapper::pSBC(-0.4, color, false, true)); // mic colors using pSBC
+    ctx.set_stroke_color(outline_color(color).css());
     ctx.stroke_rect((-w + 5.) / 2., (-h + 5.) / 2., w, h);
     ctx.restore();
 }
 
-/// Draw a grid.
-pub fn draw_grid(ctx: &CanvasRenderingContext2d, width: f64, height: f64) {
-    ctx.set_stroke_style(v8!("rgba(20, 14, 0, 1.0)"));
+/// Draw a grid in `color` over a `width` by `height` arena, or the part of it inside `view`.
+pub fn draw_grid(ctx: &mut dyn Renderer, width: f64, height: f64, view: &Aabb, color: &Color) {
+    ctx.set_stroke_color(color.css());
     ctx.set_line_width(OUTLINE_WIDTH);
-    // grid
-    for x in 0..width as u64 / 100 {
-        ctx.move_to(x as f64 * 100., 0.);
-        ctx.line_to(x as f64 * 100., height);
+    // only the lines that cross the view, and only as far as they do
+    let (left, top) = (view.min.x.max(0.), view.min.y.max(0.));
+    let (right, bottom) = (view.max.x.min(width), view.max.y.min(height));
+    if left > right || top > bottom {
+        return;
     }
-    for y in 0..height as u64 / 100 {
-        ctx.move_to(0., y as f64 * 100.);
-        ctx.line_to(width, y as f64 * 100.);
+    let lines = |from: f64, to: f64, length: f64| {
+        (from / 100.).ceil() as u64..(length as u64 / 100).min((to / 100.).floor() as u64 + 1)
+    };
+    for x in lines(left, right, width) {
+        ctx.move_to(x as f64 * 100., top);
+        ctx.line_to(x as f64 * 100., bottom);
+    }
+    for y in lines(top, bottom, height) {
+        ctx.move_to(left, y as f64 * 100.);
+        ctx.line_to(right, y as f64 * 100.);
     }
     ctx.stroke();
 }
 
 /// Draw a polygon with a radius, an amount of sides, and an angle.
 #[allow(dead_code)]
-fn regular_polygon(
-    ctx: &CanvasRenderingContext2d,
-    x: f64,
-    y: f64,
-    radius: f64,
-    sides: u8,
-    angle: f64,
-) {
+fn regular_polygon(ctx: &mut dyn Renderer, x: f64, y: f64, radius: f64, sides: u8, angle: f64) {
     if sides < 3 {
         return;
     };
@@ -174,18 +162,18 @@ fn regular_polygon(
 /// Draw a polygon with the specified parameters.
 /// An outline color will be automatically selected.
 pub fn draw_polygon(
-    ctx: &CanvasRenderingContext2d,
+    ctx: &mut dyn Renderer,
     x: f64,
     y: f64,
     radius: f64,
     sides: u8,
     angle: f64,
-    color: &str,
+    color: &Color,
 ) {
     ctx.save();
-    ctx.set_fill_style(v8!(color));
+    ctx.set_fill_color(color.css());
     ctx.set_line_width(OUTLINE_WIDTH);
-    ctx.set_stroke_style(&wrapper::pSBC(-0.4, color, false, true)); // mic colors using pSBC
+    ctx.set_stroke_color(outline_color(color).css());
     regular_polygon(ctx, x, y, radius, sides, angle);
     ctx.fill();
     ctx.stroke();
@@ -195,7 +183,7 @@ pub fn draw_polygon(
 /// Draw a polygon with a radius, an amount of sides, and an angle.
 #[allow(dead_code)]
 fn regular_star(
-    ctx: &CanvasRenderingContext2d,
+    ctx: &mut dyn Renderer,
     x: f64,
     y: f64,
     radius: f64,
@@ -228,30 +216,42 @@ fn regular_star(
 /// Draw a polygon with the specified parameters.
 /// An outline color will be automatically selected.
 pub fn draw_star(
-    ctx: &CanvasRenderingContext2d,
+    ctx: &mut dyn Renderer,
     x: f64,
     y: f64,
     radius: f64,
     inner_radius: f64,
     sides: u8,
     angle: f64,
-    color: &str,
+    color: &Color,
 ) {
     ctx.save();
-    ctx.set_fill_style(v8!(color));
+    ctx.set_fill_color(color.css());
     ctx.set_line_width(OUTLINE_WIDTH);
-    ctx.set_stroke_style(&wrapper::pSBC(-0.4, color, false, true)); // mix colors using pSBC
+    ctx.set_stroke_color(outline_color(color).css());
     regular_star(ctx, x, y, radius, inner_radius, sides, angle);
     ctx.fill();
     ctx.stroke();
     ctx.restore();
 }
 
+/// Draw the outline of a circle, `width` wide.
+pub fn draw_ring(ctx: &mut dyn Renderer, x: f64, y: f64, r: f64, width: f64, color: &Color) {
+    ctx.save();
+    ctx.set_stroke_color(color.css());
+    ctx.set_line_width(width);
+    ctx.begin_path();
+    ctx.arc(x, y, r, 0., 2. * std::f64::consts::PI);
+    ctx.stroke();
+    ctx.close_path();
+    ctx.restore();
+}
+
 /// Draw a circle.
-pub fn draw_circle(ctx: &CanvasRenderingContext2d, x: f64, y: f64, r: f64, color: &str) {
+pub fn draw_circle(ctx: &mut dyn Renderer, x: f64, y: f64, r: f64, color: &Color) {
     ctx.save();
-    ctx.set_fill_style(v8!(color));
-    ctx.set_stroke_style(&wrapper::pSBC(-0.4, color, false, true)); // mix colors using pSBC
+    ctx.set_fill_color(color.css());
+    ctx.set_stroke_color(outline_color(color).css());
     ctx.set_line_width(OUTLINE_WIDTH * 2.);
     ctx.begin_path();
     ctx.arc(x, y, r, 0., 2. * std::f64::consts::PI);
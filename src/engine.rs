@@ -1,18 +1,28 @@
+use crate::color::Color;
 use crate::draw::*;
+use crate::geometry::{Aabb, Circle};
+use crate::hitflash::{self, HitFlash};
+use crate::lighting::{self, Priority, Scene};
+use crate::renderer::{LineJoin, Renderer};
+use crate::settings::Settings;
+use crate::spatial::SpatialHash;
+use crate::sprites::{SpriteCache, SpriteKey, SpriteKind, FLASH_STEPS};
+use crate::theme::Theme;
 use crate::util::*;
 use std::collections::HashMap;
-use wasm_bindgen::prelude::*;
-use wasm_bindgen::JsCast;
 use web_sys::*;
 
+use crate::binary;
+use crate::do_error_log;
 use crate::do_info_log;
+use crate::do_success_log;
+use crate::protocol::{self, Protocol};
 use crate::wrapper;
-use std::f64::consts::PI;
 
 /// A structure that holds all the currently pressed keys.
 #[allow(dead_code)]
 #[allow(non_snake_case)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Input {
     pub W: bool,
     pub A: bool,
@@ -38,8 +48,67 @@ impl Input {
 
 /// The Draw trait provides a basic outline for how drawable entities work.
 pub trait Draw {
-    /// Draw the entity.
-    fn draw(&mut self, ctx: &CanvasRenderingContext2d, delta: f64);
+    /// Draw the entity in the colors of `theme`, showing hits with `flash`, `alpha` of the way
+    /// from its previous simulation step to its last one, with its sprite from `sprites`.
+    fn draw(
+        &mut self,
+        ctx: &mut dyn Renderer,
+        sprites: &mut SpriteCache,
+        theme: &Theme,
+        flash: &HitFlash,
+        alpha: f64,
+    );
+}
+
+/// How far outside the view an entity can be and still be drawn: glows, names and chat
+/// messages reach out past its radius.
+pub const CULL_MARGIN: f64 = 400.;
+
+/// How far an entity can be drawn from where `World::index` has it: it eases towards its last
+/// census position, and bullets fly on past it.
+pub const INDEX_SLACK: f64 = 200.;
+
+/// How far the light of a tank reaches.
+pub const TANK_LIGHT_RADIUS: f64 = 1300.;
+
+/// The length of a simulation step in milliseconds. Motion is tuned for 60 steps a second, and
+/// `dt` everywhere is measured in those steps.
+pub const STEP_MS: f64 = 1000. / 60.;
+
+/// The most steps simulated in a single frame. Anything longer (like a tab that was in the
+/// background) is dropped rather than caught up on.
+pub const MAX_STEPS: u32 = 15;
+
+/// Turns the time between frames into a whole number of fixed simulation steps, so the
+/// simulation runs the same whatever the frame rate is.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct FixedTimestep {
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `elapsed` milliseconds of real time and return how many steps to simulate.
+    pub fn advance(&mut self, elapsed: f64) -> u32 {
+        self.accumulator += elapsed.max(0.);
+        let mut steps = 0;
+        while self.accumulator >= STEP_MS {
+            self.accumulator -= STEP_MS;
+            steps += 1;
+        }
+        if steps > MAX_STEPS {
+            steps = MAX_STEPS;
+        }
+        steps
+    }
+
+    /// How far the current frame is between the last two simulation steps, from 0 to 1.
+    pub fn alpha(&self) -> f64 {
+        self.accumulator / STEP_MS
+    }
 }
 
 /// A domtank of any class.
@@ -48,97 +117,164 @@ pub struct Tank {
     pub id: u32,
     pub name: String,
     pub position: Vector2<f64>,
+    /// `position` before the last simulation step, for interpolation.
+    pub previous_position: Vector2<f64>,
     pub net_position: Vector2<f64>,
     pub net_rotation: f64,
     pub velocity: Vector2<f64>,
     pub rotation: f64,
-    pub light: Light,
+    pub previous_rotation: f64,
     pub yourself: bool,
     pub mockup: u8,
     pub health: Scalar<f32>,
     pub radius: u16,
-    pub damaged: bool,
+    /// Milliseconds of simulation time since the tank last took damage.
+    pub since_hit: f64,
     pub opacity: Scalar<f32>,
     pub message: String,
 }
 
 impl Tank {
-    fn draw(&mut self, ctx: &CanvasRenderingContext2d, mockups: &Option<Mockups>, delta: f64) {
-        ctx.set_global_alpha(self.opacity.value as f64);
-        self.position.x = self.position.x.lerp(self.net_position.x, 0.05 * delta);
-        self.position.y = self.position.y.lerp(self.net_position.y, 0.05 * delta);
+    /// A tank at `position` facing right, at full health, that hasn't been hit.
+    pub fn new(id: u32, name: String, position: Vector2<f64>) -> Self {
+        Self {
+            id,
+            name,
+            position,
+            previous_position: position,
+            net_position: position,
+            net_rotation: 0.,
+            velocity: Vector2::zero(),
+            rotation: 0.,
+            previous_rotation: 0.,
+            yourself: false,
+            mockup: 0,
+            health: Scalar::new(1.),
+            radius: 50,
+            since_hit: f64::INFINITY,
+            opacity: Scalar::new(1.),
+            message: String::new(),
+        }
+    }
+
+    /// Advance the tank by `dt` simulation steps.
+    pub fn update(&mut self, dt: f64) {
+        self.previous_position = self.position;
+        self.previous_rotation = self.rotation;
+
+        self.position = self.position.lerp(self.net_position, 0.05 * dt);
 
         if !self.yourself {
-            self.rotation = lerp_angle(self.rotation, self.net_rotation, 0.3 * delta);
+            self.rotation = lerp_angle(self.rotation, self.net_rotation, 0.3 * dt);
+        }
+
+        let ms = (dt * STEP_MS) as f32;
+        self.opacity.smooth(ms, 75.);
+        self.health.smooth(ms, 75.);
+        self.since_hit += ms as f64;
+
+        self.position += self.velocity * dt;
+
+        self.velocity *= 0.8f64.powf(dt);
+    }
+
+    /// Where to draw the tank, `alpha` of the way from its previous simulation step to its
+    /// last one.
+    pub fn render_position(&self, alpha: f64) -> Vector2<f64> {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
+    /// The circle the tank casts its shadow with.
+    pub fn shadow(&self, alpha: f64) -> Circle {
+        Circle::new(
+            self.render_position(alpha),
+            self.radius as f64 * self.opacity.value as f64,
+        )
+    }
+
+    /// The rotation to draw the tank at. Our own tank follows the mouse every frame instead.
+    pub fn render_rotation(&self, alpha: f64) -> f64 {
+        if self.yourself {
+            self.rotation
+        } else {
+            lerp_angle(self.previous_rotation, self.rotation, alpha)
+        }
+    }
+
+    fn draw(
+        &mut self,
+        ctx: &mut dyn Renderer,
+        mockups: &Option<Mockups>,
+        theme: &Theme,
+        flash: &HitFlash,
+        settings: &Settings,
+        alpha: f64,
+    ) {
+        let colors = &theme.tank;
+        ctx.set_global_alpha(self.opacity.value as f64);
+        let position = self.render_position(alpha);
+        let rotation = self.render_rotation(alpha);
 
+        if !self.yourself && settings.show_names {
             ctx.set_font("bold 48px \"Fira Sans\"");
             ctx.save();
-            ctx.set_fill_style(v8!("#ffffff"));
-            ctx.set_stroke_style(v8!("#000000"));
+            ctx.set_fill_color(colors.name.css());
+            ctx.set_stroke_color(colors.name_outline.css());
             ctx.set_line_width(20.);
             // measure text
-            let measurement = ctx.measure_text(self.name.as_str()).unwrap().width();
+            let measurement = ctx.measure_text(self.name.as_str());
             ctx.set_line_width(10.);
             ctx.stroke_text(
                 self.name.as_str(),
-                self.position.x - measurement / 2.,
-                self.position.y - self.radius as f64 - 80.,
+                position.x - measurement / 2.,
+                position.y - self.radius as f64 - 80.,
             );
             ctx.fill_text(
                 self.name.as_str(),
-                self.position.x - measurement / 2.,
-                self.position.y - self.radius as f64 - 80.,
+                position.x - measurement / 2.,
+                position.y - self.radius as f64 - 80.,
             );
             ctx.restore();
         }
 
-        if !self.message.is_empty() {
+        if !self.message.is_empty() && settings.show_chat {
             ctx.set_font("bold 48px \"Fira Sans\"");
             ctx.save();
-            ctx.set_fill_style(v8!("#ffffff"));
-            ctx.set_stroke_style(v8!("#000000"));
+            ctx.set_fill_color(colors.name.css());
+            ctx.set_stroke_color(colors.name_outline.css());
             ctx.set_line_width(20.);
             // measure text
-            let measurement = ctx.measure_text(self.message.as_str()).unwrap().width();
+            let measurement = ctx.measure_text(self.message.as_str());
             draw_rect_no_correction(
                 ctx,
-                self.position.x - measurement / 2. - 10.,
-                self.position.y - self.radius as f64 - 150. - 47.,
+                position.x - measurement / 2. - 10.,
+                position.y - self.radius as f64 - 150. - 47.,
                 measurement + 20.,
                 60.,
                 0.,
-                "#000000",
+                &colors.message_background,
             );
             ctx.set_line_width(10.);
             ctx.stroke_text(
                 self.message.as_str(),
-                self.position.x - measurement / 2.,
-                self.position.y - self.radius as f64 - 150.,
+                position.x - measurement / 2.,
+                position.y - self.radius as f64 - 150.,
             );
             ctx.fill_text(
                 self.message.as_str(),
-                self.position.x - measurement / 2.,
-                self.position.y - self.radius as f64 - 150.,
+                position.x - measurement / 2.,
+                position.y - self.radius as f64 - 150.,
             );
             ctx.restore();
         }
 
-        self.opacity.update(0.2 * delta as f32);
-        self.health.update(0.2 * delta as f32);
-
-        self.position.x += self.velocity.x * delta;
-        self.position.y += self.velocity.y * delta;
-
-        self.velocity.x *= 0.8;
-        self.velocity.y *= 0.8;
-
         match mockups {
             Some(mockups) => {
                 let my_tank = &mockups[self.mockup as usize];
                 for barrel in my_tank.barrels.iter() {
                     ctx.save();
-                    ctx.translate(self.position.x, self.position.y);
-                    ctx.rotate(self.rotation + barrel.angle as f64);
+                    ctx.translate(position.x, position.y);
+                    ctx.rotate(rotation + barrel.angle as f64);
                     ctx.translate(
                         self.radius as f64 * self.opacity.value as f64 * barrel.length as f64,
                         0. * 2.,
@@ -149,7 +285,7 @@ impl Tank {
                         0.,
                         self.radius as f64 * self.opacity.value as f64 * barrel.length as f64 * 2.,
                         self.radius as f64 * self.opacity.value as f64 * barrel.width as f64 * 2.,
-                        "rgba(20, 20, 20, 1.0)",
+                        &colors.barrel,
                     );
                     ctx.restore();
                 }
@@ -157,24 +293,17 @@ impl Tank {
             None => (),
         }
 
-        let random_chance = js_sys::Math::random() < 0.85;
-        let color = if self.damaged {
-            if random_chance {
-                "#780000"
-            } else {
-                "#8a4900"
-            }
-        } else {
-            "rgba(50, 50, 50, 1.0)"
+        let intensity = flash.intensity(self.since_hit);
+        let color = match flash.style {
+            hitflash::Style::Fill if intensity > 0. => colors.body.mix(&colors.damaged, intensity),
+            _ => colors.body.clone(),
         };
 
-        draw_circle(
-            ctx,
-            self.position.x,
-            self.position.y,
-            self.radius as f64 * self.opacity.value as f64,
-            color,
-        );
+        let radius = self.radius as f64 * self.opacity.value as f64;
+        draw_circle(ctx, position.x, position.y, radius, &color);
+        if flash.style == hitflash::Style::Outline {
+            draw_hit_ring(ctx, position, radius, intensity, &colors.damaged);
+        }
 
         // health (percentage)
         let bar_length = (self.radius + 130) as f64;
@@ -183,154 +312,180 @@ impl Tank {
         const LONGER_BAR_WIDTH: f64 = BAR_WIDTH + (10. * 2.);
         draw_bar(
             ctx,
-            self.position.x - bar_length / 2.,
-            self.position.x + bar_length / 2.,
-            self.position.y + self.radius as f64 + BAR_DISTANCE,
+            position.x - bar_length / 2.,
+            position.x + bar_length / 2.,
+            position.y + self.radius as f64 + BAR_DISTANCE,
             LONGER_BAR_WIDTH,
-            "#000000",
+            &colors.health_background,
         );
         draw_bar(
             ctx,
-            self.position.x - bar_length / 2.,
-            (self.position.x - bar_length / 2.) + bar_length * self.health.value as f64,
-            self.position.y + self.radius as f64 + BAR_DISTANCE,
+            position.x - bar_length / 2.,
+            (position.x - bar_length / 2.) + bar_length * self.health.value as f64,
+            position.y + self.radius as f64 + BAR_DISTANCE,
             BAR_WIDTH,
-            "#3ea832",
+            &colors.health,
         );
 
-        self.damaged = false;
-
-        self.light = Light {
-            x: self.position.x,
-            y: self.position.y,
-            r: 1300.,
-            color: String::from("rgba(252, 250, 157, 0.35)"),
-        };
         ctx.set_global_alpha(1.0);
     }
+
+    /// The light the tank carries, where it's drawn.
+    pub fn light(&self, theme: &Theme, alpha: f64) -> Light {
+        let position = self.render_position(alpha);
+        Light {
+            x: position.x,
+            y: position.y,
+            r: TANK_LIGHT_RADIUS * self.opacity.value as f64,
+            color: theme.tank.light.clone(),
+        }
+    }
 }
 
 pub struct Shape {
     pub id: u32,
     pub position: Vector2<f64>,
+    pub previous_position: Vector2<f64>,
     pub net_position: Vector2<f64>,
     pub velocity: Vector2<f64>,
     pub rotation: f32,
     pub sides: u8,
     pub radius: u16,
     pub health: f32,
-    pub damaged: bool,
+    /// Milliseconds of simulation time since the shape last took damage.
+    pub since_hit: f64,
 
     pub opacity: Scalar<f32>,
-    pub cached_tex: Option<web_sys::HtmlCanvasElement>,
-    pub needs_redraw: bool,
 }
 
-impl Draw for Shape {
-    fn draw(&mut self, ctx: &CanvasRenderingContext2d, delta: f64) {
-        self.position.x = self.position.x.lerp(self.net_position.x, 0.05 * delta);
-        self.position.y = self.position.y.lerp(self.net_position.y, 0.05 * delta);
-
-        self.opacity.update(0.1 * delta as f32);
-
-        if self.sides % 2 != 0 {
-            self.sides += 1;
+impl Shape {
+    /// A cactus at `position` at full health, that hasn't been hit.
+    pub fn new(id: u32, position: Vector2<f64>, sides: u8, radius: u16) -> Self {
+        Self {
+            id,
+            position,
+            previous_position: position,
+            net_position: position,
+            velocity: Vector2::zero(),
+            rotation: 0.,
+            sides,
+            radius,
+            health: 1.,
+            since_hit: f64::INFINITY,
+            opacity: Scalar::new(1.),
         }
+    }
 
-        let tex = {
-            match &self.cached_tex {
-                Some(canvas) => canvas.clone(),
-                None => {
-                    let off_can = crate::document().create_element("canvas").unwrap();
-                    let off_can: web_sys::HtmlCanvasElement = off_can
-                        .dyn_into::<web_sys::HtmlCanvasElement>()
-                        .map_err(|_| ())
-                        .unwrap();
-                    off_can.set_width((self.radius as f64 * 1.5) as u32 * 2);
-                    off_can.set_height((self.radius as f64 * 1.5) as u32 * 2);
-                    off_can.clone()
-                }
-            }
-        };
-
-        if self.needs_redraw {
-            let off_ctx = tex
-                .get_context("2d")
-                .unwrap()
-                .unwrap()
-                .dyn_into::<web_sys::CanvasRenderingContext2d>()
-                .unwrap();
+    /// Advance the shape by `dt` simulation steps.
+    pub fn update(&mut self, dt: f64) {
+        self.previous_position = self.position;
 
-            let random_chance = js_sys::Math::random() < 0.85;
+        self.position = self.position.lerp(self.net_position, 0.05 * dt);
 
-            let color = if self.damaged {
-                if random_chance {
-                    "#780000"
-                } else {
-                    "#8a4900"
-                }
-            } else {
-                "#002606"
-            };
-            let darker_color = if self.damaged {
-                if random_chance {
-                    "#570000"
-                } else {
-                    "#572e00"
-                }
-            } else {
-                "#001d03"
-            };
+        self.opacity.smooth((dt * STEP_MS) as f32, 160.);
+        self.since_hit += dt * STEP_MS;
+    }
 
-            draw_star(
-                &off_ctx,
-                self.radius as f64 * 1.5,
-                self.radius as f64 * 1.5,
-                self.radius as f64 * 1.5 / 2.14,
-                self.radius as f64 * 1.5,
-                self.sides,
-                self.rotation as f64,
-                "#1a1a1a",
-            );
+    pub fn render_position(&self, alpha: f64) -> Vector2<f64> {
+        self.previous_position.lerp(self.position, alpha)
+    }
 
-            draw_circle(
-                &off_ctx,
-                self.radius as f64 * 1.5,
-                self.radius as f64 * 1.5,
-                self.radius as f64,
-                color,
-            );
+    /// The circle the shape casts its shadow with.
+    pub fn shadow(&self, alpha: f64) -> Circle {
+        Circle::new(
+            self.render_position(alpha),
+            self.radius as f64 * self.opacity.value as f64,
+        )
+    }
+}
 
-            draw_star(
-                &off_ctx,
-                self.radius as f64 * 1.5,
-                self.radius as f64 * 1.5,
-                self.radius as f64 * 1.5 / 3.75,
-                self.radius as f64 * 1.5 / 2.14,
-                self.sides,
-                self.rotation as f64,
-                darker_color,
-            );
+impl Draw for Shape {
+    fn draw(
+        &mut self,
+        ctx: &mut dyn Renderer,
+        sprites: &mut SpriteCache,
+        theme: &Theme,
+        flash: &HitFlash,
+        alpha: f64,
+    ) {
+        let colors = &theme.cactus;
+        let position = self.render_position(alpha);
 
-            if self.damaged {
-                self.needs_redraw = true;
-            } else {
-                self.needs_redraw = false;
-            }
-            self.damaged = false;
+        if self.sides % 2 != 0 {
+            self.sides += 1;
         }
 
+        let intensity = flash.intensity(self.since_hit);
+        // the fill flash goes in steps, which each have a sprite
+        let step = match flash.style {
+            hitflash::Style::Fill => SpriteKey::flash_step(intensity),
+            _ => 0,
+        };
+        let amount = step as f64 / FLASH_STEPS as f64;
+        let (color, darker_color) = if step > 0 {
+            (
+                colors.body.mix(&colors.damaged, amount),
+                colors.core.mix(&colors.damaged_core, amount),
+            )
+        } else {
+            (colors.body.clone(), colors.core.clone())
+        };
+
+        let bucket = SpriteKey::bucket(self.radius);
+        let key = SpriteKey {
+            kind: SpriteKind::Cactus,
+            radius: bucket,
+            color: color.clone(),
+            sides: self.sides,
+            flash: step,
+        };
+        let size = (bucket as f64 * 1.5) as u32 * 2;
+        // the sprite is drawn unrotated, at the radius of its bucket
+        let half =
+            size as f64 / 2. * self.opacity.value as f64 * self.radius as f64 / bucket as f64;
+
+        ctx.save();
         ctx.set_global_alpha(self.opacity.value as f64);
-        ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
-            &tex,
-            self.position.x - tex.width() as f64 * self.opacity.value as f64 / 2.,
-            self.position.y - tex.height() as f64 * self.opacity.value as f64 / 2.,
-            tex.width() as f64 * self.opacity.value as f64,
-            tex.height() as f64 * self.opacity.value as f64,
+        ctx.translate(position.x, position.y);
+        ctx.rotate(self.rotation as f64);
+        let (radius, sides) = (bucket as f64, self.sides);
+        sprites.draw(
+            ctx,
+            &key,
+            size,
+            Aabb::from_center(Vector2::zero(), Vector2 { x: half, y: half }),
+            &mut |sprite| {
+                draw_star(
+                    sprite,
+                    radius * 1.5,
+                    radius * 1.5,
+                    radius * 1.5 / 2.14,
+                    radius * 1.5,
+                    sides,
+                    0.,
+                    &colors.spikes,
+                );
+
+                draw_circle(sprite, radius * 1.5, radius * 1.5, radius, &color);
+
+                draw_star(
+                    sprite,
+                    radius * 1.5,
+                    radius * 1.5,
+                    radius * 1.5 / 3.75,
+                    radius * 1.5 / 2.14,
+                    sides,
+                    0.,
+                    &darker_color,
+                );
+            },
         );
-        ctx.set_global_alpha(1.);
+        ctx.restore();
 
-        self.cached_tex = Some(tex);
+        if flash.style == hitflash::Style::Outline {
+            let radius = self.radius as f64 * self.opacity.value as f64;
+            draw_hit_ring(ctx, position, radius, intensity, &colors.damaged);
+        }
     }
 }
 
@@ -338,6 +493,7 @@ impl Draw for Shape {
 pub struct Bullet {
     pub id: u32,
     pub position: Vector2<f64>,
+    pub previous_position: Vector2<f64>,
     pub net_position: Vector2<f64>,
     pub velocity: Vector2<f64>,
     pub radius: u16,
@@ -345,89 +501,142 @@ pub struct Bullet {
     pub opacity: Scalar<f32>,
     pub scale: Scalar<f32>,
 
-    pub cached_tex: Option<web_sys::HtmlCanvasElement>,
+    /// Whether we fired it, which decides its colors.
+    pub own: bool,
+}
 
-    pub color: String,
+impl Bullet {
+    /// A bullet at `position`, standing still. `own` is whether we fired it.
+    pub fn new(id: u32, position: Vector2<f64>, radius: u16, own: bool) -> Self {
+        Self {
+            id,
+            position,
+            previous_position: position,
+            net_position: position,
+            velocity: Vector2::zero(),
+            radius,
+            opacity: Scalar::new(1.),
+            scale: Scalar::new(1.),
+            own,
+        }
+    }
+
+    /// Advance the bullet by `dt` simulation steps.
+    pub fn update(&mut self, dt: f64) {
+        self.previous_position = self.position;
+
+        self.position = self.position.lerp(self.net_position, 0.05 * dt);
+
+        self.position += self.velocity * dt;
+
+        let ms = (dt * STEP_MS) as f32;
+        self.opacity.smooth(ms, 45.);
+        self.scale.smooth(ms, 45.);
+    }
+
+    pub fn render_position(&self, alpha: f64) -> Vector2<f64> {
+        self.previous_position.lerp(self.position, alpha)
+    }
+
+    /// The colors of the bullet and of its glow.
+    fn colors<'a>(&self, theme: &'a Theme) -> (&'a Color, &'a Color) {
+        let colors = &theme.bullet;
+        if self.own {
+            (&colors.own, &colors.own_glow)
+        } else {
+            (&colors.enemy, &colors.enemy_glow)
+        }
+    }
+
+    /// The glow around the bullet.
+    pub fn light(&self, theme: &Theme, alpha: f64) -> Light {
+        let position = self.render_position(alpha);
+        Light {
+            x: position.x,
+            y: position.y,
+            r: self.radius as f64 * 8. * (self.opacity.value * self.scale.value) as f64,
+            color: self.colors(theme).1.clone(),
+        }
+    }
 }
 
 impl Draw for Bullet {
-    fn draw(&mut self, ctx: &CanvasRenderingContext2d, delta: f64) {
-        self.position.x = self.position.x.lerp(self.net_position.x, 0.05 * delta);
-        self.position.y = self.position.y.lerp(self.net_position.y, 0.05 * delta);
-
-        self.position.x += self.velocity.x * delta;
-        self.position.y += self.velocity.y * delta;
-
-        self.opacity.update(0.3 * delta as f32);
-        self.scale.update(0.3 * delta as f32);
-
-        match &self.cached_tex {
-            Some(canvas) => {
-                ctx.set_global_alpha(self.opacity.value as f64);
-                ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
-                    &canvas,
-                    self.position.x - canvas.width() as f64 * self.scale.value as f64 / 2.,
-                    self.position.y - canvas.height() as f64 * self.scale.value as f64 / 2.,
-                    canvas.width() as f64 * self.scale.value as f64,
-                    canvas.height() as f64 * self.scale.value as f64,
-                );
-                ctx.set_global_alpha(1.);
-            }
-            None => {
-                let off_can = crate::document().create_element("canvas").unwrap();
-                let off_can: web_sys::HtmlCanvasElement = off_can
-                    .dyn_into::<web_sys::HtmlCanvasElement>()
-                    .map_err(|_| ())
-                    .unwrap();
-                let off_ctx = off_can
-                    .get_context("2d")
-                    .unwrap()
-                    .unwrap()
-                    .dyn_into::<web_sys::CanvasRenderingContext2d>()
-                    .unwrap();
-
-                off_can.set_width((self.radius as u32 + 9) * 2 + 300);
-                off_can.set_height((self.radius as u32 + 9) * 2 + 300);
-
-                off_ctx.set_shadow_blur(100.);
-                off_ctx.set_shadow_color(self.color.as_str());
+    fn draw(
+        &mut self,
+        ctx: &mut dyn Renderer,
+        sprites: &mut SpriteCache,
+        theme: &Theme,
+        _flash: &HitFlash,
+        alpha: f64,
+    ) {
+        let position = self.render_position(alpha);
+        let color = self.colors(theme).0;
+
+        let bucket = SpriteKey::bucket(self.radius);
+        let key = SpriteKey {
+            kind: SpriteKind::Bullet,
+            radius: bucket,
+            color: color.clone(),
+            sides: 0,
+            flash: 0,
+        };
+        let size = (bucket as u32 + 9) * 2 + 300;
+        let half = size as f64 / 2. * self.scale.value as f64 * self.radius as f64 / bucket as f64;
+
+        ctx.save();
+        ctx.set_global_alpha(self.opacity.value as f64);
+        let radius = bucket as f64;
+        sprites.draw(
+            ctx,
+            &key,
+            size,
+            Aabb::from_center(position, Vector2 { x: half, y: half }),
+            &mut |sprite| {
+                sprite.set_shadow_blur(100.);
+                sprite.set_shadow_color(color.css());
                 draw_circle(
-                    &off_ctx,
-                    self.radius as f64 + 9. + 150.,
-                    self.radius as f64 + 9. + 150.,
-                    self.radius as f64,
-                    self.color.as_str(),
-                );
-                off_ctx.set_shadow_blur(0.);
-
-                ctx.save();
-                ctx.set_global_alpha(self.opacity.value as f64);
-                ctx.draw_image_with_html_canvas_element_and_dw_and_dh(
-                    &off_can,
-                    self.position.x - off_can.width() as f64 * self.scale.value as f64 / 2.,
-                    self.position.y - off_can.height() as f64 * self.scale.value as f64 / 2.,
-                    off_can.width() as f64 * self.scale.value as f64,
-                    off_can.height() as f64 * self.scale.value as f64,
+                    sprite,
+                    radius + 9. + 150.,
+                    radius + 9. + 150.,
+                    radius,
+                    color,
                 );
-                ctx.restore();
-                self.cached_tex = Some(off_can);
-            }
-        }
+                sprite.set_shadow_blur(0.);
+            },
+        );
+        ctx.restore();
     }
 }
 
+/// Draw the ring of an outline hit flash around something `radius` big at `position`: it
+/// pulses out and fades as the flash does.
+fn draw_hit_ring(
+    ctx: &mut dyn Renderer,
+    position: Vector2<f64>,
+    radius: f64,
+    intensity: f64,
+    color: &Color,
+) {
+    if intensity <= 0. {
+        return;
+    }
+    let color = color.with_alpha(color.channels().3 * intensity);
+    let r = radius + 15. + 25. * (1. - intensity);
+    draw_ring(ctx, position.x, position.y, r, 8., &color);
+}
+
 /// A structure used for rendering lights.
 #[derive(Clone)]
 pub struct Light {
     pub x: f64,
     pub y: f64,
     pub r: f64,
-    pub color: String,
+    pub color: Color,
 }
 
 impl Light {
-    fn draw(&mut self, ctx: &CanvasRenderingContext2d) {
-        draw_light(ctx, self.x, self.y, self.r, &*self.color);
+    pub fn position(&self) -> Vector2<f64> {
+        Vector2::new(self.x, self.y)
     }
 }
 
@@ -439,13 +648,32 @@ pub enum Entity {
     Bullet(Bullet),
 }
 
-/// Represents a shadow triangle (for lighting)
-pub struct Quadrilateral(
-    pub Vector2<f64>,
-    pub Vector2<f64>,
-    pub Vector2<f64>,
-    pub Vector2<f64>,
-);
+impl Entity {
+    pub fn id(&self) -> u32 {
+        match self {
+            Entity::Tank(e) => e.id,
+            Entity::Shape(e) => e.id,
+            Entity::Bullet(e) => e.id,
+        }
+    }
+
+    pub fn entity_type(&self) -> protocol::EntityType {
+        match self {
+            Entity::Tank(_) => protocol::EntityType::Tank,
+            Entity::Shape(_) => protocol::EntityType::Shape,
+            Entity::Bullet(_) => protocol::EntityType::Bullet,
+        }
+    }
+
+    /// Where the last census put the entity.
+    pub fn net_position(&self) -> Vector2<f64> {
+        match self {
+            Entity::Tank(e) => e.net_position,
+            Entity::Shape(e) => e.net_position,
+            Entity::Bullet(e) => e.net_position,
+        }
+    }
+}
 
 pub type Mockups = Vec<crate::protocol::TankMockup>;
 
@@ -472,6 +700,17 @@ impl GameState {
         }
     }
 
+    /// Fade the death screen in or out.
+    pub fn show_death_screen(&mut self, shown: bool) {
+        if shown {
+            self.death_animation_completion
+                .tween_to(1., 600., Easing::OutCubic);
+        } else {
+            self.death_animation_completion
+                .tween_to(0., 250., Easing::InCubic);
+        }
+    }
+
     pub fn is_dead(&self) -> bool {
         match self.player_state {
             PlayerState::Dead(_) => true,
@@ -495,104 +734,819 @@ impl GameState {
 pub struct World {
     pub input: Input,
     pub camera: Vector2<f64>,
+    /// `camera` before the last simulation step, for interpolation.
+    pub previous_camera: Vector2<f64>,
     pub size: Scalar<f32>,
-
-    pub canvas: HtmlCanvasElement,
-    pub ctx: CanvasRenderingContext2d,
-
-    pub composite: HtmlCanvasElement,
-    pub composite_ctx: CanvasRenderingContext2d,
-
-    pub chat_input: HtmlInputElement,
-    pub chat_div: HtmlDivElement,
+    /// How far the camera is zoomed out, on top of the field of view of the tank.
+    pub zoom: Scalar<f32>,
 
     pub yourself: Tank,
     pub state: GameState,
 
     pub entities: HashMap<u32, Entity>,
+    /// Where `entities` are, by their last census position.
+    pub index: SpatialHash,
 
     pub mockups: Option<Mockups>,
     pub leaderboard: crate::protocol::LeaderboardPacket,
+
+    /// The raw handshake packet, kept around for session recordings.
+    pub handshake: Vec<u8>,
+    pub recorder: Option<crate::replay::Recorder>,
+    /// The replay being watched, if this isn't a live game.
+    pub playback: Option<crate::replay::Player>,
+    pub killcam: crate::killcam::Killcam,
+    pub netsim: crate::netsim::NetSim,
+    pub netstats: crate::netstats::NetStats,
+    pub input_sender: crate::input_sender::InputSender,
+    /// The most lights drawn in a frame.
+    pub light_budget: usize,
+    /// The sprites of cacti and bullets.
+    pub sprites: SpriteCache,
+    /// The colors everything is drawn in. Change it with `set_theme`.
+    pub theme: Theme,
+    /// How entities show that they've been hit.
+    pub hit_flash: HitFlash,
+    /// How big the HUD is drawn, on top of the size that fits the window.
+    pub ui_scale: f64,
+    /// What the player picked in the settings menu. Change it with `apply_settings`.
+    pub settings: Settings,
+    /// The widgets of menus, kept between frames.
+    pub ui: crate::widget::Ui,
+    pub menu: crate::menu::Menu,
 }
 
 impl World {
-    /// Draw all entities that aren't comprised of UI.
-    pub fn draw_entities(&mut self, delta: f64) -> Vec<Quadrilateral> {
-        self.size.update(0.075 * delta as f32);
-        let mut lights: Vec<Light> = Vec::new();
+    /// A world with nothing in it yet, for a player called `name`.
+    pub fn new(name: String, netsim: crate::netsim::NetSim, light_budget: usize) -> Self {
+        Self {
+            yourself: Tank {
+                yourself: true,
+                ..Tank::new(0, name, Vector2::zero())
+            },
+            state: GameState::new(),
+            input: Input::new(),
+            camera: Vector2 { x: 0., y: 0. },
+            previous_camera: Vector2 { x: 0., y: 0. },
+            size: Scalar::new(1.),
+            zoom: Scalar::new(1.),
+            entities: HashMap::new(),
+            index: SpatialHash::default(),
+            mockups: None,
+            leaderboard: crate::protocol::LeaderboardPacket {
+                entries: Vec::new(),
+            },
+            handshake: Vec::new(),
+            recorder: None,
+            playback: None,
+            killcam: crate::killcam::Killcam::new(),
+            netsim,
+            netstats: crate::netstats::NetStats::new(),
+            input_sender: crate::input_sender::InputSender::new(),
+            light_budget,
+            sprites: SpriteCache::new(crate::sprites::DEFAULT_BUDGET),
+            theme: Theme::default(),
+            hit_flash: HitFlash::DEFAULT,
+            ui_scale: 1.,
+            settings: Settings::default(),
+            ui: crate::widget::Ui::new(),
+            menu: crate::menu::Menu::default(),
+        }
+    }
+
+    /// Advance the simulation by `dt` steps. Everything that moves does so here, while drawing
+    /// only interpolates between the last two steps.
+    pub fn update(&mut self, dt: f64) {
+        if !self.state.chat_open && !self.state.is_dead() {
+            if self.input.W {
+                self.yourself.velocity.y -= dt;
+            } else if self.input.S {
+                self.yourself.velocity.y += dt;
+            }
+
+            if self.input.A {
+                self.yourself.velocity.x -= dt;
+            } else if self.input.D {
+                self.yourself.velocity.x += dt;
+            }
+        }
+
+        update_entities(&mut self.entities, dt);
+        if let Some(replay) = &mut self.killcam.replay {
+            update_entities(&mut replay.entities, dt);
+        }
+        let ms = (dt * STEP_MS) as f32;
+        self.yourself.update(dt);
+        self.yourself.opacity.smooth(ms, 25.);
 
-        let mut tanks = Vec::new();
-        let mut cacti = Vec::new();
-        let mut bullets = Vec::new();
-        let mut shadows = Vec::new();
+        self.size.smooth(ms, 215.);
+        self.state.level.smooth(ms, 325.);
+        self.state.death_animation_completion.tween(ms);
 
+        self.zoom.tv = match &self.killcam.replay {
+            Some(replay) if replay.wide => 1.75,
+            _ => 1.,
+        };
+        self.zoom.spring(ms, 700., 0.8);
+
+        // move the camera
+        self.previous_camera = self.camera;
+        match &self.playback {
+            Some(player) if player.free_camera => {
+                self.camera += player.pan * (30. * dt);
+            }
+            _ => {
+                let focus = if self.killcam.is_playing() {
+                    self.viewpoint(1.)
+                } else {
+                    self.yourself.net_position
+                };
+                self.camera = self.camera.lerp(focus, 0.075 * dt);
+            }
+        }
+    }
+
+    /// Where to draw the camera, `alpha` of the way from the previous simulation step to the
+    /// last one.
+    pub fn render_camera(&self, alpha: f64) -> Vector2<f64> {
+        self.previous_camera.lerp(self.camera, alpha)
+    }
+
+    /// How many pixels a world unit takes up in a `width` by `height` window, going by the field
+    /// of view of the tank we're looking through and the zoom.
+    pub fn fov_scale(&self, width: f64, height: f64) -> f64 {
+        // the killcam looks through the eyes of the killer, or zooms out with the wide camera
+        let mockup = match &self.killcam.replay {
+            Some(replay) if !replay.wide => replay
+                .focus_tank()
+                .map(|t| t.mockup)
+                .unwrap_or(self.yourself.mockup),
+            _ => self.yourself.mockup,
+        };
+        let fov = match &self.mockups {
+            Some(mockups) => mockups[mockup as usize].fov,
+            None => 20,
+        };
+        let design_resolution = 112.5 * fov as f64 * self.zoom.value as f64;
+        (width + height) / (design_resolution + design_resolution)
+    }
+
+    /// The point in the world under `point`, in pixels from the top left of a `width` by
+    /// `height` window.
+    pub fn screen_to_world(
+        &self,
+        point: Vector2<f64>,
+        width: f64,
+        height: f64,
+        alpha: f64,
+    ) -> Vector2<f64> {
+        let scale = self.fov_scale(width, height);
+        let center = Vector2::new(width, height) / (2. * scale);
+        point / scale + self.render_camera(alpha) - center
+    }
+
+    /// The part of the world a `width` by `height` window shows.
+    pub fn view(&self, width: f64, height: f64, alpha: f64) -> Aabb {
+        let scale = self.fov_scale(width, height);
+        Aabb::from_center(
+            self.render_camera(alpha),
+            Vector2::new(width, height) / (2. * scale),
+        )
+    }
+
+    /// Draw a whole frame for a `width` by `height` window: the arena and its entities onto
+    /// `canvas`, and the lights and the HUD onto `composite`, which goes on top of it. `frame`
+    /// counts the frames drawn so far, for animations.
+    pub fn draw_frame(
+        &mut self,
+        canvas: &mut dyn Renderer,
+        composite: &mut dyn Renderer,
+        width: f64,
+        height: f64,
+        alpha: f64,
+        frame: u32,
+    ) {
+        let scale = self.fov_scale(width, height);
+        let view = self.view(width, height, alpha);
+        let center = view.size() / 2.;
+        let camera = view.center();
+
+        canvas.save();
+        canvas.scale(scale, scale);
+        canvas.translate(center.x - camera.x, center.y - camera.y);
+        composite.save();
+        composite.scale(scale, scale);
+        composite.translate(center.x - camera.x, center.y - camera.y);
+
+        // clear the canvas
+        canvas.set_fill_color(self.theme.arena.css());
+        let size = self.size.value as f64;
+        canvas.fill_rect(0., 0., size, size);
+        composite.clear_rect(0., 0., size, size);
+
+        canvas.set_line_join(LineJoin::Round);
+
+        // grid
+        draw_grid(canvas, size.ceil(), size.ceil(), &view, &self.theme.grid);
+
+        // render
+        let scene = self.draw_entities(canvas, alpha, &view);
+
+        let lights = lighting::select(
+            &scene.lights,
+            self.viewpoint(alpha),
+            &view,
+            self.light_budget,
+        );
+        let occluders: &[Circle] = if self.settings.quality.shadows() {
+            &scene.occluders
+        } else {
+            &[]
+        };
+        lighting::render(composite, &lights, occluders);
+
+        canvas.restore();
+        composite.restore();
+
+        // gui pass
+        let gui_scale = crate::hud::scale(width, height, self.ui_scale);
+        composite.save();
+        composite.scale(gui_scale, gui_scale);
+        crate::hud::draw(
+            composite,
+            self,
+            frame,
+            width / gui_scale,
+            height / gui_scale,
+        );
+        // menus change the world as they're drawn, so their widgets are taken out of it meanwhile
+        let mut ui = std::mem::take(&mut self.ui);
+        crate::menu::draw(
+            &mut ui.frame(composite, &self.theme.hud, gui_scale),
+            self,
+            width / gui_scale,
+            height / gui_scale,
+        );
+        self.ui = ui;
+        composite.restore();
+    }
+
+    /// Draw all entities that aren't comprised of UI, and return their lights and the
+    /// circles that cast shadows. Entities further than `CULL_MARGIN` outside of `view` aren't
+    /// drawn, but still light it and cast shadows into it if they're close enough. Entities are
+    /// found through `index`, so ones that aren't in it aren't drawn at all.
+    pub fn draw_entities(&mut self, ctx: &mut dyn Renderer, alpha: f64, view: &Aabb) -> Scene {
+        // The killcam brings its own entities, which are drawn in place of the live ones.
+        let mut killcam = self.killcam.replay.take();
+        let focus = match &killcam {
+            Some(replay) => replay.focus_tank().map(|t| t.id),
+            None => None,
+        };
+        if let Some(replay) = &mut killcam {
+            std::mem::swap(&mut self.entities, &mut replay.entities);
+            std::mem::swap(&mut self.index, &mut replay.index);
+        }
+
+        let mut scene = Scene::default();
+
+        // only what's close enough to be seen, or to light or shade what is
+        let nearby = self
+            .index
+            .query_rect(&view.expand(TANK_LIGHT_RADIUS + INDEX_SLACK));
+        let reach = view.expand(CULL_MARGIN);
+
+        for id in &nearby {
+            if let Some(Entity::Shape(cactus)) = self.entities.get_mut(id) {
+                let shadow = cactus.shadow(alpha);
+                if shadow.intersects_aabb(&reach) {
+                    cactus.draw(ctx, &mut self.sprites, &self.theme, &self.hit_flash, alpha);
+                }
+                scene.occluders.push(shadow);
+            }
+        }
+
+        for id in &nearby {
+            if let Some(Entity::Tank(tank)) = self.entities.get_mut(id) {
+                let shadow = tank.shadow(alpha);
+                if shadow.intersects_aabb(&reach) {
+                    tank.draw(
+                        ctx,
+                        &self.mockups,
+                        &self.theme,
+                        &self.hit_flash,
+                        &self.settings,
+                        alpha,
+                    );
+                }
+                scene.occluders.push(shadow);
+                let priority = if Some(tank.id) == focus {
+                    Priority::Viewpoint
+                } else {
+                    Priority::Tank
+                };
+                scene
+                    .lights
+                    .push((priority, tank.light(&self.theme, alpha)));
+            }
+        }
+
+        for id in &nearby {
+            if let Some(Entity::Bullet(bullet)) = self.entities.get_mut(id) {
+                let position = bullet.render_position(alpha);
+                if Circle::new(position, bullet.radius as f64).intersects_aabb(&reach) {
+                    bullet.draw(ctx, &mut self.sprites, &self.theme, &self.hit_flash, alpha);
+                }
+                let light = bullet.light(&self.theme, alpha);
+                scene.lights.push((Priority::Bullet, light));
+            }
+        }
+
+        self.yourself.draw(
+            ctx,
+            &self.mockups,
+            &self.theme,
+            &self.hit_flash,
+            &self.settings,
+            alpha,
+        );
+        scene.occluders.push(self.yourself.shadow(alpha));
+        let priority = if focus.is_none() {
+            Priority::Viewpoint
+        } else {
+            Priority::Tank
+        };
+        scene
+            .lights
+            .push((priority, self.yourself.light(&self.theme, alpha)));
+
+        if let Some(replay) = &mut killcam {
+            std::mem::swap(&mut self.entities, &mut replay.entities);
+            std::mem::swap(&mut self.index, &mut replay.index);
+        }
+        self.killcam.replay = killcam;
+        scene
+    }
+
+    /// Where the scene is lit from: the tank the killcam follows, or `yourself`.
+    pub fn viewpoint(&self, alpha: f64) -> Vector2<f64> {
+        match self.killcam.replay.as_ref().and_then(|r| r.focus_tank()) {
+            Some(tank) => tank.render_position(alpha),
+            None => self.yourself.render_position(alpha),
+        }
+    }
+
+    /// Add an entity to the entity cache and its index, as if a census had brought it.
+    pub fn add_entity(&mut self, entity: Entity) {
+        self.index
+            .insert(entity.id(), entity.entity_type(), entity.net_position());
+        self.entities.insert(entity.id(), entity);
+    }
+
+    /// Replace the settings, and play by what changed in them from the next frame on: the lights,
+    /// the HUD, the network stats, the hit flashes and, if it's a built-in one, the theme. What
+    /// didn't change is left alone, so something set for just this session (like from the URL)
+    /// holds until its setting is changed.
+    pub fn apply_settings(&mut self, mut settings: Settings) {
+        let (min, max) = Settings::UI_SCALES;
+        settings.ui_scale = settings.ui_scale.clamp(min, max);
+        let old = &self.settings;
+        if settings.quality != old.quality || settings.lights != old.lights {
+            self.light_budget = if settings.lights {
+                settings.quality.light_budget()
+            } else {
+                0
+            };
+        }
+        if settings.ui_scale != old.ui_scale {
+            self.ui_scale = settings.ui_scale;
+        }
+        if settings.show_netstats != old.show_netstats {
+            self.netstats.visible = settings.show_netstats;
+        }
+        if settings.hit_flash != old.hit_flash {
+            self.hit_flash = settings.hit_flash();
+        }
+        if settings.theme != old.theme {
+            if let Some(theme) = Theme::built_in(&settings.theme) {
+                self.set_theme(theme);
+            }
+        }
+        self.settings = settings;
+    }
+
+    /// Draw everything in the colors of `theme` from the next frame on.
+    pub fn set_theme(&mut self, theme: Theme) {
+        // sprites are only told apart by their main color, not by the rest of the theme
+        self.sprites.clear();
+        self.theme = theme;
+    }
+
+    /// Forget everything that was learned from the server, apart from the handshake.
+    pub fn clear(&mut self) {
+        self.entities.clear();
+        self.index.clear();
+        self.killcam.clear();
+        self.leaderboard.entries.clear();
+        self.state.player_state = PlayerState::Alive;
+        self.state.death_animation_completion = Scalar::new(0.);
+        self.yourself.opacity.tv = 1.;
+    }
+
+    /// Move every entity straight to its last known network state, skipping interpolation.
+    pub fn snap_to_network(&mut self) {
         for entity in self.entities.values_mut() {
             match entity {
                 Entity::Tank(e) => {
-                    tanks.push(e);
-                    //e.draw(&self.ctx);
-                    //lights.push(e.light.clone());
+                    e.position = e.net_position;
+                    e.previous_position = e.net_position;
+                    e.rotation = e.net_rotation;
+                    e.previous_rotation = e.net_rotation;
+                }
+                Entity::Shape(e) => {
+                    e.position = e.net_position;
+                    e.previous_position = e.net_position;
+                }
+                Entity::Bullet(e) => {
+                    e.position = e.net_position;
+                    e.previous_position = e.net_position;
                 }
-                Entity::Shape(e) => cacti.push(e),
-                Entity::Bullet(e) => bullets.push(e),
             }
         }
+        self.yourself.position = self.yourself.net_position;
+        self.yourself.previous_position = self.yourself.net_position;
+        self.camera = self.yourself.net_position;
+        self.previous_camera = self.yourself.net_position;
+        self.size.snap();
+        self.state.level.snap();
+    }
 
-        for cactus in cacti {
-            cactus.draw(&self.ctx, delta);
+    fn play_entries(&mut self, player: &crate::replay::Player, range: std::ops::Range<usize>) {
+        for entry in &player.replay.entries[range] {
+            match &entry.record {
+                crate::replay::Record::Packet(data) => self.handle_packet(data.clone()),
+                crate::replay::Record::Input(input) => self.input = *input,
+            }
+        }
+    }
 
-            let angle = (self.yourself.position.y - cactus.position.y)
-                .atan2(self.yourself.position.x - cactus.position.x);
-            let right_angle = angle + PI / 2.;
-            let left_angle = angle - PI / 2.;
+    /// Play the entries of the replay being watched that became due in the last `delta`
+    /// milliseconds.
+    pub fn advance_playback(&mut self, delta: f64) {
+        if let Some(mut player) = self.playback.take() {
+            let range = player.advance(delta);
+            self.play_entries(&player, range);
+            self.playback = Some(player);
+        }
+    }
 
-            let right_point = Vector2 {
-                x: (right_angle.cos() * cactus.radius as f64 * cactus.opacity.value as f64)
-                    + cactus.position.x,
-                y: (right_angle.sin() * cactus.radius as f64 * cactus.opacity.value as f64)
-                    + cactus.position.y,
-            };
+    /// Jump to a tick of the replay being watched, rebuilding the world from the nearest
+    /// keyframe.
+    pub fn seek_playback(&mut self, tick: usize) {
+        if let Some(mut player) = self.playback.take() {
+            let range = player.seek(tick);
+            self.clear();
+            if let Some(leaderboard) = player.leaderboard_before(range.start) {
+                self.handle_packet(leaderboard.to_vec());
+            }
+            self.play_entries(&player, range);
+            self.snap_to_network();
+            self.playback = Some(player);
+        }
+    }
 
-            let left_point = Vector2 {
-                x: (left_angle.cos() * cactus.radius as f64 * cactus.opacity.value as f64)
-                    + cactus.position.x,
-                y: (left_angle.sin() * cactus.radius as f64 * cactus.opacity.value as f64)
-                    + cactus.position.y,
-            };
+    /// Handle a WebSocket message, which goes through the network simulator first.
+    pub fn on_message(&mut self, now: f64, data: Vec<u8>) {
+        if self.netsim.is_active() {
+            self.netsim.incoming.send(now, data);
+        } else {
+            self.receive(now, data);
+        }
+    }
 
-            // Now lets make a massive quad for every shadow...?
-            let right_angle = (self.yourself.position.y - right_point.y)
-                .atan2(self.yourself.position.x - right_point.x);
-            let left_angle = (self.yourself.position.y - left_point.y)
-                .atan2(self.yourself.position.x - left_point.x);
+    /// Send a packet to the server through the network simulator.
+    pub fn send<P: Protocol>(&mut self, ws: &WebSocket, now: f64, packet: &P) {
+        let data = packet.encode().cursor.into_inner();
+        if self.netsim.is_active() {
+            self.netsim.outgoing.send(now, data);
+        } else {
+            ws.send_with_u8_array(&data).ok();
+        }
+    }
 
-            let right_point2 = Vector2 {
-                x: right_point.x - (right_angle.cos() * 2200.),
-                y: right_point.y - (right_angle.sin() * 2200.),
-            };
+    /// Deliver the packets the network simulator has let through by `now`, in both directions.
+    pub fn flush_netsim(&mut self, ws: &WebSocket, now: f64) {
+        for data in self.netsim.outgoing.poll(now) {
+            ws.send_with_u8_array(&data).ok();
+        }
+        for data in self.netsim.incoming.poll(now) {
+            self.receive(now, data);
+        }
+    }
 
-            let left_point2 = Vector2 {
-                x: left_point.x - (left_angle.cos() * 2200.),
-                y: left_point.y - (left_angle.sin() * 2200.),
-            };
+    /// Handle a packet received from the server at `now` (in milliseconds).
+    pub fn receive(&mut self, now: f64, data: Vec<u8>) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record_packet(now, &data);
+        }
+        match data.first() {
+            Some(&protocol::Census::id) => self.killcam.push(now, &data),
+            Some(&protocol::DeathPacket::id) => {
+                self.killcam.start(self.yourself.id);
+            }
+            _ => {}
+        }
+        self.apply_packet(data, true);
+    }
 
-            shadows.push(Quadrilateral(
-                right_point,
-                left_point,
-                right_point2,
-                left_point2,
-            ));
+    /// Handle a packet from a replay, which isn't counted in `netstats`.
+    pub fn handle_packet(&mut self, data: Vec<u8>) {
+        self.apply_packet(data, false);
+    }
+
+    /// Handle a packet, counting it in `netstats` if it came from the server rather than a
+    /// replay, where seeking plays the same packets again.
+    fn apply_packet(&mut self, data: Vec<u8>, live: bool) {
+        let handshake =
+            (data.first() == Some(&protocol::HandshakePacket::id)).then(|| data.clone());
+        let length = data.len();
+        let mut buf = binary::StreamPeerBuffer::new();
+        buf.set_data_array(data);
+        let id = buf.get_u8();
+
+        let started = now();
+        let packet = protocol::decode(id, &mut buf);
+        if live {
+            self.netstats
+                .record_packet(started, id, length, now() - started);
+        }
+        // half a packet is worse than none
+        if let Some(error) = buf.error {
+            do_error_log!(
+                "Dropped a malformed {} packet: {}",
+                protocol::packet_name(id).unwrap_or("unknown"),
+                error
+            );
+            return;
+        }
+        if let Some(handshake) = handshake {
+            self.handshake = handshake;
         }
 
-        for tank in tanks {
-            tank.draw(&self.ctx, &self.mockups, delta);
+        match packet {
+            Some(protocol::Decoded::Census(census)) => {
+                // Decode the census and get your own id
+                // We'll need to check our own id against every entity later on.
+                if live {
+                    self.netstats.record_census(started, &census);
+                }
+                self.apply_census(census);
+            }
+            Some(protocol::Decoded::Handshake(res)) => {
+                do_success_log!(
+                    "Init packet has been acknowledged by the server! Our id is: {}",
+                    res.id
+                );
+                do_info_log!("Mockups: {:?}", res.mockups);
+                self.mockups = Some(res.mockups);
+                self.yourself.id = res.id;
+            }
+            Some(protocol::Decoded::Death(res)) => {
+                do_info_log!(
+                    "The server has delivered the unfortunate news of our death. We lived for {} seconds",
+                    res.time_alive
+                );
+                self.state.player_state = PlayerState::Dead(res.time_alive);
+            }
+            Some(protocol::Decoded::Leaderboard(res)) => {
+                self.leaderboard = res;
+            }
+            None => do_error_log!("Unknown packet id!"),
+            _ => {}
         }
+    }
 
-        for bullet in bullets {
-            bullet.draw(&self.ctx, delta);
+    /// Update our entity cache from a census.
+    pub fn apply_census(&mut self, census: protocol::Census) {
+        let yourself_id = self.yourself.id;
+
+        self.size.tv = census.arena_size as f32;
+        self.state.level.tv = census.level;
+
+        // If the census contains yourself, update `yourself`.
+        if let Some(protocol::Entity::Tank(t)) = census.entities.get(&yourself_id) {
+            self.yourself.net_position = t.position.into();
+            self.yourself.mockup = t.mockup;
+            self.yourself.radius = t.radius;
+            if self.yourself.health.tv > t.health {
+                self.yourself.since_hit = 0.;
+            }
+            self.yourself.health.tv = t.health;
+            self.yourself.message = t.message.clone();
         }
 
-        self.yourself.draw(&self.ctx, &self.mockups, delta);
-        shadows
+        update_entity_cache(
+            &mut self.entities,
+            &mut self.index,
+            &census,
+            yourself_id,
+            false,
+        );
+    }
+}
+
+/// Advance every entity of an entity cache by `dt` simulation steps.
+pub fn update_entities(entities: &mut HashMap<u32, Entity>, dt: f64) {
+    for entity in entities.values_mut() {
+        match entity {
+            Entity::Tank(e) => e.update(dt),
+            Entity::Shape(e) => e.update(dt),
+            Entity::Bullet(e) => e.update(dt),
+        }
+    }
+}
+
+/// Update an entity cache, and its `index`, from a census.
+///
+/// `yourself_id` decides the color of bullets. Our own tank is only added to the cache when
+/// `include_yourself` is set, since it normally lives in `World::yourself`.
+pub fn update_entity_cache(
+    entities: &mut HashMap<u32, Entity>,
+    index: &mut SpatialHash,
+    census: &protocol::Census,
+    yourself_id: u32,
+    include_yourself: bool,
+) {
+    // Lets check if any entities need to be removed from our cache.
+    // We can just look at all the entities in our cache that are not in the census.
+    // Think of it as a git diff but we can only see subtractions.
+
+    entities.retain(|k, e| {
+        let keep = match e {
+            Entity::Bullet(entity) => {
+                if entity.opacity.value < 0.05 {
+                    false
+                } else {
+                    if census.entities.contains_key(k) {
+                        true
+                    } else {
+                        entity.opacity.set_update(0.0, 0.1);
+                        entity.scale.set_update(2.0, 0.1);
+                        true
+                    }
+                }
+            }
+
+            Entity::Tank(entity) => {
+                if entity.opacity.value < 0.05 {
+                    false
+                } else {
+                    if census.entities.contains_key(k) {
+                        true
+                    } else {
+                        entity.opacity.set_update(0.0, 0.1);
+                        true
+                    }
+                }
+            }
+
+            Entity::Shape(entity) => {
+                if entity.opacity.value < 0.05 {
+                    false
+                } else {
+                    if census.entities.contains_key(k) {
+                        true
+                    } else {
+                        entity.opacity.set_update(0.0, 0.1);
+                        true
+                    }
+                }
+            }
+        };
+        if !keep {
+            index.remove(*k);
+        }
+        keep
+    });
+
+    for (id, entity) in &census.entities {
+        if *id == yourself_id && !include_yourself {
+            continue;
+        }
+
+        // Check what type the foreign entity is
+        match entity {
+            // It's a tank!
+            // Check whether it's in our local cache already
+            protocol::Entity::Tank(census_entity) => {
+                // It's in our local cache, lets update our cache.
+                if entities.contains_key(id) {
+                    // `game_entity` is our cached entity.
+                    let game_entity = entities.get_mut(id).unwrap();
+                    match game_entity {
+                        Entity::Tank(e) => {
+                            e.net_position = census_entity.position.into();
+                            e.net_rotation = census_entity.rotation as f64;
+                            e.mockup = census_entity.mockup;
+                            if !e.yourself {
+                                e.velocity = Vector2::from(census_entity.velocity) / 2.;
+                            }
+                            if census_entity.health < e.health.tv {
+                                e.since_hit = 0.;
+                            }
+                            e.health.tv = census_entity.health;
+                            e.radius = census_entity.radius;
+                            e.message = census_entity.message.clone();
+                        }
+                        _ => {}
+                    }
+                } else {
+                    // it's not in our cache, lets add it.
+                    entities.insert(
+                        *id,
+                        Entity::Tank(Tank {
+                            net_rotation: census_entity.rotation as f64,
+                            velocity: Vector2::from(census_entity.velocity) / 4.,
+                            rotation: census_entity.rotation as f64,
+                            previous_rotation: census_entity.rotation as f64,
+                            mockup: census_entity.mockup,
+                            health: Scalar::new(census_entity.health),
+                            radius: census_entity.radius,
+                            message: census_entity.message.clone(),
+                            ..Tank::new(
+                                *id,
+                                census_entity.name.clone(),
+                                census_entity.position.into(),
+                            )
+                        }),
+                    );
+                }
+            }
+            protocol::Entity::Shape(census_entity) => {
+                // It's in our local cache, lets update our cache.
+                if entities.contains_key(id) {
+                    // `game_entity` is our cached entity.
+                    let game_entity = entities.get_mut(id).unwrap();
+                    match game_entity {
+                        Entity::Shape(e) => {
+                            e.net_position = census_entity.position.into();
+                            if census_entity.health < e.health {
+                                e.since_hit = 0.;
+                            }
+                            e.health = census_entity.health;
+                        }
+                        _ => {}
+                    }
+                } else {
+                    // it's not in our cache, lets add it.
+                    entities.insert(
+                        *id,
+                        Entity::Shape(Shape {
+                            rotation: census_entity.position.x as f32
+                                + census_entity.position.y as f32,
+                            health: census_entity.health,
+                            ..Shape::new(
+                                *id,
+                                census_entity.position.into(),
+                                ((random() * 10.) + 10.) as u8,
+                                census_entity.radius,
+                            )
+                        }),
+                    );
+                }
+            }
+            protocol::Entity::Bullet(census_entity) => {
+                // It's in our local cache, lets update our cache.
+                if entities.contains_key(id) {
+                    // `game_entity` is our cached entity.
+                    let game_entity = entities.get_mut(id).unwrap();
+                    match game_entity {
+                        Entity::Bullet(e) => {
+                            e.net_position = census_entity.position.into();
+
+                            e.velocity = Vector2::from(census_entity.velocity) / 3.;
+                        }
+                        _ => {}
+                    }
+                } else {
+                    // it's not in our cache, lets add it.
+                    entities.insert(
+                        *id,
+                        Entity::Bullet(Bullet {
+                            velocity: Vector2::from(census_entity.velocity) / 3.,
+                            ..Bullet::new(
+                                *id,
+                                census_entity.position.into(),
+                                census_entity.radius,
+                                census_entity.owner == yourself_id,
+                            )
+                        }),
+                    );
+                }
+            }
+        }
+
+        if let Some(entity) = entities.get(id) {
+            index.insert(*id, entity.entity_type(), entity.net_position());
+        }
     }
 }
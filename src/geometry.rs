@@ -0,0 +1,281 @@
+//! Shapes and intersection tests for culling, shadows and prediction.
+//!
+//! Everything works on `Vector2<f64>` in world units, and touching counts as intersecting.
+
+use crate::util::Vector2;
+use std::f64::consts::TAU;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Circle {
+    pub center: Vector2<f64>,
+    pub radius: f64,
+}
+
+/// A line segment from `a` to `b`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Segment {
+    pub a: Vector2<f64>,
+    pub b: Vector2<f64>,
+}
+
+/// An axis aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vector2<f64>,
+    pub max: Vector2<f64>,
+}
+
+impl Circle {
+    pub fn new(center: Vector2<f64>, radius: f64) -> Self {
+        Self { center, radius }
+    }
+
+    pub fn contains(&self, point: Vector2<f64>) -> bool {
+        (point - self.center).length_squared() <= self.radius * self.radius
+    }
+
+    pub fn intersects_circle(&self, other: &Circle) -> bool {
+        let reach = self.radius + other.radius;
+        (other.center - self.center).length_squared() <= reach * reach
+    }
+
+    pub fn intersects_segment(&self, segment: &Segment) -> bool {
+        self.contains(segment.closest_point(self.center))
+    }
+
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.contains(aabb.closest_point(self.center))
+    }
+
+    /// The smallest box around the circle.
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_center(self.center, Vector2::new(self.radius, self.radius))
+    }
+}
+
+impl Segment {
+    pub fn new(a: Vector2<f64>, b: Vector2<f64>) -> Self {
+        Self { a, b }
+    }
+
+    pub fn length(&self) -> f64 {
+        self.a.distance(self.b)
+    }
+
+    /// The point of the segment closest to `point`.
+    pub fn closest_point(&self, point: Vector2<f64>) -> Vector2<f64> {
+        let direction = self.b - self.a;
+        let length_squared = direction.length_squared();
+        if length_squared == 0. {
+            return self.a;
+        }
+        let t = ((point - self.a).dot(direction) / length_squared).clamp(0., 1.);
+        self.a + direction * t
+    }
+
+    pub fn distance_to(&self, point: Vector2<f64>) -> f64 {
+        self.closest_point(point).distance(point)
+    }
+
+    /// Where the segments cross, if they do. Overlapping parallel segments don't have a single
+    /// crossing point and give `None`.
+    pub fn intersection(&self, other: &Segment) -> Option<Vector2<f64>> {
+        let direction = self.b - self.a;
+        let other_direction = other.b - other.a;
+        let denominator = direction.cross(other_direction);
+        if denominator == 0. {
+            return None;
+        }
+        let offset = other.a - self.a;
+        let t = offset.cross(other_direction) / denominator;
+        let u = offset.cross(direction) / denominator;
+        if (0. ..=1.).contains(&t) && (0. ..=1.).contains(&u) {
+            Some(self.a + direction * t)
+        } else {
+            None
+        }
+    }
+
+    pub fn intersects_segment(&self, other: &Segment) -> bool {
+        self.intersection(other).is_some()
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vector2<f64>, max: Vector2<f64>) -> Self {
+        Self { min, max }
+    }
+
+    /// A box reaching `half_size` out from `center` in each direction.
+    pub fn from_center(center: Vector2<f64>, half_size: Vector2<f64>) -> Self {
+        Self {
+            min: center - half_size,
+            max: center + half_size,
+        }
+    }
+
+    pub fn center(&self) -> Vector2<f64> {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn size(&self) -> Vector2<f64> {
+        self.max - self.min
+    }
+
+    pub fn contains(&self, point: Vector2<f64>) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    pub fn intersects_aabb(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    /// The point of the box closest to `point`, which is `point` itself if it's inside.
+    pub fn closest_point(&self, point: Vector2<f64>) -> Vector2<f64> {
+        Vector2 {
+            x: point.x.clamp(self.min.x, self.max.x),
+            y: point.y.clamp(self.min.y, self.max.y),
+        }
+    }
+
+    /// Whether the segment passes through the box, using the slab test.
+    pub fn intersects_segment(&self, segment: &Segment) -> bool {
+        let direction = segment.b - segment.a;
+        let mut enter = 0f64;
+        let mut exit = 1f64;
+        for (start, direction, min, max) in [
+            (segment.a.x, direction.x, self.min.x, self.max.x),
+            (segment.a.y, direction.y, self.min.y, self.max.y),
+        ] {
+            if direction == 0. {
+                if start < min || start > max {
+                    return false;
+                }
+                continue;
+            }
+            let (mut near, mut far) = ((min - start) / direction, (max - start) / direction);
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            enter = enter.max(near);
+            exit = exit.min(far);
+            if enter > exit {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The box grown by `margin` on every side.
+    pub fn expand(&self, margin: f64) -> Self {
+        let margin = Vector2::new(margin, margin);
+        Self {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+}
+
+/// Whether `point` is inside the polygon, by the even-odd rule.
+pub fn polygon_contains(polygon: &[Vector2<f64>], point: Vector2<f64>) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(vertex) => *vertex,
+        None => return false,
+    };
+    for vertex in polygon {
+        if (vertex.y > point.y) != (previous.y > point.y) {
+            let x =
+                vertex.x + (point.y - vertex.y) / (previous.y - vertex.y) * (previous.x - vertex.x);
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+        previous = *vertex;
+    }
+    inside
+}
+
+/// How many rays sample the edge of the light where nothing is in the way.
+const BOUNDARY_RAYS: usize = 96;
+
+/// How many rays sample the lit side of each occluder between its tangents.
+const ARC_RAYS: usize = 6;
+
+/// How far to either side of a tangent the rays that find the edge of a shadow are cast.
+const TANGENT_OFFSET: f64 = 1e-4;
+
+/// How far along a ray from `origin` in `direction` (a unit vector) it first hits the circle,
+/// if it does.
+fn ray_circle(origin: Vector2<f64>, direction: Vector2<f64>, circle: &Circle) -> Option<f64> {
+    let offset = origin - circle.center;
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - circle.radius * circle.radius;
+    let discriminant = b * b - c;
+    if discriminant < 0. {
+        return None;
+    }
+    let t = -b - discriminant.sqrt();
+    if t >= 0. {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// The region lit by a light at `origin` reaching `range` units, with `occluders` casting
+/// shadows, as a polygon in order of angle around the light.
+///
+/// Rays are cast just either side of the tangents of every occluder, across the side of the
+/// occluder facing the light and around the edge of the light, and each one stops at the
+/// nearest occluder it hits. Occluders the light is inside of (like the tank carrying it) are
+/// ignored.
+pub fn visibility_polygon(
+    origin: Vector2<f64>,
+    range: f64,
+    occluders: &[Circle],
+) -> Vec<Vector2<f64>> {
+    let occluders: Vec<&Circle> = occluders
+        .iter()
+        .filter(|c| !c.contains(origin) && c.center.distance(origin) < range + c.radius)
+        .collect();
+
+    let mut angles: Vec<f64> = (0..BOUNDARY_RAYS)
+        .map(|i| i as f64 / BOUNDARY_RAYS as f64 * TAU)
+        .collect();
+    for occluder in &occluders {
+        let to_center = occluder.center - origin;
+        let center_angle = to_center.angle();
+        let half_width = (occluder.radius / to_center.length()).asin();
+        angles.push(center_angle - half_width - TANGENT_OFFSET);
+        angles.push(center_angle + half_width + TANGENT_OFFSET);
+        for i in 0..=ARC_RAYS {
+            let t = i as f64 / ARC_RAYS as f64;
+            angles.push(
+                center_angle - half_width + TANGENT_OFFSET + t * (half_width - TANGENT_OFFSET) * 2.,
+            );
+        }
+    }
+    for angle in &mut angles {
+        *angle = angle.rem_euclid(TAU);
+    }
+    angles.sort_by(f64::total_cmp);
+
+    angles
+        .into_iter()
+        .map(|angle| {
+            let direction = Vector2::from_angle(angle);
+            let distance = occluders
+                .iter()
+                .filter_map(|occluder| ray_circle(origin, direction, occluder))
+                .fold(range, f64::min);
+            origin + direction * distance
+        })
+        .collect()
+}
@@ -0,0 +1,366 @@
+//! The HUD, drawn over the world in design units (4000 of them across the width and height of
+//! the window together, at a UI scale of 1). Its panels are placed with a `Layout`, so that
+//! they move out of each other's way on narrow and portrait windows.
+
+use crate::draw::*;
+use crate::engine::{Entity, World};
+use crate::layout::{Anchor, Layout};
+use crate::renderer::Renderer;
+use crate::util::{self, Vector2};
+
+/// How far panels are from the edges of the screen, in design units.
+pub const MARGIN: f64 = 30.;
+/// How far apart stacked panels are.
+pub const GAP: f64 = 15.;
+/// The narrowest the screen gets in design units, so that the widest panel fits across it.
+pub const MIN_WIDTH: f64 = 1300.;
+
+/// How many pixels a design unit takes up on a `width` by `height` window, at a UI scale of
+/// `ui_scale`. However big the UI scale, the screen stays `MIN_WIDTH` design units across.
+pub fn scale(width: f64, height: f64, ui_scale: f64) -> f64 {
+    ((width + height) / (2000. + 2000.) * ui_scale).min(width / MIN_WIDTH)
+}
+
+/// Format milliseconds as minutes and seconds.
+fn format_time(ms: f64) -> String {
+    let seconds = (ms / 1000.) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Stroke and fill `text` centered on `x`.
+fn centered_text(ctx: &mut dyn Renderer, text: &str, x: f64, y: f64) {
+    let measurement = ctx.measure_text(text);
+    ctx.stroke_text(text, x - measurement / 2., y);
+    ctx.fill_text(text, x - measurement / 2., y);
+}
+
+/// Draw the HUD on a `width` by `height` screen, in design units, and return where its panels
+/// went. `frame` counts the frames drawn so far, for animations.
+pub fn draw(ctx: &mut dyn Renderer, world: &World, frame: u32, width: f64, height: f64) -> Layout {
+    let colors = &world.theme.hud;
+    let mut layout = Layout::new(Vector2::new(width, height), MARGIN, GAP);
+
+    ctx.set_font("75px \"Fira Sans\"");
+    ctx.save();
+    ctx.set_shadow_blur(((frame as f64 / 50.).sin() + 2.) * 20.);
+    ctx.set_shadow_color(colors.title.css());
+    ctx.set_fill_color(colors.title.css());
+    ctx.set_stroke_color(colors.text_outline.css());
+    ctx.set_line_width(10.);
+    let text = "CactusWar.io";
+    let title = layout.place(
+        Anchor::TopLeft,
+        Vector2::new(ctx.measure_text(text) + 40., 90.),
+    );
+    ctx.fill_text(text, title.min.x + 20., title.min.y + 70.);
+    ctx.set_shadow_blur(0.);
+
+    if let Some(mockups) = &world.mockups {
+        // leaderboard
+        ctx.save();
+        ctx.set_font("50px \"Fira Sans\"");
+        ctx.set_fill_color(colors.text.css());
+
+        let panel = layout.place(
+            Anchor::TopRight,
+            Vector2::new(525., 120. + world.leaderboard.entries.len() as f64 * 65.),
+        );
+        draw_rect_no_correction(
+            ctx,
+            panel.min.x,
+            panel.min.y,
+            panel.size().x,
+            panel.size().y,
+            0.,
+            &colors.panel,
+        );
+
+        let text = "Leaderboard";
+        ctx.stroke_text(text, panel.min.x + 90., panel.min.y + 60.);
+        ctx.fill_text(text, panel.min.x + 90., panel.min.y + 60.);
+
+        ctx.set_font("30px \"Fira Sans\"");
+        ctx.set_line_width(5.);
+
+        if let Some(entry) = world.leaderboard.entries.first() {
+            let max_level = entry.level;
+            let left = panel.min.x + 55.;
+            for (index, entry) in world.leaderboard.entries.iter().enumerate() {
+                let text = &*format!(
+                    "{}  ➤  Level {}",
+                    if entry.name.as_str().is_empty() {
+                        "Unnamed Tank"
+                    } else {
+                        entry.name.as_str()
+                    },
+                    entry.level as u32,
+                );
+                let y = panel.min.y + 130. + index as f64 * 65.;
+                let level = entry.level / max_level;
+
+                draw_bar(
+                    ctx,
+                    left,
+                    left + 400.0,
+                    y - 10.0,
+                    60.0,
+                    &colors.bar_background,
+                );
+                draw_bar(
+                    ctx,
+                    left,
+                    left + (400.0 * level as f64),
+                    y - 10.0,
+                    40.0,
+                    &colors.leaderboard_level,
+                );
+
+                ctx.stroke_text(text, left, y);
+                ctx.fill_text(text, left, y);
+            }
+        }
+        ctx.restore();
+
+        // level and name
+        ctx.save();
+        ctx.set_font("50px \"Fira Sans\"");
+        ctx.set_fill_color(colors.text.css());
+
+        let level_percentage = world.state.level.value.fract();
+        let bar_length = 800.;
+        const BAR_WIDTH: f64 = 79.;
+        const LONGER_BAR_WIDTH: f64 = BAR_WIDTH + 20.;
+        let panel = layout.place(Anchor::BottomRight, Vector2::new(bar_length + 130., 245.));
+        let left = panel.min.x + 60.;
+        draw_rect_no_correction(
+            ctx,
+            panel.min.x,
+            panel.min.y,
+            panel.size().x,
+            panel.size().y,
+            0.,
+            &colors.panel,
+        );
+        draw_bar(
+            ctx,
+            left,
+            left + bar_length,
+            panel.max.y - 70.,
+            LONGER_BAR_WIDTH,
+            &colors.bar_background,
+        );
+        draw_bar(
+            ctx,
+            left,
+            left + bar_length * level_percentage as f64,
+            panel.max.y - 70.,
+            BAR_WIDTH,
+            &colors.level,
+        );
+
+        ctx.set_shadow_color(colors.text_shadow.css());
+        ctx.set_shadow_blur(5.);
+        let text = &*format!(
+            "Level {} {}",
+            world.state.level.tv as u32, mockups[world.yourself.mockup as usize].name
+        );
+        ctx.stroke_text(text, left, panel.max.y - 55.);
+        ctx.fill_text(text, left, panel.max.y - 55.);
+
+        ctx.set_font("66px \"Fira Sans\"");
+        let text = if world.yourself.name.as_str().is_empty() {
+            "Unnamed Tank"
+        } else {
+            world.yourself.name.as_str()
+        };
+        ctx.stroke_text(text, left, panel.max.y - 157.5);
+        ctx.fill_text(text, left, panel.max.y - 157.5);
+        ctx.restore();
+    }
+
+    if let Some(player) = &world.playback {
+        let progress = if player.tick_count() > 0 {
+            (player.tick() + 1) as f64 / player.tick_count() as f64
+        } else {
+            0.
+        };
+        let panel = layout.place(Anchor::Top, Vector2::new(1200., 110.));
+        draw_bar(
+            ctx,
+            panel.min.x,
+            panel.max.x,
+            panel.min.y + 30.,
+            30.,
+            &colors.bar_background,
+        );
+        draw_bar(
+            ctx,
+            panel.min.x,
+            panel.min.x + 1200. * progress,
+            panel.min.y + 30.,
+            18.,
+            &colors.progress,
+        );
+
+        ctx.save();
+        ctx.set_font("36px \"Fira Sans\"");
+        ctx.set_fill_color(colors.text.css());
+        ctx.set_line_width(8.);
+        let text = format!(
+            "{}  {}x    Tick {}/{}    {} / {}{}",
+            if player.paused { "PAUSED" } else { "REPLAY" },
+            player.speed(),
+            player.tick() + 1,
+            player.tick_count(),
+            format_time(player.time.min(player.replay.duration())),
+            format_time(player.replay.duration()),
+            if player.free_camera {
+                "    Free camera"
+            } else {
+                ""
+            },
+        );
+        centered_text(ctx, &text, panel.center().x, panel.min.y + 100.);
+        ctx.restore();
+    }
+
+    if let Some(recorder) = &world.recorder {
+        ctx.save();
+        ctx.set_font("36px \"Fira Sans\"");
+        ctx.set_fill_color(colors.recording.css());
+        let text = format!("● REC {} KB", recorder.size() / 1024);
+        let line = layout.place(
+            Anchor::TopLeft,
+            Vector2::new(ctx.measure_text(&text) + 40., 45.),
+        );
+        ctx.fill_text(&text, line.min.x + 20., line.min.y + 36.);
+        ctx.restore();
+    }
+
+    if !world.netsim.conditions().is_perfect() {
+        ctx.save();
+        ctx.set_font("36px \"Fira Sans\"");
+        ctx.set_fill_color(colors.netsim.css());
+        let text = format!("NETSIM {}", world.netsim.conditions());
+        let line = layout.place(
+            Anchor::TopLeft,
+            Vector2::new(ctx.measure_text(&text) + 40., 45.),
+        );
+        ctx.fill_text(&text, line.min.x + 20., line.min.y + 36.);
+        ctx.restore();
+    }
+
+    if world.netstats.visible {
+        let lines = world.netstats.overlay_lines(util::now());
+        let panel = layout.place(
+            Anchor::TopLeft,
+            Vector2::new(1100., 30. + lines.len() as f64 * 34.),
+        );
+        draw_rect_no_correction(
+            ctx,
+            panel.min.x,
+            panel.min.y,
+            panel.size().x,
+            panel.size().y,
+            0.,
+            &colors.panel,
+        );
+        ctx.save();
+        ctx.set_font("28px monospace");
+        ctx.set_fill_color(colors.text.css());
+        for (index, line) in lines.iter().enumerate() {
+            let y = panel.min.y + 40. + index as f64 * 34.;
+            ctx.fill_text(line, panel.min.x + 20., y);
+        }
+        ctx.restore();
+    }
+
+    // death screen
+    if world.mockups.is_some() {
+        // with the shadow of the level and name, which darkens the overlay too
+        ctx.set_shadow_color(colors.text_shadow.css());
+        ctx.set_shadow_blur(5.);
+        if let Some(replay) = &world.killcam.replay {
+            // the stats are shown once the killcam is over
+            let subtitle = match replay.killer {
+                Some(_) if replay.wide => String::from("Wide camera"),
+                Some(killer) => match replay.entities.get(&killer) {
+                    Some(Entity::Tank(t)) if !t.name.is_empty() => {
+                        format!("Killed by {}", t.name)
+                    }
+                    _ => String::from("Killed by an unnamed tank"),
+                },
+                None => String::from("Killer unknown"),
+            };
+            let progress = (replay.time / replay.duration().max(1.)).min(1.);
+
+            ctx.save();
+            ctx.set_font("44px \"Fira Sans\"");
+            let panel_width = ctx.measure_text(&subtitle).max(600.);
+            let panel = layout.place(Anchor::Top, Vector2::new(panel_width, 280.));
+            let center = panel.center().x;
+
+            ctx.set_font("104px \"Fira Sans\"");
+            ctx.set_fill_color(colors.progress.css());
+            centered_text(ctx, "KILLCAM", center, panel.min.y + 90.);
+
+            draw_bar(
+                ctx,
+                center - 300.,
+                center + 300.,
+                panel.min.y + 140.,
+                20.,
+                &colors.bar_background,
+            );
+            draw_bar(
+                ctx,
+                center - 300.,
+                center - 300. + 600. * progress,
+                panel.min.y + 140.,
+                10.,
+                &colors.progress,
+            );
+
+            ctx.set_font("44px \"Fira Sans\"");
+            ctx.set_fill_color(colors.text.css());
+            centered_text(ctx, &subtitle, center, panel.min.y + 210.);
+
+            ctx.set_font("24px \"Fira Sans\"");
+            let text = "(Space To Skip, C To Switch Camera)";
+            centered_text(ctx, text, center, panel.min.y + 260.);
+            ctx.restore();
+        } else if world.state.is_dead() {
+            ctx.set_global_alpha(world.state.death_animation_completion.value as f64);
+            ctx.set_fill_color(colors.death_overlay.css());
+            ctx.fill_rect(0.0, 0.0, width, height);
+
+            let panel = layout.place(Anchor::Center, Vector2::new(600., 330.));
+            let center = panel.center().x;
+            ctx.set_font("104px \"Fira Sans\"");
+            ctx.set_fill_color(colors.text.css());
+            centered_text(ctx, "YOU DIED!", center, panel.min.y + 90.);
+
+            ctx.set_font("44px \"Fira Sans\"");
+
+            let duration: humantime::Duration =
+                std::time::Duration::from_secs(world.state.time_alive() as u64).into();
+            let text = format!("Time alive: {}", duration);
+            centered_text(ctx, &text, center, panel.min.y + 190.);
+
+            let text = format!("Level: {}", world.state.level.value.floor());
+            centered_text(ctx, &text, center, panel.min.y + 250.);
+
+            ctx.set_global_alpha(
+                world.state.death_animation_completion.value as f64
+                    * (((frame as f64 / 7.5).sin() + 1.) / 2.),
+            );
+
+            ctx.set_font("24px \"Fira Sans\"");
+            let text = "(Press Enter To Continue)";
+            centered_text(ctx, text, center, panel.min.y + 310.);
+        }
+    }
+
+    ctx.restore();
+    layout
+}
@@ -0,0 +1,76 @@
+//! Decides when the local input is worth sending to the server.
+//!
+//! Input used to be sent every animation frame, whether it changed or not. Now it is only sent
+//! when it changes (with the mouse snapped to a grid, so tiny movements don't count), no more
+//! often than `max_rate` times a second, plus a heartbeat when nothing changes for a while.
+
+use crate::engine::Input;
+
+/// Inputs are sent at most this many times a second.
+pub const MAX_RATE: f64 = 30.;
+
+/// An unchanged input is sent again after this many milliseconds, so the server knows we're
+/// still here.
+pub const HEARTBEAT: f64 = 1000.;
+
+/// The mouse position is rounded to a multiple of this, in world units.
+pub const MOUSE_QUANTUM: i16 = 4;
+
+pub struct InputSender {
+    pub max_rate: f64,
+    pub heartbeat: f64,
+    pub mouse_quantum: i16,
+    last_sent: Option<Input>,
+    last_sent_at: f64,
+}
+
+impl InputSender {
+    pub fn new() -> Self {
+        Self {
+            max_rate: MAX_RATE,
+            heartbeat: HEARTBEAT,
+            mouse_quantum: MOUSE_QUANTUM,
+            last_sent: None,
+            last_sent_at: 0.,
+        }
+    }
+
+    fn quantize(&self, value: i16) -> i16 {
+        let quantum = self.mouse_quantum.max(1) as f64;
+        ((value as f64 / quantum).round() * quantum) as i16
+    }
+
+    /// Called every frame with the current input. Returns the input to send at `now` (in
+    /// milliseconds), if any.
+    pub fn poll(&mut self, now: f64, input: Input) -> Option<Input> {
+        let mut input = input;
+        input.mouse_position.x = self.quantize(input.mouse_position.x);
+        input.mouse_position.y = self.quantize(input.mouse_position.y);
+
+        let since_last = now - self.last_sent_at;
+        let send = match self.last_sent {
+            None => true,
+            Some(last) if last != input => since_last >= 1000. / self.max_rate,
+            Some(_) => since_last >= self.heartbeat,
+        };
+        if send {
+            self.last_sent = Some(input);
+            self.last_sent_at = now;
+            Some(input)
+        } else {
+            None
+        }
+    }
+
+    /// Forget what was sent last, so the next input goes out straight away. Used when the
+    /// server may have forgotten it, like after respawning.
+    pub fn reset(&mut self) {
+        self.last_sent = None;
+    }
+}
+
+impl Default for InputSender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
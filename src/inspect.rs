@@ -0,0 +1,82 @@
+//! Packet dissection for the packet inspector (`src/bin/inspect.rs`).
+//!
+//! Packets are decoded with the same `protocol` types the client uses, with every problem the
+//! decoder runs into reported along with its byte offset inside the packet.
+
+use crate::binary::{DecodeError, StreamPeerBuffer};
+use crate::protocol;
+use serde::Serialize;
+
+/// A packet taken apart.
+#[derive(Debug, Serialize)]
+pub struct Dissection {
+    pub id: Option<u8>,
+    pub name: Option<&'static str>,
+    /// The length of the packet in bytes, id included.
+    pub length: usize,
+    pub packet: Option<protocol::Decoded>,
+    /// The first problem found, with the offset counted from the start of the packet.
+    pub error: Option<DecodeError>,
+}
+
+/// Look up a packet id by its name or number.
+pub fn packet_id(name: &str) -> Option<u8> {
+    match name.parse::<u8>() {
+        Ok(id) => Some(id),
+        Err(_) => (0..=u8::MAX).find(|id| protocol::packet_name(*id) == Some(name)),
+    }
+}
+
+/// Decode a packet, id included.
+pub fn dissect(data: &[u8]) -> Dissection {
+    let mut dissection = Dissection {
+        id: data.first().copied(),
+        name: data.first().and_then(|id| protocol::packet_name(*id)),
+        length: data.len(),
+        packet: None,
+        error: None,
+    };
+    let id = match dissection.id {
+        Some(id) => id,
+        None => {
+            dissection.error = Some(DecodeError {
+                offset: 0,
+                message: String::from("empty packet"),
+                truncated: true,
+            });
+            return dissection;
+        }
+    };
+
+    let mut buf = StreamPeerBuffer::new();
+    buf.set_data_array(data.to_vec());
+    buf.get_u8();
+    dissection.packet = protocol::decode(id, &mut buf);
+    if dissection.packet.is_none() {
+        buf.fail(0, format!("unknown packet id {}", id));
+    }
+
+    if dissection.packet.is_some() && buf.remaining() > 0 {
+        let offset = buf.cursor.position() as usize;
+        buf.fail(
+            offset,
+            format!("unexpected trailing data ({} bytes)", buf.remaining()),
+        );
+    }
+    dissection.error = buf.error;
+    dissection
+}
+
+impl Dissection {
+    /// Drop the census entities whose id isn't in `ids`. Returns false if the packet is a census
+    /// and none of its entities are left.
+    pub fn retain_entities(&mut self, ids: &[u32]) -> bool {
+        match &mut self.packet {
+            Some(protocol::Decoded::Census(census)) => {
+                census.entities.retain(|id, _| ids.contains(id));
+                !census.entities.is_empty()
+            }
+            _ => true,
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! The killcam, a short replay of the moments before our death.
+//!
+//! The last few seconds of censuses are kept around at all times. When we die, they are played
+//! back into an entity cache of their own, which `World::draw_entities` draws in place of the
+//! live entities until the killcam ends.
+
+use crate::binary;
+use crate::engine::{self, Entity};
+use crate::protocol::{self, Protocol};
+use crate::spatial::SpatialHash;
+use crate::util::Vector2;
+use std::collections::{HashMap, VecDeque};
+
+/// How much history is kept for the killcam, in milliseconds.
+pub const BUFFER_LENGTH: f64 = 5000.;
+
+fn decode_census(data: &[u8]) -> protocol::Census {
+    let mut buf = binary::StreamPeerBuffer::new();
+    buf.set_data_array(data.to_vec());
+    buf.get_u8();
+    protocol::Census::decode(&mut buf)
+}
+
+/// Keeps the recent censuses and plays them back on death.
+pub struct Killcam {
+    /// Raw censuses, with the time they were received at.
+    buffer: VecDeque<(f64, Vec<u8>)>,
+    /// The killcam being watched.
+    pub replay: Option<KillcamReplay>,
+}
+
+impl Killcam {
+    pub fn new() -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            replay: None,
+        }
+    }
+
+    /// Remember a census that was received at `now` (in milliseconds).
+    pub fn push(&mut self, now: f64, data: &[u8]) {
+        self.buffer.push_back((now, data.to_vec()));
+        while let Some((time, _)) = self.buffer.front() {
+            if now - time <= BUFFER_LENGTH {
+                break;
+            }
+            self.buffer.pop_front();
+        }
+    }
+
+    /// Forget the buffered censuses and stop the killcam.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        self.replay = None;
+    }
+
+    /// Start playing the buffered censuses back. Returns false if there was nothing to play.
+    pub fn start(&mut self, yourself_id: u32) -> bool {
+        let frames: Vec<_> = self
+            .buffer
+            .drain(..)
+            .map(|(time, data)| (time, decode_census(&data)))
+            .collect();
+        if frames.is_empty() {
+            return false;
+        }
+        self.replay = Some(KillcamReplay::new(frames, yourself_id));
+        true
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Play the censuses that became due in the last `delta` milliseconds, and stop once the
+    /// killcam is over.
+    pub fn advance(&mut self, delta: f64) {
+        if let Some(replay) = &mut self.replay {
+            replay.advance(delta);
+            if replay.finished() {
+                self.replay = None;
+            }
+        }
+    }
+}
+
+impl Default for Killcam {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Guess who killed us from the last census before our death: the owner of the nearest
+/// bullet that isn't ours, or else the nearest tank.
+fn find_killer(census: &protocol::Census, yourself_id: u32) -> Option<u32> {
+    let position = match census.entities.get(&yourself_id) {
+        Some(protocol::Entity::Tank(t)) => t.position,
+        _ => return None,
+    };
+    let distance =
+        |other: Vector2<i16>| Vector2::<f64>::from(other).distance(Vector2::from(position));
+
+    let bullet = census
+        .entities
+        .values()
+        .filter_map(|entity| match entity {
+            protocol::Entity::Bullet(b) if b.owner != yourself_id => Some(b),
+            _ => None,
+        })
+        .min_by(|a, b| distance(a.position).total_cmp(&distance(b.position)));
+    if let Some(bullet) = bullet {
+        return Some(bullet.owner);
+    }
+
+    census
+        .entities
+        .values()
+        .filter_map(|entity| match entity {
+            protocol::Entity::Tank(t) if t.id != yourself_id => Some(t),
+            _ => None,
+        })
+        .min_by(|a, b| distance(a.position).total_cmp(&distance(b.position)))
+        .map(|t| t.id)
+}
+
+/// A killcam that is being watched.
+pub struct KillcamReplay {
+    frames: Vec<(f64, protocol::Census)>,
+    /// The index of the next census to play.
+    cursor: usize,
+    /// The playback position in milliseconds, relative to the first census.
+    pub time: f64,
+    pub yourself_id: u32,
+    /// The tank we think killed us.
+    pub killer: Option<u32>,
+    /// Show the whole fight around our own tank instead of following the killer.
+    pub wide: bool,
+    /// The entities of the killcam, drawn in place of the live ones.
+    pub entities: HashMap<u32, Entity>,
+    pub index: SpatialHash,
+}
+
+impl KillcamReplay {
+    fn new(frames: Vec<(f64, protocol::Census)>, yourself_id: u32) -> Self {
+        let killer = frames
+            .iter()
+            .rev()
+            .find(|(_, census)| census.entities.contains_key(&yourself_id))
+            .and_then(|(_, census)| find_killer(census, yourself_id));
+        let start = frames[0].0;
+        let frames = frames
+            .into_iter()
+            .map(|(time, census)| (time - start, census))
+            .collect();
+        Self {
+            frames,
+            cursor: 0,
+            time: 0.,
+            yourself_id,
+            killer,
+            wide: killer.is_none(),
+            entities: HashMap::new(),
+            index: SpatialHash::default(),
+        }
+    }
+
+    /// The length of the killcam in milliseconds.
+    pub fn duration(&self) -> f64 {
+        self.frames.last().map(|(time, _)| *time).unwrap_or(0.)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.frames.len() && self.time >= self.duration() + 500.
+    }
+
+    /// Move forward by `delta` milliseconds, applying the censuses that became due.
+    pub fn advance(&mut self, delta: f64) {
+        self.time += delta;
+        while self.cursor < self.frames.len() && self.frames[self.cursor].0 <= self.time {
+            engine::update_entity_cache(
+                &mut self.entities,
+                &mut self.index,
+                &self.frames[self.cursor].1,
+                self.yourself_id,
+                true,
+            );
+            self.cursor += 1;
+        }
+    }
+
+    /// The tank the camera follows: the killer, or ourselves with the wide camera.
+    pub fn focus_tank(&self) -> Option<&engine::Tank> {
+        let id = match self.killer {
+            Some(killer) if !self.wide => killer,
+            _ => self.yourself_id,
+        };
+        match self.entities.get(&id) {
+            Some(Entity::Tank(t)) => Some(t),
+            _ => None,
+        }
+    }
+}
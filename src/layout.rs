@@ -0,0 +1,124 @@
+//! Where the panels of the HUD go.
+//!
+//! Panels are anchored to a corner, an edge or the middle of the screen, `margin` in from its
+//! edges. Panels placed at the same anchor stack up away from it: down from the top and the
+//! middle of the screen, and up from the bottom. A panel that would overlap one placed before it
+//! is pushed further along the way it stacks, so on narrow or portrait screens panels end up
+//! under each other rather than on top of each other. Place the most important panels first.
+
+use crate::geometry::Aabb;
+use crate::util::Vector2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    /// Where panels go horizontally: 0 at the left, 0.5 in the middle, 1 at the right.
+    fn horizontal(self) -> f64 {
+        match self {
+            Anchor::TopLeft | Anchor::Left | Anchor::BottomLeft => 0.,
+            Anchor::Top | Anchor::Center | Anchor::Bottom => 0.5,
+            Anchor::TopRight | Anchor::Right | Anchor::BottomRight => 1.,
+        }
+    }
+
+    /// Whether panels stack up from the bottom of the screen rather than down.
+    fn stacks_up(self) -> bool {
+        matches!(
+            self,
+            Anchor::BottomLeft | Anchor::Bottom | Anchor::BottomRight
+        )
+    }
+
+    fn in_middle(self) -> bool {
+        matches!(self, Anchor::Left | Anchor::Center | Anchor::Right)
+    }
+}
+
+/// The panels placed on a screen so far.
+pub struct Layout {
+    size: Vector2<f64>,
+    margin: f64,
+    /// The space between stacked panels.
+    gap: f64,
+    /// Where the next panel at each anchor goes: its top, or its bottom for bottom anchors.
+    next: [Option<f64>; 9],
+    panels: Vec<Aabb>,
+}
+
+impl Layout {
+    /// An empty layout for a `size` screen, with panels `margin` in from its edges and `gap`
+    /// apart.
+    pub fn new(size: Vector2<f64>, margin: f64, gap: f64) -> Self {
+        Self {
+            size,
+            margin,
+            gap,
+            next: [None; 9],
+            panels: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> Vector2<f64> {
+        self.size
+    }
+
+    /// The panels placed so far, in order.
+    pub fn panels(&self) -> &[Aabb] {
+        &self.panels
+    }
+
+    /// Place a `size` panel at `anchor`, and return where it goes.
+    pub fn place(&mut self, anchor: Anchor, size: Vector2<f64>) -> Aabb {
+        let x = self.margin + (self.size.x - 2. * self.margin - size.x) * anchor.horizontal();
+        let up = anchor.stacks_up();
+        let y = match self.next[anchor.index()] {
+            Some(edge) if up => edge - size.y,
+            Some(edge) => edge,
+            None if up => self.size.y - self.margin - size.y,
+            None if anchor.in_middle() => (self.size.y - size.y) / 2.,
+            None => self.margin,
+        };
+        let mut panel = Aabb::new(Vector2::new(x, y), Vector2::new(x + size.x, y + size.y));
+
+        // move past whatever's in the way, until nothing is
+        while let Some(other) = self.panels.iter().find(|other| self.overlap(&panel, other)) {
+            let y = if up {
+                other.min.y - self.gap - size.y
+            } else {
+                other.max.y + self.gap
+            };
+            panel = Aabb::new(Vector2::new(x, y), Vector2::new(x + size.x, y + size.y));
+        }
+
+        self.next[anchor.index()] = Some(if up {
+            panel.min.y - self.gap
+        } else {
+            panel.max.y + self.gap
+        });
+        self.panels.push(panel);
+        panel
+    }
+
+    /// Whether two panels are closer than `gap` to each other.
+    fn overlap(&self, a: &Aabb, b: &Aabb) -> bool {
+        a.min.x < b.max.x + self.gap
+            && b.min.x < a.max.x + self.gap
+            && a.min.y < b.max.y + self.gap
+            && b.min.y < a.max.y + self.gap
+    }
+}
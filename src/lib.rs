@@ -6,7 +6,7 @@
 
 use std::cell::Cell;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::f64;
 use std::rc::Rc;
 
@@ -14,20 +14,42 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{MessageEvent, WebSocket};
 
-use num_traits::FromPrimitive;
-
 pub mod wrapper;
 #[macro_use]
 pub mod macros;
 pub mod binary;
+pub mod bot;
+pub mod canvas;
+pub mod color;
 pub mod draw;
 pub mod engine;
+pub mod geometry;
+pub mod hitflash;
+pub mod hud;
+pub mod input_sender;
+pub mod inspect;
+pub mod killcam;
+pub mod layout;
+pub mod lighting;
+pub mod menu;
+pub mod netsim;
+pub mod netstats;
 pub mod protocol;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod raster;
+pub mod renderer;
+pub mod replay;
+pub mod settings;
+pub mod spatial;
+pub mod sprites;
+pub mod svg;
+pub mod theme;
 pub mod util;
+pub mod widget;
 
-use draw::*;
+use canvas::CanvasRenderer;
 use protocol::Protocol;
-use util::Lerp;
+use settings::{Action, Settings};
 
 fn window() -> web_sys::Window {
     web_sys::window().expect("no global `window` exists")
@@ -51,6 +73,68 @@ fn body() -> web_sys::HtmlElement {
     document().body().expect("document should have a body")
 }
 
+/// Handle the keyboard while watching a replay.
+///
+/// * Space pauses
+/// * Up and down change the speed
+/// * Left and right seek by 30 ticks, comma and period step a single tick
+/// * C detaches the camera from `yourself`, the movement keys then move it around
+/// * The chat key dismisses the death screen
+fn playback_key(world: &mut engine::World, key_code: u32, down: bool) {
+    let action = world.settings.keys.action(key_code);
+    let player = world.playback.as_mut().unwrap();
+    let pan = if down { 1. } else { 0. };
+    match action {
+        Some(Action::Up) => player.pan.y = -pan,
+        Some(Action::Down) => player.pan.y = pan,
+        Some(Action::Left) => player.pan.x = -pan,
+        Some(Action::Right) => player.pan.x = pan,
+        _ => {}
+    }
+    if down {
+        return;
+    }
+
+    let tick = player.tick();
+    match key_code {
+        32 => player.paused = !player.paused,
+        38 => player.faster(),
+        40 => player.slower(),
+        67 => player.free_camera = !player.free_camera,
+        37 => world.seek_playback(tick.saturating_sub(30)),
+        39 => world.seek_playback(tick + 30),
+        188 => world.seek_playback(tick.saturating_sub(1)),
+        190 => world.seek_playback(tick + 1),
+        _ if action == Some(Action::Chat) => {
+            world.state.player_state = engine::PlayerState::Alive;
+            world.state.show_death_screen(false);
+            world.yourself.opacity.tv = 1.0;
+        }
+        _ => {}
+    }
+}
+
+/// The elements of the page the game draws on and chats through.
+#[derive(Clone)]
+struct Page {
+    canvas: web_sys::HtmlCanvasElement,
+    ctx: web_sys::CanvasRenderingContext2d,
+    /// The layer over `canvas`, with the lights and the HUD.
+    composite: web_sys::HtmlCanvasElement,
+    composite_ctx: web_sys::CanvasRenderingContext2d,
+    chat_input: web_sys::HtmlInputElement,
+    chat_div: web_sys::HtmlDivElement,
+}
+
+impl Page {
+    /// Color the page behind the arena like `theme` says.
+    fn show_theme(&self, theme: &theme::Theme) {
+        self.canvas
+            .style()
+            .set_property("background-color", theme.page.css());
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn start() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
@@ -94,65 +178,109 @@ pub fn start() {
         .map_err(|_| ())
         .unwrap();
 
-    let world = Rc::new(RefCell::new(engine::World {
-        yourself: engine::Tank {
-            id: 0,
-            name: wrapper::query_name(),
-            mockup: 0,
-            position: util::Vector2 { x: 0., y: 0. },
-            net_position: util::Vector2 { x: 0., y: 0. },
-            velocity: util::Vector2 { x: 0., y: 0. },
-            rotation: 0.,
-            light: engine::Light {
-                x: 0.,
-                y: 0.,
-                r: 500.,
-                color: String::from("rgba(252, 250, 157, 0.2)"),
-            },
-            net_rotation: 0.,
-            yourself: true,
-            health: util::Scalar::new(1.),
-            radius: 50,
-            damaged: false,
-            opacity: util::Scalar::new(1.),
-            message: String::new(),
-        },
-        state: engine::GameState::new(),
-        input: engine::Input::new(),
-        camera: util::Vector2 { x: 0., y: 0. },
-        size: util::Scalar::new(1.),
+    let page = Page {
         canvas,
         ctx,
-        composite_ctx,
         composite,
-        entities: HashMap::new(),
-        mockups: None,
+        composite_ctx,
         chat_input: input_element,
         chat_div,
-        leaderboard: protocol::LeaderboardPacket {
-            entries: Vec::new(),
-        },
-    }));
-
-    let ws = WebSocket::new(wrapper::query_server_url().as_str()).expect("Failed to connect!");
-    ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+    };
+
+    let world = Rc::new(RefCell::new(engine::World::new(
+        wrapper::query_name(),
+        netsim::NetSim::new(
+            netsim::Conditions::parse(&wrapper::query_netsim()).unwrap_or_else(|e| {
+                do_error_log!("{}", e);
+                netsim::Conditions::PERFECT
+            }),
+            js_sys::Date::now() as u64,
+        ),
+        lighting::DEFAULT_BUDGET,
+    )));
+
+    let mut saved_settings = match wrapper::load_settings(settings::STORAGE_KEY) {
+        Some(text) => Settings::parse(&text).unwrap_or_else(|e| {
+            do_error_log!("{}", e);
+            Settings::default()
+        }),
+        None => Settings::default(),
+    };
+    {
+        let mut world = world.borrow_mut();
+        world.apply_settings(saved_settings.clone());
+        // the URL only changes things for this session, until their settings are changed
+        match wrapper::query_theme().as_str() {
+            "" => {}
+            text => match theme::Theme::parse(text) {
+                Ok(theme) => world.set_theme(theme),
+                Err(e) => do_error_log!("{}", e),
+            },
+        }
+        match wrapper::query_hitflash().as_str() {
+            "" => {}
+            text => match hitflash::HitFlash::parse(text) {
+                Ok(flash) => world.hit_flash = flash,
+                Err(e) => do_error_log!("{}", e),
+            },
+        }
+        if let Some(scale) = wrapper::query_ui_scale() {
+            let (min, max) = Settings::UI_SCALES;
+            world.ui_scale = scale.clamp(min, max);
+        }
+        if let Some(budget) = wrapper::query_lights() {
+            world.light_budget = budget as usize;
+        }
+    }
+    page.show_theme(&world.borrow().theme);
+
+    // A replay is watched without connecting to a server.
+    let ws = match wrapper::query_replay() {
+        Some(data) => {
+            let recording = match replay::Replay::decode(data.to_vec()) {
+                Ok(recording) => recording,
+                Err(e) => {
+                    do_error_log!("Failed to load replay: {}", e);
+                    return;
+                }
+            };
+            do_success_log!(
+                "Loaded a replay with {} entries ({:.0} seconds)",
+                recording.entries.len(),
+                recording.duration() / 1000.
+            );
+            let mut world = world.borrow_mut();
+            world.handle_packet(recording.handshake.clone());
+            world.playback = Some(replay::Player::new(recording));
+            None
+        }
+        None => {
+            let ws =
+                WebSocket::new(wrapper::query_server_url().as_str()).expect("Failed to connect!");
+            ws.set_binary_type(web_sys::BinaryType::Arraybuffer);
+            Some(ws)
+        }
+    };
 
     let mouse_position = Rc::new(Cell::new((0., 0.)));
+    // keys a widget took when they went down, which the game shouldn't see go up either
+    let ui_keys = Rc::new(RefCell::new(HashSet::new()));
     let win_size = Rc::new(Cell::new([1., 1.]));
-    let mut frame = 0;
+    let mut frame: u32 = 0;
 
-    let mut last_frame_time = js_sys::Date::now();
-    let mut delta = 1.;
+    let mut last_frame_time = window().performance().unwrap().now();
+    // the page color shown, which follows theme changes
+    let mut page_color = world.borrow().theme.page.clone();
+    let mut timestep = engine::FixedTimestep::new();
 
     // requestAnimationFrame
     let f = Rc::new(RefCell::new(None));
     {
-        // To get the moust position use:
-        // mouse_position.get().0 / fov_math, mouse_position.get().1 / fov_math
         clone!(mouse_position);
         clone!(win_size);
         clone!(ws);
         clone!(world);
+        clone!(page);
 
         let g = f.clone();
         *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
@@ -160,439 +288,86 @@ pub fn start() {
 
             frame += 1;
             let now = window().performance().unwrap().now();
-            delta = ((now - last_frame_time) / 16.).lerp(delta, 0.7);
-            if delta < 1.0 {
-                delta = 1.0;
+            world.advance_playback(now - last_frame_time);
+            world.killcam.advance(now - last_frame_time);
+            if let Some(ws) = &ws {
+                world.flush_netsim(ws, now);
             }
-            if now - last_frame_time > 500. {
-                delta = 1.0;
+            for _ in 0..timestep.advance(now - last_frame_time) {
+                world.update(1.);
             }
+            let alpha = timestep.alpha();
             last_frame_time = now;
             // set width and height
-            world
-                .canvas
-                .set_width(window().inner_width().unwrap().as_f64().unwrap() as u32);
-            world
-                .canvas
-                .set_height(window().inner_height().unwrap().as_f64().unwrap() as u32);
-            world
-                .composite
-                .set_width(window().inner_width().unwrap().as_f64().unwrap() as u32);
-            world
-                .composite
-                .set_height(window().inner_height().unwrap().as_f64().unwrap() as u32);
-
-            win_size.set([
-                window().inner_width().unwrap().as_f64().unwrap(),
-                window().inner_height().unwrap().as_f64().unwrap(),
-            ]);
-
-            let fov = match &world.mockups {
-                Some(mockups) => mockups[world.yourself.mockup as usize].fov,
-                None => 20,
-            };
-            let design_resolution = [112.5 * fov as f64, 112.5 * fov as f64];
-            // fov
-            world.ctx.scale(
-                (win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]),
-                (win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]),
-            );
-
-            world.composite_ctx.scale(
-                (win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]),
-                (win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]),
-            );
-
-            let center_x = world.canvas.width() as f64
-                / 2.
-                / ((win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]));
-            let center_y = world.canvas.height() as f64
-                / 2.
-                / ((win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]));
-
-            let fov_math = (win_size.get()[0] + win_size.get()[1])
-                / (design_resolution[0] + design_resolution[1]);
-            world.ctx.translate(center_x, center_y);
-            world.ctx.translate(-world.camera.x, -world.camera.y);
-            world.composite_ctx.translate(center_x, center_y);
-            world
-                .composite_ctx
-                .translate(-world.camera.x, -world.camera.y);
-
-            if !world.state.chat_open && !world.state.is_dead() {
-                if world.input.W {
-                    world.yourself.velocity.y -= 1.;
-                } else if world.input.S {
-                    world.yourself.velocity.y += 1.;
-                }
-
-                if world.input.A {
-                    world.yourself.velocity.x -= 1.;
-                } else if world.input.D {
-                    world.yourself.velocity.x += 1.;
-                }
+            let width = window().inner_width().unwrap().as_f64().unwrap();
+            let height = window().inner_height().unwrap().as_f64().unwrap();
+            page.canvas.set_width(width as u32);
+            page.canvas.set_height(height as u32);
+            page.composite.set_width(width as u32);
+            page.composite.set_height(height as u32);
+
+            win_size.set([width, height]);
+
+            // replays bring their own input
+            if world.playback.is_none() {
+                let (x, y) = mouse_position.get();
+                world.input.mouse_position = world
+                    .screen_to_world(util::Vector2::new(x, y), width, height, alpha)
+                    .into();
             }
 
-            world.input.mouse_position = util::Vector2 {
-                x: ((mouse_position.get().0 / fov_math) + world.camera.x - center_x) as i16,
-                y: ((mouse_position.get().1 / fov_math) + world.camera.y - center_y) as i16,
-            };
-            // clear the canvas
-            world.ctx.set_fill_style(v8!("rgba(30, 23, 0, 1.0)"));
-            let w = world.size.value as f64;
-            let h = world.size.value as f64;
-            world.ctx.fill_rect(0., 0., w, h);
-            world.composite_ctx.clear_rect(0., 0., w, h);
-
-            world.ctx.set_line_join("round");
-
-            // grid
-            draw_grid(&world.ctx, w.ceil(), h.ceil());
-
-            // render
-            let shadows = world.draw_entities(delta);
-
-            if ws.ready_state() == 1 && !world.state.is_dead() {
-                if !world.state.chat_open {
-                    util::talk(&ws, &protocol::InputPacket::from_input(world.input));
-                } else {
-                    util::talk(
-                        &ws,
-                        &protocol::InputPacket::from_input(engine::Input::new()),
-                    );
-                }
+            let input = world.input;
+            if let Some(recorder) = &mut world.recorder {
+                recorder.record_input(now, input);
             }
 
-            world.yourself.rotation = (world.input.mouse_position.y as f64
-                - world.yourself.position.y)
-                .atan2(world.input.mouse_position.x as f64 - world.yourself.position.x);
-
-            draw_light_with_shadows(
-                &world.ctx,
-                &world.composite_ctx,
-                world.yourself.position.x,
-                world.yourself.position.y,
-                1300.,
-                "rgba(252, 250, 157, 0.25)",
-                shadows,
-                world.size.value as u16,
-            );
-
-            // gui pass
-            world.ctx.translate(-center_x, -center_y);
-            world.ctx.translate(world.camera.x, world.camera.y);
-
-            world.composite_ctx.translate(-center_x, -center_y);
-            world
-                .composite_ctx
-                .translate(world.camera.x, world.camera.y);
-
-            world.ctx.scale(
-                ((win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]))
-                    .recip(),
-                ((win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]))
-                    .recip(),
-            );
-
-            world.composite_ctx.scale(
-                ((win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]))
-                    .recip(),
-                ((win_size.get()[0] + win_size.get()[1])
-                    / (design_resolution[0] + design_resolution[1]))
-                    .recip(),
-            );
-
-            world.ctx.scale(
-                (win_size.get()[0] + win_size.get()[1]) / (2000. + 2000.),
-                (win_size.get()[0] + win_size.get()[1]) / (2000. + 2000.),
-            );
-
-            world.composite_ctx.scale(
-                (win_size.get()[0] + win_size.get()[1]) / (2000. + 2000.),
-                (win_size.get()[0] + win_size.get()[1]) / (2000. + 2000.),
-            );
-
-            let center_x = world.canvas.width() as f64
-                / 2.
-                / ((win_size.get()[0] + win_size.get()[1]) / (2000. + 2000.));
-            let center_y = world.canvas.height() as f64
-                / 2.
-                / ((win_size.get()[0] + win_size.get()[1]) / (2000. + 2000.));
-
-            world.state.level.update(0.05 * delta as f32);
-
-            world.composite_ctx.set_font("75px \"Fira Sans\"");
-            world.composite_ctx.save();
-            world
-                .composite_ctx
-                .set_shadow_blur(((frame as f64 / 50.).sin() + 2.) * 20.);
-            world.composite_ctx.set_shadow_color("#f28900");
-            world.composite_ctx.set_fill_style(v8!("#f28900"));
-            world.composite_ctx.set_stroke_style(v8!("#000000"));
-            world.composite_ctx.set_line_width(10.);
-            world.composite_ctx.fill_text("CactusWar.io", 50., 100.);
-            world.composite_ctx.set_shadow_blur(0.);
-
-            world.yourself.opacity.update(0.5 * delta as f32);
-            match world.mockups {
-                Some(ref mockups) => {
-                    world.composite_ctx.set_font("50px \"Fira Sans\"");
-
-                    world.composite_ctx.set_fill_style(v8!("#ffffff"));
-
-                    let level_percentage = world.state.level.value.fract();
-
-                    let text = &*format!(
-                        "Level {} {}",
-                        world.state.level.tv as u32, mockups[world.yourself.mockup as usize].name
-                    );
-                    let bar_length = 800.;
-                    const BAR_WIDTH: f64 = 79.;
-                    const LONGER_BAR_WIDTH: f64 = BAR_WIDTH + 20.;
-                    draw_rect_no_correction(
-                        &world.composite_ctx,
-                        center_x * 2. - 560. - bar_length / 2.,
-                        center_y * 2. - 260.,
-                        bar_length + 130.,
-                        245.,
-                        0.,
-                        "#121212aa",
-                    );
-                    draw_bar(
-                        &world.composite_ctx,
-                        center_x * 2. - 500. - bar_length / 2.,
-                        center_x * 2. - 500. + bar_length / 2.,
-                        center_y * 2. - 85.,
-                        LONGER_BAR_WIDTH,
-                        "#000000",
-                    );
-
-                    draw_bar(
-                        &world.composite_ctx,
-                        center_x * 2. - 500. - bar_length / 2.,
-                        (center_x * 2. - 500. - bar_length / 2.)
-                            + bar_length * level_percentage as f64,
-                        center_y * 2. - 85.,
-                        BAR_WIDTH,
-                        "#00FFFF",
-                    );
-
-                    world.composite_ctx.set_shadow_color("#232323");
-                    world.composite_ctx.set_shadow_blur(5.);
-                    world.composite_ctx.stroke_text(
-                        text,
-                        center_x * 2. - 500. - bar_length / 2.,
-                        center_y * 2. - 70.,
-                    );
-                    world.composite_ctx.fill_text(
-                        text,
-                        center_x * 2. - 500. - bar_length / 2.,
-                        center_y * 2. - 70.,
-                    );
-
-                    world.composite_ctx.set_font("66px \"Fira Sans\"");
-                    let text = if world.yourself.name.as_str().is_empty() {
-                        "Unnamed Tank"
+            if let Some(ws) = &ws {
+                if ws.ready_state() == 1 && !world.state.is_dead() {
+                    let input = if !world.state.chat_open {
+                        world.input
                     } else {
-                        world.yourself.name.as_str()
+                        engine::Input::new()
                     };
-                    world.composite_ctx.stroke_text(
-                        text,
-                        center_x * 2. - 500. - bar_length / 2.,
-                        center_y * 2. - 172.5,
-                    );
-                    world.composite_ctx.fill_text(
-                        text,
-                        center_x * 2. - 500. - bar_length / 2.,
-                        center_y * 2. - 172.5,
-                    );
-
-                    // leaderboard
-                    world.composite_ctx.save();
-                    world.composite_ctx.set_shadow_blur(0.);
-                    world.composite_ctx.set_font("50px \"Fira Sans\"");
-                    world.composite_ctx.set_fill_style(v8!("#ffffff"));
-
-                    draw_rect_no_correction(
-                        &world.composite_ctx,
-                        (center_x * 2. - 500.).floor() - 55.0,
-                        20.0,
-                        525.0,
-                        120.0 + world.leaderboard.entries.len() as f64 * 65.0,
-                        0.,
-                        "#121212aa",
-                    );
-
-                    let text = "Leaderboard";
-                    world
-                        .composite_ctx
-                        .stroke_text(text, (center_x * 2. - 465.).floor(), 80.0);
-                    world
-                        .composite_ctx
-                        .fill_text(text, (center_x * 2. - 465.).floor(), 80.0);
-
-                    world.composite_ctx.set_font("30px \"Fira Sans\"");
-                    world.composite_ctx.set_line_width(5.);
-
-                    if let Some(entry) = world.leaderboard.entries.get(0) {
-                        let max_level = entry.level;
-                        for (index, entry) in world.leaderboard.entries.iter().enumerate() {
-                            let text = &*format!(
-                                "{}  ➤  Level {}",
-                                if entry.name.as_str().is_empty() {
-                                    "Unnamed Tank"
-                                } else {
-                                    entry.name.as_str()
-                                },
-                                entry.level as u32,
-                            );
-    
-                            let measurement = world.composite_ctx.measure_text(text).unwrap().width();
-    
-                            let level = entry.level / max_level;
-    
-                            draw::draw_bar(
-                                &world.composite_ctx,
-                                (center_x * 2. - 500.).floor(),
-                                (center_x * 2. - 500.).floor() + 400.0,
-                                (150. + index as f64 * 65.) - 10.0,
-                                60.0,
-                                "rgba(0, 0, 0, 1.0)",
-                            );
-    
-                            draw::draw_bar(
-                                &world.composite_ctx,
-                                (center_x * 2. - 500.).floor(),
-                                (center_x * 2. - 500.).floor() + (400.0 * level as f64),
-                                (150. + index as f64 * 65.) - 10.0,
-                                40.0,
-                                "rgba(140, 140, 140, 1.0)",
-                            );
-    
-                            world.composite_ctx.stroke_text(
-                                text,
-                                (center_x * 2. - 500.).floor(),
-                                150. + index as f64 * 65.,
-                            );
-                            world.composite_ctx.fill_text(
-                                text,
-                                (center_x * 2. - 500.).floor(),
-                                150. + index as f64 * 65.,
-                            );
-                        }
-                    }
-
-                    world.composite_ctx.restore();
-
-                    // death screen
-                    world
-                        .state
-                        .death_animation_completion
-                        .update(0.2 * delta as f32);
-                    if world.state.is_dead() {
-                        world.state.death_animation_completion.tv = 1.0;
-                        world.yourself.opacity.tv = 0.0;
-                        world
-                            .composite_ctx
-                            .set_global_alpha(world.state.death_animation_completion.value as f64);
-                        world
-                            .composite_ctx
-                            .set_fill_style(v8!("rgba(0, 0, 0, 0.2)"));
-                        world
-                            .composite_ctx
-                            .fill_rect(0.0, 0.0, center_x * 2., center_y * 2.);
-                        world.state.death_animation_completion.tv = 1.0;
-                        world.composite_ctx.set_font("104px \"Fira Sans\"");
-                        world.composite_ctx.set_fill_style(v8!("#ffffff"));
-                        let text = "YOU DIED!";
-                        let measurement = world.composite_ctx.measure_text(text).unwrap().width();
-                        world.composite_ctx.stroke_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y,
-                        );
-                        world
-                            .composite_ctx
-                            .fill_text(text, center_x - measurement / 2., center_y);
-
-                        world.composite_ctx.set_font("44px \"Fira Sans\"");
-
-                        let duration: humantime::Duration =
-                            std::time::Duration::from_secs(world.state.time_alive() as u64).into();
-                        let text = format!("Time alive: {}", duration);
-                        let text = text.as_str();
-                        let measurement = world.composite_ctx.measure_text(text).unwrap().width();
-                        world.composite_ctx.stroke_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y + 100.,
-                        );
-                        world.composite_ctx.fill_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y + 100.,
-                        );
-
-                        let text = format!("Level: {}", world.state.level.value.floor());
-                        let text = text.as_str();
-                        let measurement = world.composite_ctx.measure_text(text).unwrap().width();
-                        world.composite_ctx.stroke_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y + 160.,
-                        );
-                        world.composite_ctx.fill_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y + 160.,
-                        );
-
-                        world.composite_ctx.set_global_alpha(
-                            world.state.death_animation_completion.value as f64
-                                * (((frame as f64 / 7.5).sin() + 1.) / 2.),
-                        );
-
-                        world.composite_ctx.set_font("24px \"Fira Sans\"");
-                        let text = "(Press Enter To Continue)";
-                        let measurement = world.composite_ctx.measure_text(text).unwrap().width();
-                        world.composite_ctx.stroke_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y + 220.,
-                        );
-                        world.composite_ctx.fill_text(
-                            text,
-                            center_x - measurement / 2.,
-                            center_y + 220.,
-                        );
-                    } else {
-                        world.state.death_animation_completion.tv = 0.0;
-                        world.yourself.opacity.tv = 1.0;
+                    if let Some(input) = world.input_sender.poll(now, input) {
+                        world.send(ws, now, &protocol::InputPacket::from_input(input));
                     }
                 }
-                None => (),
             }
 
-            world.ctx.restore();
+            let position = world.yourself.render_position(alpha);
+            world.yourself.rotation = (world.input.mouse_position.y as f64 - position.y)
+                .atan2(world.input.mouse_position.x as f64 - position.x);
+
+            // the death screen fades in once the killcam is over
+            if world.mockups.is_some() {
+                if world.killcam.replay.is_some() {
+                    world.state.show_death_screen(false);
+                    world.yourself.opacity.tv = 0.0;
+                } else if world.state.is_dead() {
+                    world.state.show_death_screen(true);
+                    world.yourself.opacity.tv = 0.0;
+                } else {
+                    world.state.show_death_screen(false);
+                    world.yourself.opacity.tv = 1.0;
+                }
+            }
 
-            // set the camera position
-            world.camera.x = world
-                .camera
-                .x
-                .lerp(world.yourself.net_position.x, 0.075 * delta);
-            world.camera.y = world
-                .camera
-                .y
-                .lerp(world.yourself.net_position.y, 0.075 * delta);
+            world.draw_frame(
+                &mut CanvasRenderer::new(page.ctx.clone()),
+                &mut CanvasRenderer::new(page.composite_ctx.clone()),
+                width,
+                height,
+                alpha,
+                frame,
+            );
+            if world.theme.page != page_color {
+                page.show_theme(&world.theme);
+                page_color = world.theme.page.clone();
+            }
+            if world.settings != saved_settings {
+                wrapper::save_settings(settings::STORAGE_KEY, &world.settings.to_json());
+                saved_settings = world.settings.clone();
+            }
 
             // Schedule ourself for another requestAnimationFrame callback.
             request_animation_frame(f.borrow().as_ref().unwrap());
@@ -603,21 +378,20 @@ pub fn start() {
     // mousemove
     {
         clone!(mouse_position);
-        clone!(win_size);
         clone!(world);
         let closure = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
-            mouse_position.set((event.page_x() as f64, event.page_y() as f64));
+            let (x, y) = (event.page_x() as f64, event.page_y() as f64);
+            mouse_position.set((x, y));
+            world.borrow_mut().ui.pointer_move(x, y);
         }) as Box<dyn FnMut(_)>);
-        world
-            .borrow()
-            .composite
+        page.composite
             .add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref())
             .expect("Failed to add event listener to canvas!");
         closure.forget();
     }
 
     // onmessage
-    {
+    if let Some(ws) = &ws {
         #[allow(unused_variables)]
         let cloned_ws = ws.clone();
         clone!(world);
@@ -628,287 +402,7 @@ pub fn start() {
                 #[allow(unused_variables)]
                 let len = array.byte_length() as usize;
                 let data = array.to_vec();
-                let mut buf = binary::StreamPeerBuffer::new();
-                buf.set_data_array(data);
-                match FromPrimitive::from_u8(buf.get_u8()) {
-                    Some(protocol::Packet::Census) => {
-                        // Decode the census and get your own id
-                        // We'll need to check our own id against every entity later on.
-                        let census = protocol::Census::decode(buf);
-                        let yourself_id = world.yourself.id;
-
-                        world.size.tv = census.arena_size as f32;
-                        world.state.level.tv = census.level;
-
-                        // Lets check if any entities need to be removed from our cache.
-                        // We can just look at all the entities in our cache that are not in the census.
-                        // Think of it as a git diff but we can only see subtractions.
-
-                        world.entities.retain(|k, e| match e {
-                            engine::Entity::Bullet(entity) => {
-                                if entity.opacity.value < 0.05 {
-                                    false
-                                } else {
-                                    if census.entities.contains_key(k) {
-                                        true
-                                    } else {
-                                        entity.opacity.set_update(0.0, 0.1);
-                                        entity.scale.set_update(2.0, 0.1);
-                                        true
-                                    }
-                                }
-                            }
-
-                            engine::Entity::Tank(entity) => {
-                                if entity.opacity.value < 0.05 {
-                                    false
-                                } else {
-                                    if census.entities.contains_key(k) {
-                                        true
-                                    } else {
-                                        entity.opacity.set_update(0.0, 0.1);
-                                        true
-                                    }
-                                }
-                            }
-
-                            engine::Entity::Shape(entity) => {
-                                if entity.opacity.value < 0.05 {
-                                    false
-                                } else {
-                                    if census.entities.contains_key(k) {
-                                        true
-                                    } else {
-                                        entity.opacity.set_update(0.0, 0.1);
-                                        true
-                                    }
-                                }
-                            }
-                        });
-
-                        for (id, entity) in &census.entities {
-                            // If the entity is yourself, update `yourself`.
-                            if *id == yourself_id {
-                                match entity {
-                                    protocol::Entity::Tank(t) => {
-                                        world.yourself.net_position = util::Vector2 {
-                                            x: t.position.x as f64,
-                                            y: t.position.y as f64,
-                                        };
-                                        world.yourself.mockup = t.mockup;
-                                        world.yourself.radius = t.radius;
-                                        if world.yourself.health.tv > t.health {
-                                            world.yourself.damaged = true;
-                                        }
-                                        world.yourself.health.tv = t.health;
-                                        world.yourself.message = t.message.clone();
-                                    }
-                                    _ => {}
-                                }
-                            } else {
-                                // Check what type the foreign entity is
-                                match entity {
-                                    // It's a tank!
-                                    // Check whether it's in our local cache already
-                                    protocol::Entity::Tank(census_entity) => {
-                                        // It's in our local cache, lets update our cache.
-                                        if world.entities.contains_key(id) {
-                                            // `game_entity` is our cached entity.
-                                            let game_entity = world.entities.get_mut(id).unwrap();
-                                            match game_entity {
-                                                engine::Entity::Tank(e) => {
-                                                    e.net_position = util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    };
-                                                    e.net_rotation = census_entity.rotation as f64;
-                                                    e.mockup = census_entity.mockup;
-                                                    if !e.yourself {
-                                                        e.velocity = util::Vector2 {
-                                                            x: census_entity.velocity.x as f64 / 2.,
-                                                            y: census_entity.velocity.y as f64 / 2.,
-                                                        };
-                                                    }
-                                                    if census_entity.health < e.health.tv {
-                                                        e.damaged = true;
-                                                    }
-                                                    e.health.tv = census_entity.health;
-                                                    e.radius = census_entity.radius;
-                                                    e.message = census_entity.message.clone();
-                                                }
-                                                _ => {}
-                                            }
-                                        } else {
-                                            // it's not in our cache, lets add it.
-                                            world.entities.insert(
-                                                *id,
-                                                engine::Entity::Tank(engine::Tank {
-                                                    id: *id,
-                                                    name: census_entity.name.clone(),
-                                                    position: util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    },
-                                                    net_position: util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    },
-                                                    velocity: util::Vector2 {
-                                                        x: census_entity.velocity.x as f64 / 4.,
-                                                        y: census_entity.velocity.y as f64 / 4.,
-                                                    },
-                                                    rotation: census_entity.rotation as f64,
-                                                    light: engine::Light {
-                                                        x: 0.,
-                                                        y: 0.,
-                                                        r: 1000.,
-                                                        color: String::from(
-                                                            "rgba(252, 250, 157, 0.4)",
-                                                        ),
-                                                    },
-                                                    yourself: false,
-                                                    net_rotation: census_entity.rotation as f64,
-                                                    mockup: census_entity.mockup,
-                                                    radius: census_entity.radius,
-                                                    health: util::Scalar::new(census_entity.health),
-                                                    damaged: false,
-                                                    opacity: util::Scalar::new(1.),
-                                                    message: census_entity.message.clone(),
-                                                }),
-                                            );
-                                        }
-                                    }
-                                    protocol::Entity::Shape(census_entity) => {
-                                        // It's in our local cache, lets update our cache.
-                                        if world.entities.contains_key(id) {
-                                            // `game_entity` is our cached entity.
-                                            let game_entity = world.entities.get_mut(id).unwrap();
-                                            match game_entity {
-                                                engine::Entity::Shape(e) => {
-                                                    e.net_position = util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    };
-                                                    if census_entity.health < e.health {
-                                                        e.damaged = true;
-                                                        e.needs_redraw = true;
-                                                    }
-                                                    e.health = census_entity.health;
-                                                }
-                                                _ => {}
-                                            }
-                                        } else {
-                                            // it's not in our cache, lets add it.
-                                            world.entities.insert(
-                                                *id,
-                                                engine::Entity::Shape(engine::Shape {
-                                                    id: *id,
-                                                    position: util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    },
-                                                    net_position: util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    },
-                                                    sides: ((js_sys::Math::random() * 10.) + 10.)
-                                                        as u8,
-                                                    velocity: util::Vector2 { x: 0., y: 0. },
-                                                    rotation: census_entity.position.x as f32
-                                                        + census_entity.position.y as f32,
-
-                                                    health: census_entity.health,
-                                                    damaged: false,
-
-                                                    opacity: util::Scalar::new(1.),
-                                                    cached_tex: None,
-                                                    needs_redraw: true,
-                                                    radius: census_entity.radius,
-                                                }),
-                                            );
-                                        }
-                                    }
-                                    protocol::Entity::Bullet(census_entity) => {
-                                        // It's in our local cache, lets update our cache.
-                                        if world.entities.contains_key(id) {
-                                            // `game_entity` is our cached entity.
-                                            let game_entity = world.entities.get_mut(id).unwrap();
-                                            match game_entity {
-                                                engine::Entity::Bullet(e) => {
-                                                    e.net_position = util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    };
-
-                                                    e.velocity = util::Vector2 {
-                                                        x: census_entity.velocity.x as f64 / 3.,
-                                                        y: census_entity.velocity.y as f64 / 3.,
-                                                    };
-                                                }
-                                                _ => {}
-                                            }
-                                        } else {
-                                            let color = if census_entity.owner == world.yourself.id
-                                            {
-                                                String::from("#00e6f2")
-                                            } else {
-                                                String::from("#f28900")
-                                            };
-
-                                            // it's not in our cache, lets add it.
-                                            world.entities.insert(
-                                                *id,
-                                                engine::Entity::Bullet(engine::Bullet {
-                                                    id: *id,
-                                                    position: util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    },
-                                                    net_position: util::Vector2 {
-                                                        x: census_entity.position.x as f64,
-                                                        y: census_entity.position.y as f64,
-                                                    },
-                                                    radius: census_entity.radius,
-                                                    velocity: util::Vector2 {
-                                                        x: census_entity.velocity.x as f64 / 3.,
-                                                        y: census_entity.velocity.y as f64 / 3.,
-                                                    },
-                                                    opacity: util::Scalar::new(1.),
-                                                    scale: util::Scalar::new(1.),
-                                                    cached_tex: None,
-                                                    color,
-                                                }),
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Some(protocol::Packet::Handshake) => {
-                        let res = protocol::HandshakePacket::decode(buf);
-                        do_success_log!(
-                            "Init packet has been acknowledged by the server! Our id is: {}",
-                            res.id
-                        );
-                        do_info_log!("Mockups: {:?}", res.mockups);
-                        world.mockups = Some(res.mockups);
-                        world.yourself.id = res.id;
-                    }
-                    Some(protocol::Packet::Death) => {
-                        let res = protocol::DeathPacket::decode(buf);
-                        do_info_log!(
-                            "The server has delivered the unfortunate news of our death. We lived for {} seconds",
-                            res.time_alive
-                        );
-                        world.state.player_state = engine::PlayerState::Dead(res.time_alive);
-                    }
-                    Some(protocol::Packet::Leaderboard) => {
-                        world.leaderboard = protocol::LeaderboardPacket::decode(buf);
-                    }
-                    None => do_error_log!("Unknown packet id!"),
-                    _ => {}
-                }
+                world.on_message(window().performance().unwrap().now(), data);
             }
         }) as Box<dyn FnMut(MessageEvent)>);
         // set message event handler on WebSocket
@@ -918,7 +412,7 @@ pub fn start() {
     }
 
     // onopen
-    {
+    if let Some(ws) = &ws {
         let cloned_ws = ws.clone();
         let onopen_callback = Closure::wrap(Box::new(move |_| {
             do_success_log!("WebSocket has opened. Sending init packet.");
@@ -938,7 +432,7 @@ pub fn start() {
     }
 
     // onerror
-    {
+    if let Some(ws) = &ws {
         let onerror_callback = Closure::wrap(Box::new(move |_: web_sys::ErrorEvent| {
             do_error_log!("Failed to connect to WebSocket! Please check your network connection!");
         }) as Box<dyn FnMut(web_sys::ErrorEvent)>);
@@ -949,13 +443,50 @@ pub fn start() {
     // onkeydown
     {
         let cloned_world = world.clone();
+        clone!(ui_keys);
         let closure = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
             let mut world = cloned_world.borrow_mut();
-            match event.key_code() {
-                87 => world.input.W = true,
-                65 => world.input.A = true,
-                83 => world.input.S = true,
-                68 => world.input.D = true,
+            // keys being bound in the menu aren't pressed
+            if menu::key_down(&mut world, event.key_code()) {
+                event.prevent_default();
+                return;
+            }
+            // the function keys are ours (see keyup), not the browser's
+            let action = world.settings.keys.action(event.key_code());
+            if let Some(
+                Action::NetStats
+                | Action::Theme
+                | Action::Screenshot
+                | Action::Record
+                | Action::NetSim,
+            ) = action
+            {
+                event.prevent_default();
+            }
+            // while a menu is shown, its widgets take Tab, and Enter, Space and the arrow keys
+            // when one has focus
+            if world.ui.is_shown() {
+                if let Some(key) = widget::Key::from_code(event.key_code(), event.shift_key()) {
+                    if world.ui.key(key) {
+                        event.prevent_default();
+                        ui_keys.borrow_mut().insert(event.key_code());
+                        return;
+                    }
+                }
+            }
+            if world.playback.is_some() {
+                playback_key(&mut world, event.key_code(), true);
+                return;
+            }
+            // the tank stands still while the menu is open
+            if world.menu.open {
+                return;
+            }
+            match action {
+                Some(Action::Up) => world.input.W = true,
+                Some(Action::Left) => world.input.A = true,
+                Some(Action::Down) => world.input.S = true,
+                Some(Action::Right) => world.input.D = true,
                 _ => {}
             }
         }) as Box<dyn FnMut(_)>);
@@ -968,8 +499,15 @@ pub fn start() {
     // onmousedown
     {
         let cloned_world = world.clone();
-        let closure = Closure::wrap(Box::new(move |_: web_sys::MouseEvent| {
+        let closure = Closure::wrap(Box::new(move |event: web_sys::MouseEvent| {
             let mut world = cloned_world.borrow_mut();
+            // clicks on menus don't shoot
+            if world
+                .ui
+                .pointer_down(event.page_x() as f64, event.page_y() as f64)
+            {
+                return;
+            }
             world.input.mouse_down = true;
         }) as Box<dyn FnMut(_)>);
         window()
@@ -983,6 +521,7 @@ pub fn start() {
         let cloned_world = world.clone();
         let closure = Closure::wrap(Box::new(move |_: web_sys::MouseEvent| {
             let mut world = cloned_world.borrow_mut();
+            world.ui.pointer_up();
             world.input.mouse_down = false;
         }) as Box<dyn FnMut(_)>);
         window()
@@ -995,48 +534,151 @@ pub fn start() {
     {
         let cloned_world = world.clone();
         clone!(ws);
+        clone!(page);
+        clone!(ui_keys);
         let closure = Closure::wrap(Box::new(move |event: web_sys::KeyboardEvent| {
             let mut world = cloned_world.borrow_mut();
-            match event.key_code() {
-                87 => world.input.W = false,
-                65 => world.input.A = false,
-                83 => world.input.S = false,
-                68 => world.input.D = false,
-                13 => {
+            if menu::key_up(&mut world, event.key_code()) {
+                return;
+            }
+            if ui_keys.borrow_mut().remove(&event.key_code()) {
+                return;
+            }
+            let action = world.settings.keys.action(event.key_code());
+            // F7 saves the screen as an SVG, in replays too
+            if action == Some(Action::Screenshot) {
+                let ctx = page.ctx.clone();
+                let svg = svg::draw_world(
+                    &mut world,
+                    page.canvas.width() as f64,
+                    page.canvas.height() as f64,
+                    1.,
+                    0,
+                    Box::new(move |font, text| {
+                        ctx.save();
+                        ctx.set_font(font);
+                        let width = ctx.measure_text(text).unwrap().width();
+                        ctx.restore();
+                        width
+                    }),
+                );
+                do_success_log!("Screenshot taken ({} bytes)", svg.len());
+                util::download(
+                    svg.as_bytes(),
+                    &format!("cactuswar-{}.svg", js_sys::Date::now() as u64),
+                    "image/svg+xml",
+                );
+                return;
+            }
+            // F4 cycles through the built-in themes, in replays too
+            if action == Some(Action::Theme) {
+                let mut settings = world.settings.clone();
+                settings.theme = world.theme.next().name;
+                do_info_log!("Theme: {}", settings.theme);
+                world.apply_settings(settings);
+                return;
+            }
+            // Escape closes the chat, or else opens and closes the menu
+            if event.key_code() == 27 {
+                if world.state.chat_open {
+                    world.state.chat_open = false;
+                    page.chat_div.style().set_property("display", "none");
+                } else {
+                    menu::toggle(&mut world);
+                }
+                return;
+            }
+            if world.playback.is_some() {
+                playback_key(&mut world, event.key_code(), false);
+                return;
+            }
+            let ws = ws.as_ref().expect("live games have a connection");
+            let now = window().performance().unwrap().now();
+            match action {
+                Some(Action::Up) => world.input.W = false,
+                Some(Action::Left) => world.input.A = false,
+                Some(Action::Down) => world.input.S = false,
+                Some(Action::Right) => world.input.D = false,
+                Some(Action::Chat) => {
                     event.prevent_default();
                     match world.state.player_state {
                         engine::PlayerState::Alive => {
                             if world.state.chat_open {
                                 // send
-                                util::talk(
-                                    &ws,
-                                    &protocol::MessagePacket {
-                                        message: world.chat_input.value(),
-                                    },
-                                );
-                                world.chat_input.set_value("");
-                                world.chat_div.style().set_property("display", "none");
+                                let message = protocol::MessagePacket {
+                                    message: page.chat_input.value(),
+                                };
+                                world.send(ws, now, &message);
+                                page.chat_input.set_value("");
+                                page.chat_div.style().set_property("display", "none");
                                 world.state.chat_open = false;
                             } else {
                                 world.state.chat_open = true;
-                                world.chat_input.set_value("");
-                                world.chat_div.style().set_property("display", "block");
-                                world.chat_input.focus();
+                                page.chat_input.set_value("");
+                                page.chat_div.style().set_property("display", "block");
+                                page.chat_input.focus();
                             }
                         }
                         engine::PlayerState::Dead(_) => {
+                            world.killcam.replay = None;
                             world.state.player_state = engine::PlayerState::Alive;
-                            world.state.death_animation_completion.tv = 0.0;
+                            world.state.show_death_screen(false);
                             world.yourself.opacity.tv = 1.0;
-                            util::talk(&ws, &protocol::RespawnPacket);
+                            world.send(ws, now, &protocol::RespawnPacket);
+                            world.input_sender.reset();
                         }
                     }
                 }
-                27 => {
-                    world.state.chat_open = false;
-                    world.chat_div.style().set_property("display", "none");
+                // F2 toggles the network stats overlay
+                Some(Action::NetStats) => {
+                    let mut settings = world.settings.clone();
+                    settings.show_netstats = !settings.show_netstats;
+                    world.apply_settings(settings);
                 }
-                _ => {}
+                // F9 cycles through the network simulator presets
+                Some(Action::NetSim) => {
+                    let preset = world.netsim.next_preset();
+                    do_info_log!(
+                        "Network simulator: {} ({})",
+                        preset,
+                        world.netsim.conditions()
+                    );
+                }
+                // F8 toggles session recording
+                Some(Action::Record) => match world.recorder.take() {
+                    Some(recorder) => {
+                        let recording = recorder.finish();
+                        do_success_log!("Recording stopped ({} bytes)", recording.len());
+                        util::download(
+                            &recording,
+                            &format!("cactuswar-{}.cwr", js_sys::Date::now() as u64),
+                            "application/octet-stream",
+                        );
+                    }
+                    None => {
+                        if world.handshake.is_empty() {
+                            do_error_log!("Can't record before the server has sent a handshake!");
+                        } else {
+                            do_info_log!("Recording started");
+                            world.recorder = Some(replay::Recorder::new(
+                                window().performance().unwrap().now(),
+                                js_sys::Date::now(),
+                                &world.handshake,
+                            ));
+                        }
+                    }
+                },
+                // handled above, in replays too
+                Some(Action::Theme | Action::Screenshot) => {}
+                None => match event.key_code() {
+                    // Space skips the killcam, C switches between the killer and the wide camera
+                    32 if world.killcam.is_playing() => world.killcam.replay = None,
+                    67 if world.killcam.is_playing() => {
+                        let replay = world.killcam.replay.as_mut().unwrap();
+                        replay.wide = !replay.wide || replay.killer.is_none();
+                    }
+                    _ => {}
+                },
             }
         }) as Box<dyn FnMut(_)>);
         window()
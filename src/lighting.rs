@@ -0,0 +1,67 @@
+//! The lighting pass.
+//!
+//! Every tank carries a light and every bullet glows. Each light is clipped to what it can see
+//! (see `geometry::visibility_polygon`) and drawn onto the composite canvas, where overlapping
+//! lights add up. Casting shadows for a light isn't free, so at most `budget` lights are drawn:
+//! the one we're looking through first, then tanks, then bullets, nearest first.
+
+use crate::draw::draw_light_with_shadows;
+use crate::engine::Light;
+use crate::geometry::{self, Aabb, Circle};
+use crate::renderer::{BlendMode, Renderer};
+use crate::util::Vector2;
+
+/// How many lights are drawn unless the `lights` URL parameter says otherwise.
+pub const DEFAULT_BUDGET: usize = 16;
+
+/// Which lights are dropped first when there are too many. Earlier variants win.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// The light of the tank we're looking through.
+    Viewpoint,
+    Tank,
+    Bullet,
+}
+
+/// The lights and shadow casters of a frame, gathered while drawing the entities.
+#[derive(Default)]
+pub struct Scene {
+    pub lights: Vec<(Priority, Light)>,
+    pub occluders: Vec<Circle>,
+}
+
+/// Pick the lights to draw: the ones reaching into `view`, most important and nearest to
+/// `viewpoint` first, and no more than `budget` of them.
+pub fn select<'a>(
+    lights: &'a [(Priority, Light)],
+    viewpoint: Vector2<f64>,
+    view: &Aabb,
+    budget: usize,
+) -> Vec<&'a Light> {
+    let mut visible: Vec<&(Priority, Light)> = lights
+        .iter()
+        .filter(|(_, light)| Circle::new(light.position(), light.r).intersects_aabb(view))
+        .collect();
+    visible.sort_by(|(a_priority, a), (b_priority, b)| {
+        a_priority.cmp(b_priority).then_with(|| {
+            let a_distance = a.position().distance(viewpoint);
+            a_distance.total_cmp(&b.position().distance(viewpoint))
+        })
+    });
+    visible
+        .into_iter()
+        .take(budget)
+        .map(|(_, light)| light)
+        .collect()
+}
+
+/// Draw `lights` additively, each with the shadows of `occluders`.
+pub fn render(ctx: &mut dyn Renderer, lights: &[&Light], occluders: &[Circle]) {
+    ctx.save();
+    ctx.set_blend_mode(BlendMode::Lighter);
+    for light in lights {
+        let lit = geometry::visibility_polygon(light.position(), light.r, occluders);
+        draw_light_with_shadows(ctx, light.x, light.y, light.r, &light.color, &lit);
+    }
+    ctx.restore();
+}
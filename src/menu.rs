@@ -0,0 +1,266 @@
+//! The in-game menu, opened and closed with Escape, where the settings are changed: graphics,
+//! the interface and key bindings, and the network simulator, which isn't saved. Changes are
+//! applied as they're made.
+
+use crate::engine::World;
+use crate::geometry::Aabb;
+use crate::hitflash::Style;
+use crate::layout::{Anchor, Layout};
+use crate::netsim::PRESETS;
+use crate::settings::{self, Action, Quality, Settings};
+use crate::theme::BUILT_IN;
+use crate::util::Vector2;
+use crate::widget::Frame;
+
+const WIDTH: f64 = 900.;
+const HEIGHT: f64 = 1050.;
+/// How far widgets are from the edges of the menu, and from each other.
+const SPACING: f64 = 25.;
+const TABS: [&str; 4] = ["Graphics", "Interface", "Keys", "Network"];
+/// The tab with the key bindings.
+const KEYS: usize = 2;
+const FLASH_STYLES: [(&str, Style); 3] = [
+    ("Fill", Style::Fill),
+    ("Outline", Style::Outline),
+    ("Off", Style::Off),
+];
+/// Cancels binding a key.
+const ESCAPE: u32 = 27;
+
+#[derive(Debug, Default)]
+pub struct Menu {
+    pub open: bool,
+    /// The tab shown, from `TABS`.
+    tab: usize,
+    /// The action waiting for a key to be pressed, to bind it to.
+    binding: Option<Action>,
+    /// The key that was just bound, until it goes up.
+    bound: Option<u32>,
+    /// A `RESERVED` key pressed while waiting for a key, to say why it wasn't bound.
+    refused: Option<u32>,
+}
+
+/// Widgets going down the menu, one under the other.
+struct Column {
+    left: f64,
+    right: f64,
+    top: f64,
+}
+
+impl Column {
+    fn next(&mut self, height: f64) -> Aabb {
+        let rect = Aabb::new(
+            Vector2::new(self.left, self.top),
+            Vector2::new(self.right, self.top + height),
+        );
+        self.top += height + SPACING;
+        rect
+    }
+
+    /// The next row, split into `count` widgets side by side.
+    fn row(&mut self, height: f64, count: usize) -> Vec<Aabb> {
+        let row = self.next(height);
+        let width = (row.size().x - SPACING * (count - 1) as f64) / count as f64;
+        (0..count)
+            .map(|index| {
+                let left = row.min.x + index as f64 * (width + SPACING);
+                Aabb::new(
+                    Vector2::new(left, row.min.y),
+                    Vector2::new(left + width, row.max.y),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Open the menu, or close it. The tank stands still while the menu is open, so keys and the
+/// mouse button held when it opens are let go.
+pub fn toggle(world: &mut World) {
+    world.menu.open = !world.menu.open;
+    if world.menu.open {
+        world.input.W = false;
+        world.input.A = false;
+        world.input.S = false;
+        world.input.D = false;
+        world.input.mouse_down = false;
+    }
+}
+
+/// Draw the menu of `world` on a `width` by `height` screen, in design units, if it's open, and
+/// apply whatever was changed in it.
+pub fn draw(ui: &mut Frame, world: &mut World, width: f64, height: f64) {
+    if !world.menu.open {
+        world.menu.binding = None;
+        return;
+    }
+    let panel = Layout::new(Vector2::new(width, height), crate::hud::MARGIN, 0.)
+        .place(Anchor::Center, Vector2::new(WIDTH, HEIGHT));
+    ui.panel(panel);
+    let mut column = Column {
+        left: panel.min.x + SPACING,
+        right: panel.max.x - SPACING,
+        top: panel.min.y + SPACING,
+    };
+
+    ui.tabs("tabs", column.next(70.), &TABS, &mut world.menu.tab);
+    if world.menu.tab != KEYS {
+        world.menu.binding = None;
+    }
+    if world.menu.binding.is_none() {
+        world.menu.refused = None;
+    }
+    let mut settings = world.settings.clone();
+    match world.menu.tab {
+        0 => graphics(ui, &mut settings, &mut column),
+        1 => interface(ui, &mut settings, &mut column),
+        KEYS => keys(ui, &mut settings, &mut world.menu, &mut column),
+        _ => network(ui, world, &mut column),
+    }
+
+    column.top = panel.max.y - SPACING - 80.;
+    if ui.button("resume", column.next(80.), "Resume") {
+        world.menu.open = false;
+    }
+    if settings != world.settings {
+        world.apply_settings(settings);
+    }
+}
+
+fn graphics(ui: &mut Frame, settings: &mut Settings, column: &mut Column) {
+    ui.label(column.next(40.), "Quality");
+    let names: Vec<&str> = Quality::ALL.iter().map(|quality| quality.name()).collect();
+    let mut quality = Quality::ALL
+        .iter()
+        .position(|quality| *quality == settings.quality)
+        .unwrap_or(0);
+    if ui.tabs("quality", column.next(70.), &names, &mut quality) {
+        settings.quality = Quality::ALL[quality];
+    }
+    ui.toggle("lights", column.next(80.), "Lights", &mut settings.lights);
+
+    ui.label(column.next(40.), "Theme");
+    let mut theme = BUILT_IN
+        .iter()
+        .position(|name| *name == settings.theme)
+        .unwrap_or(BUILT_IN.len());
+    if ui.list("theme", column.next(60. * 5.), &BUILT_IN, &mut theme) {
+        settings.theme = String::from(BUILT_IN[theme]);
+    }
+
+    ui.label(column.next(40.), "Hit flashes");
+    let names: Vec<&str> = FLASH_STYLES.iter().map(|(name, _)| *name).collect();
+    let mut flash = settings.hit_flash();
+    let mut style = FLASH_STYLES
+        .iter()
+        .position(|(_, style)| *style == flash.style)
+        .unwrap_or(0);
+    if ui.tabs("hit flash", column.next(70.), &names, &mut style) {
+        flash.style = FLASH_STYLES[style].1;
+        settings.hit_flash = flash.to_string();
+    }
+}
+
+fn interface(ui: &mut Frame, settings: &mut Settings, column: &mut Column) {
+    let text = format!("UI scale: {:.1}x", settings.ui_scale);
+    let (min, max) = Settings::UI_SCALES;
+    ui.slider(
+        "ui scale",
+        column.next(100.),
+        &text,
+        &mut settings.ui_scale,
+        min,
+        max,
+        0.1,
+    );
+    ui.toggle(
+        "names",
+        column.next(80.),
+        "Names over tanks",
+        &mut settings.show_names,
+    );
+    ui.toggle(
+        "chat",
+        column.next(80.),
+        "Chat over tanks",
+        &mut settings.show_chat,
+    );
+    ui.toggle(
+        "netstats",
+        column.next(80.),
+        "Network stats",
+        &mut settings.show_netstats,
+    );
+}
+
+fn keys(ui: &mut Frame, settings: &mut Settings, menu: &mut Menu, column: &mut Column) {
+    let hint = match menu.refused {
+        Some(key_code) => format!(
+            "{} is kept for replays and the killcam, press another key",
+            settings::key_name(key_code)
+        ),
+        None => String::from("Pick an action, then press its new key (Escape cancels)"),
+    };
+    ui.label(column.next(40.), &hint);
+    let binding = &mut menu.binding;
+    for actions in Action::ALL.chunks(2) {
+        let cells = column.row(70., actions.len());
+        for (action, cell) in actions.iter().zip(cells) {
+            let key = if *binding == Some(*action) {
+                String::from("...")
+            } else {
+                settings::key_name(settings.keys.key(*action))
+            };
+            let text = format!("{}: {}", action.name(), key);
+            if ui.button(action.name(), cell, &text) {
+                *binding = Some(*action);
+            }
+        }
+    }
+    if ui.button("reset keys", column.next(70.), "Reset keys") {
+        settings.keys = Default::default();
+        *binding = None;
+    }
+}
+
+fn network(ui: &mut Frame, world: &mut World, column: &mut Column) {
+    ui.label(column.next(40.), "Simulated connection, for this session");
+    let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+    let mut preset = PRESETS
+        .iter()
+        .position(|(_, conditions)| *conditions == world.netsim.conditions())
+        .unwrap_or(PRESETS.len());
+    if ui.list("netsim", column.next(60. * 4.), &names, &mut preset) {
+        world.netsim.set_conditions(PRESETS[preset].1);
+    }
+}
+
+/// A key went down. While an action is waiting for a key, the key is bound to it (or Escape
+/// cancels, and other `RESERVED` keys are refused), and this returns true: the game shouldn't
+/// see the key go down, or up.
+pub fn key_down(world: &mut World, key_code: u32) -> bool {
+    let action = match world.menu.binding.take() {
+        Some(action) => action,
+        None => return false,
+    };
+    world.menu.bound = Some(key_code);
+    if key_code == ESCAPE {
+        return true;
+    }
+    let mut settings = world.settings.clone();
+    if settings.keys.bind(action, key_code) {
+        world.apply_settings(settings);
+    } else {
+        world.menu.binding = Some(action);
+        world.menu.refused = Some(key_code);
+    }
+    true
+}
+
+/// A key went up. Returns whether it was the one just bound, which the game shouldn't see.
+pub fn key_up(world: &mut World, key_code: u32) -> bool {
+    if world.menu.bound == Some(key_code) {
+        world.menu.bound = None;
+        return true;
+    }
+    false
+}
@@ -0,0 +1,256 @@
+//! Network condition simulator.
+//!
+//! Sits between the WebSocket and the rest of the client and holds packets back to simulate a
+//! bad connection: latency, jitter, reordering, duplication and a bandwidth cap, applied to
+//! both directions separately. Nothing here touches the network, so it works natively as well.
+//!
+//! Conditions are picked with the `netsim` URL parameter, either a preset name or a list of
+//! settings, or cycled through the presets with F9:
+//!
+//! ```text
+//! ?netsim=bad
+//! ?netsim=latency:100,jitter:30,reorder:5,duplicate:1,bandwidth:32
+//! ```
+
+use crate::util::Rng;
+
+/// The conditions of one direction of the connection.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Conditions {
+    /// Delay added to every packet, in milliseconds.
+    pub latency: f64,
+    /// Random extra delay of up to this many milliseconds.
+    pub jitter: f64,
+    /// Chance (0 to 1) that a packet is held back long enough for later ones to overtake it.
+    pub reorder: f64,
+    /// Chance (0 to 1) that a packet is delivered twice.
+    pub duplicate: f64,
+    /// Bytes per second, or 0 for no cap.
+    pub bandwidth: f64,
+}
+
+impl Conditions {
+    /// A perfect connection, which the simulator stays out of the way for.
+    pub const PERFECT: Conditions = Conditions {
+        latency: 0.,
+        jitter: 0.,
+        reorder: 0.,
+        duplicate: 0.,
+        bandwidth: 0.,
+    };
+
+    pub fn is_perfect(&self) -> bool {
+        *self == Self::PERFECT
+    }
+
+    /// Parse a preset name, or settings like `latency:100,jitter:30`. Latency and jitter are in
+    /// milliseconds, reorder and duplicate in percent and bandwidth in kilobytes per second.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        if let Some((_, conditions)) = PRESETS.iter().find(|(name, _)| *name == text) {
+            return Ok(*conditions);
+        }
+
+        let mut conditions = Self::PERFECT;
+        for setting in text.split(',').filter(|s| !s.is_empty()) {
+            let mut parts = setting.splitn(2, ':');
+            let key = parts.next().unwrap_or("");
+            let value: f64 = parts
+                .next()
+                .and_then(|v| v.parse().ok())
+                .filter(|v: &f64| *v >= 0.)
+                .ok_or_else(|| format!("Invalid network condition: {}", setting))?;
+            match key {
+                "latency" => conditions.latency = value,
+                "jitter" => conditions.jitter = value,
+                "reorder" => conditions.reorder = (value / 100.).min(1.),
+                "duplicate" => conditions.duplicate = (value / 100.).min(1.),
+                "bandwidth" => conditions.bandwidth = value * 1024.,
+                _ => return Err(format!("Unknown network condition: {}", key)),
+            }
+        }
+        Ok(conditions)
+    }
+}
+
+impl std::fmt::Display for Conditions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:.0}ms ±{:.0}", self.latency, self.jitter)?;
+        if self.reorder > 0. {
+            write!(f, ", {:.0}% reordered", self.reorder * 100.)?;
+        }
+        if self.duplicate > 0. {
+            write!(f, ", {:.0}% duplicated", self.duplicate * 100.)?;
+        }
+        if self.bandwidth > 0. {
+            write!(f, ", {:.0} KB/s", self.bandwidth / 1024.)?;
+        }
+        Ok(())
+    }
+}
+
+/// The presets F9 cycles through. Latencies are one way, so round trips take twice as long.
+pub const PRESETS: [(&str, Conditions); 4] = [
+    ("off", Conditions::PERFECT),
+    (
+        "200ms",
+        Conditions {
+            latency: 100.,
+            jitter: 20.,
+            reorder: 0.,
+            duplicate: 0.,
+            bandwidth: 0.,
+        },
+    ),
+    (
+        "bad",
+        Conditions {
+            latency: 150.,
+            jitter: 80.,
+            reorder: 0.05,
+            duplicate: 0.02,
+            bandwidth: 0.,
+        },
+    ),
+    (
+        "3g",
+        Conditions {
+            latency: 200.,
+            jitter: 50.,
+            reorder: 0.,
+            duplicate: 0.,
+            bandwidth: 32. * 1024.,
+        },
+    ),
+];
+
+/// A packet on its way.
+struct InFlight {
+    due: f64,
+    /// Keeps packets that are due at the same time in order.
+    sequence: u64,
+    data: Vec<u8>,
+}
+
+/// One direction of a simulated connection.
+pub struct Link {
+    pub conditions: Conditions,
+    rng: Rng,
+    queue: Vec<InFlight>,
+    sequence: u64,
+    /// When the bandwidth cap lets the next packet start sending.
+    busy_until: f64,
+    /// When the last packet that wasn't reordered is due, which later packets can't overtake.
+    last_due: f64,
+}
+
+impl Link {
+    pub fn new(conditions: Conditions, seed: u64) -> Self {
+        Self {
+            conditions,
+            rng: Rng::new(seed),
+            queue: Vec::new(),
+            sequence: 0,
+            busy_until: 0.,
+            last_due: 0.,
+        }
+    }
+
+    fn push(&mut self, due: f64, data: Vec<u8>) {
+        self.sequence += 1;
+        self.queue.push(InFlight {
+            due,
+            sequence: self.sequence,
+            data,
+        });
+    }
+
+    /// Send a packet at `now` (in milliseconds).
+    pub fn send(&mut self, now: f64, data: Vec<u8>) {
+        let conditions = self.conditions;
+
+        let mut start = now;
+        if conditions.bandwidth > 0. {
+            start = start.max(self.busy_until);
+            self.busy_until = start + data.len() as f64 * 1000. / conditions.bandwidth;
+            start = self.busy_until;
+        }
+
+        let mut due = start + conditions.latency + self.rng.range(0., conditions.jitter);
+        if self.rng.next_f64() < conditions.reorder {
+            // held back without holding up anything behind it
+            due += conditions.latency.max(50.) + conditions.jitter;
+        } else {
+            // a connection that doesn't reorder delivers in order, however much it jitters
+            due = due.max(self.last_due);
+            self.last_due = due;
+        }
+
+        if self.rng.next_f64() < conditions.duplicate {
+            let copy_due = due + self.rng.range(0., conditions.jitter.max(1.));
+            self.push(copy_due, data.clone());
+        }
+        self.push(due, data);
+    }
+
+    /// Take the packets that have arrived by `now`, in the order they arrived.
+    pub fn poll(&mut self, now: f64) -> Vec<Vec<u8>> {
+        self.queue
+            .sort_by(|a, b| a.due.total_cmp(&b.due).then(a.sequence.cmp(&b.sequence)));
+        let arrived = self.queue.iter().take_while(|p| p.due <= now).count();
+        self.queue.drain(..arrived).map(|p| p.data).collect()
+    }
+
+    /// The amount of packets that haven't arrived yet.
+    pub fn in_flight(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+/// Both directions of a simulated connection.
+pub struct NetSim {
+    /// Packets from the server.
+    pub incoming: Link,
+    /// Packets to the server.
+    pub outgoing: Link,
+}
+
+impl NetSim {
+    pub fn new(conditions: Conditions, seed: u64) -> Self {
+        Self {
+            incoming: Link::new(conditions, seed),
+            outgoing: Link::new(conditions, seed.wrapping_add(1)),
+        }
+    }
+
+    /// Change the conditions of both directions. Packets already on their way keep their
+    /// delivery time.
+    pub fn set_conditions(&mut self, conditions: Conditions) {
+        self.incoming.conditions = conditions;
+        self.outgoing.conditions = conditions;
+    }
+
+    pub fn conditions(&self) -> Conditions {
+        self.incoming.conditions
+    }
+
+    /// Whether packets have to go through the simulator. A perfect connection skips it once
+    /// everything that was in flight has arrived.
+    pub fn is_active(&self) -> bool {
+        !self.conditions().is_perfect()
+            || self.incoming.in_flight() > 0
+            || self.outgoing.in_flight() > 0
+    }
+
+    /// Switch to the next preset and return its name.
+    pub fn next_preset(&mut self) -> &'static str {
+        let current = PRESETS
+            .iter()
+            .position(|(_, conditions)| *conditions == self.conditions());
+        let (name, conditions) = match current {
+            Some(index) => PRESETS[(index + 1) % PRESETS.len()],
+            None => PRESETS[0],
+        };
+        self.set_conditions(conditions);
+        name
+    }
+}
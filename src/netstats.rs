@@ -0,0 +1,200 @@
+//! Bandwidth and decode time accounting for the packets we receive.
+//!
+//! Every packet is counted by type, and the entities inside censuses by entity type, so we can
+//! tell what census bandwidth is spent on. Totals are kept since the start of the session and
+//! over rolling one second and one minute windows.
+
+use crate::protocol;
+use std::collections::{BTreeMap, VecDeque};
+
+/// How long a bucket of the rolling windows is, in milliseconds.
+const BUCKET_LENGTH: f64 = 100.;
+
+/// The longest window that can be asked for, in milliseconds.
+pub const MAX_WINDOW: f64 = 60_000.;
+
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Totals {
+    pub count: u64,
+    pub bytes: u64,
+    /// Time spent decoding, in milliseconds.
+    pub decode_time: f64,
+}
+
+impl std::ops::AddAssign for Totals {
+    fn add_assign(&mut self, other: Self) {
+        self.count += other.count;
+        self.bytes += other.bytes;
+        self.decode_time += other.decode_time;
+    }
+}
+
+/// Totals for a single kind of thing, overall and over a rolling window.
+#[derive(Debug, Default)]
+pub struct Counter {
+    pub total: Totals,
+    /// Totals per bucket, oldest first.
+    buckets: VecDeque<(u64, Totals)>,
+}
+
+impl Counter {
+    /// Add to the totals at `now` (in milliseconds).
+    pub fn record(&mut self, now: f64, totals: Totals) {
+        self.total += totals;
+
+        let bucket = (now / BUCKET_LENGTH) as u64;
+        match self.buckets.back_mut() {
+            Some((index, last)) if *index == bucket => *last += totals,
+            _ => self.buckets.push_back((bucket, totals)),
+        }
+
+        let span = (MAX_WINDOW / BUCKET_LENGTH) as u64;
+        while let Some((index, _)) = self.buckets.front() {
+            if index + span > bucket {
+                break;
+            }
+            self.buckets.pop_front();
+        }
+    }
+
+    /// The totals over the last `length` milliseconds before `now`.
+    pub fn window(&self, now: f64, length: f64) -> Totals {
+        let bucket = (now / BUCKET_LENGTH) as u64;
+        let span = (length / BUCKET_LENGTH) as u64;
+        let mut totals = Totals::default();
+        for (_, bucket_totals) in self
+            .buckets
+            .iter()
+            .filter(|(index, _)| index + span > bucket)
+        {
+            totals += *bucket_totals;
+        }
+        totals
+    }
+}
+
+/// The name of an entity type, for the overlay.
+pub fn entity_type_name(entity_type: u8) -> &'static str {
+    match num_traits::FromPrimitive::from_u8(entity_type) {
+        Some(protocol::EntityType::Tank) => "tank",
+        Some(protocol::EntityType::Shape) => "shape",
+        Some(protocol::EntityType::Bullet) => "bullet",
+        None => "unknown",
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.)
+    }
+}
+
+/// Network statistics for a session.
+#[derive(Debug, Default)]
+pub struct NetStats {
+    /// Per packet id.
+    pub packets: BTreeMap<u8, Counter>,
+    /// Per `protocol::EntityType`, for the entities inside censuses. Decode time isn't tracked
+    /// per entity.
+    pub entities: BTreeMap<u8, Counter>,
+    /// Whether the overlay is shown.
+    pub visible: bool,
+}
+
+impl NetStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a packet of `length` bytes (id included) that took `decode_time` milliseconds to
+    /// decode.
+    pub fn record_packet(&mut self, now: f64, id: u8, length: usize, decode_time: f64) {
+        self.packets.entry(id).or_default().record(
+            now,
+            Totals {
+                count: 1,
+                bytes: length as u64,
+                decode_time,
+            },
+        );
+    }
+
+    /// Count the entities of a census.
+    pub fn record_census(&mut self, now: f64, census: &protocol::Census) {
+        let mut per_type: BTreeMap<u8, Totals> = BTreeMap::new();
+        for entity in census.entities.values() {
+            let entity_type = match entity {
+                protocol::Entity::Tank(_) => protocol::EntityType::Tank,
+                protocol::Entity::Shape(_) => protocol::EntityType::Shape,
+                protocol::Entity::Bullet(_) => protocol::EntityType::Bullet,
+            };
+            let totals = per_type.entry(entity_type as u8).or_default();
+            totals.count += 1;
+            totals.bytes += entity.encoded_size() as u64;
+        }
+        for (entity_type, totals) in per_type {
+            self.entities
+                .entry(entity_type)
+                .or_default()
+                .record(now, totals);
+        }
+    }
+
+    /// The rows of the overlay as of `now`: packets by type, then census entities by type.
+    pub fn overlay_lines(&self, now: f64) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:<12}{:>8}{:>11}{:>8}{:>11}{:>11}",
+            "packet", "n/1s", "bytes/1s", "n/1m", "bytes/1m", "decode"
+        )];
+        for (id, counter) in &self.packets {
+            let second = counter.window(now, 1000.);
+            let minute = counter.window(now, MAX_WINDOW);
+            let decode = if minute.count > 0 {
+                format!("{:.3}ms", minute.decode_time / minute.count as f64)
+            } else {
+                String::from("-")
+            };
+            lines.push(format!(
+                "{:<12}{:>8}{:>11}{:>8}{:>11}{:>11}",
+                protocol::packet_name(*id).unwrap_or("unknown"),
+                second.count,
+                format_bytes(second.bytes),
+                minute.count,
+                format_bytes(minute.bytes),
+                decode
+            ));
+        }
+
+        let census_bytes = self
+            .packets
+            .get(&(protocol::Packet::Census as u8))
+            .map(|counter| counter.window(now, MAX_WINDOW).bytes)
+            .unwrap_or(0);
+        lines.push(String::new());
+        lines.push(format!(
+            "{:<12}{:>8}{:>11}{:>8}{:>11}{:>11}",
+            "entity", "n/1s", "bytes/1s", "n/1m", "bytes/1m", "census"
+        ));
+        for (entity_type, counter) in &self.entities {
+            let second = counter.window(now, 1000.);
+            let minute = counter.window(now, MAX_WINDOW);
+            let share = if census_bytes > 0 {
+                format!("{:.0}%", minute.bytes as f64 * 100. / census_bytes as f64)
+            } else {
+                String::from("-")
+            };
+            lines.push(format!(
+                "{:<12}{:>8}{:>11}{:>8}{:>11}{:>11}",
+                entity_type_name(*entity_type),
+                second.count,
+                format_bytes(second.bytes),
+                minute.count,
+                format_bytes(minute.bytes),
+                share
+            ));
+        }
+        lines
+    }
+}
@@ -1,13 +1,12 @@
 #![allow(dead_code)]
 #![allow(non_upper_case_globals)]
 use crate::binary;
-use crate::do_error_log;
 use crate::do_info_log;
 use crate::engine;
 use crate::util;
-use crate::wrapper;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
+use serde::Serialize;
 use std::collections::HashMap;
 
 /// This trait allows objects that implement it to be sent (and recieved!) over websockets.
@@ -17,9 +16,11 @@ pub trait Protocol {
     /// `encode` will consume its host object.
     fn encode(&self) -> binary::StreamPeerBuffer;
 
-    /// An associated function that takes a `binary::StreamPeerBuffer` and returns an instance
-    /// of `Self`
-    fn decode(buf: binary::StreamPeerBuffer) -> Self;
+    /// An associated function that reads an instance of `Self` from a `binary::StreamPeerBuffer`,
+    /// starting after the packet id.
+    ///
+    /// Malformed data doesn't make decoding fail; check `buf.error` afterwards.
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self;
 
     /// The id of the packet
     ///
@@ -45,7 +46,51 @@ pub enum Packet {
     Leaderboard = 7,
 }
 
+/// The name of a packet type, as used by the packet inspector and the stats overlay.
+pub fn packet_name(id: u8) -> Option<&'static str> {
+    match FromPrimitive::from_u8(id)? {
+        Packet::Init => Some("init"),
+        Packet::Input => Some("input"),
+        Packet::Census => Some("census"),
+        Packet::Handshake => Some("handshake"),
+        Packet::Message => Some("message"),
+        Packet::Death => Some("death"),
+        Packet::Respawn => Some("respawn"),
+        Packet::Leaderboard => Some("leaderboard"),
+    }
+}
+
+/// A decoded packet of any type.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Decoded {
+    Init(InitPacket),
+    Input(InputPacket),
+    Census(Census),
+    Handshake(HandshakePacket),
+    Message(MessagePacket),
+    Death(DeathPacket),
+    Respawn(RespawnPacket),
+    Leaderboard(LeaderboardPacket),
+}
+
+/// Decode a packet whose id has already been read from `buf`. Returns `None` for unknown ids.
+pub fn decode(id: u8, buf: &mut binary::StreamPeerBuffer) -> Option<Decoded> {
+    match FromPrimitive::from_u8(id) {
+        Some(Packet::Init) => Some(Decoded::Init(InitPacket::decode(buf))),
+        Some(Packet::Input) => Some(Decoded::Input(InputPacket::decode(buf))),
+        Some(Packet::Census) => Some(Decoded::Census(Census::decode(buf))),
+        Some(Packet::Handshake) => Some(Decoded::Handshake(HandshakePacket::decode(buf))),
+        Some(Packet::Message) => Some(Decoded::Message(MessagePacket::decode(buf))),
+        Some(Packet::Death) => Some(Decoded::Death(DeathPacket::decode(buf))),
+        Some(Packet::Respawn) => Some(Decoded::Respawn(RespawnPacket::decode(buf))),
+        Some(Packet::Leaderboard) => Some(Decoded::Leaderboard(LeaderboardPacket::decode(buf))),
+        None => None,
+    }
+}
+
 /// Packet that registers the player with the server.
+#[derive(Debug, Serialize)]
 pub struct InitPacket {
     pub name: String,
 }
@@ -60,12 +105,15 @@ impl Protocol for InitPacket {
 
     const id: u8 = Packet::Init as u8;
 
-    fn decode(_: binary::StreamPeerBuffer) -> Self {
-        unimplemented!()
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
+        Self {
+            name: buf.get_utf8(),
+        }
     }
 }
 
 /// Packet that sends chat messages
+#[derive(Debug, Serialize)]
 pub struct MessagePacket {
     pub message: String,
 }
@@ -80,8 +128,10 @@ impl Protocol for MessagePacket {
 
     const id: u8 = Packet::Message as u8;
 
-    fn decode(_: binary::StreamPeerBuffer) -> Self {
-        unimplemented!()
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
+        Self {
+            message: buf.get_utf8(),
+        }
     }
 }
 
@@ -90,6 +140,7 @@ impl Protocol for MessagePacket {
 ///
 /// See also: https://diep.io
 #[allow(non_snake_case)]
+#[derive(Debug, Serialize)]
 pub struct InputPacket {
     pub W: bool,
     pub A: bool,
@@ -121,8 +172,19 @@ impl Protocol for InputPacket {
 
     const id: u8 = Packet::Input as u8;
 
-    fn decode(_: binary::StreamPeerBuffer) -> Self {
-        unimplemented!()
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
+        let flags = buf.get_u8();
+        Self {
+            W: flags & 0b10000 != 0,
+            A: flags & 0b01000 != 0,
+            S: flags & 0b00100 != 0,
+            D: flags & 0b00010 != 0,
+            mouse_down: flags & 0b00001 != 0,
+            mouse_position: util::Vector2 {
+                x: buf.get_16(),
+                y: buf.get_16(),
+            },
+        }
     }
 }
 
@@ -138,6 +200,18 @@ impl InputPacket {
             mouse_position: input.mouse_position,
         }
     }
+
+    /// The inverse of `from_input`.
+    pub fn to_input(&self) -> engine::Input {
+        engine::Input {
+            W: self.W,
+            A: self.A,
+            S: self.S,
+            D: self.D,
+            mouse_down: self.mouse_down,
+            mouse_position: self.mouse_position,
+        }
+    }
 }
 
 /// ## Base
@@ -169,7 +243,7 @@ impl InputPacket {
 /// * Position (i16, i16)
 /// * Radius (u16)
 /// * Velocity (i16, i16)
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Census {
     pub entity_count: u16,
     pub arena_size: u16,
@@ -181,7 +255,7 @@ pub struct Census {
 }
 
 /// Represents the structure of a `Tank` when packed into a `Census`.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TankPacket {
     pub id: u32,
     pub position: util::Vector2<i16>,
@@ -195,7 +269,7 @@ pub struct TankPacket {
 }
 
 /// Represents the structure of a `Shape` when packed into a `Census`.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct ShapePacket {
     pub id: u32,
     pub position: util::Vector2<i16>,
@@ -204,7 +278,7 @@ pub struct ShapePacket {
 }
 
 /// Represents the structure of a `Bullet` when packed into a `Census`.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct BulletPacket {
     pub id: u32,
     pub position: util::Vector2<i16>,
@@ -214,34 +288,118 @@ pub struct BulletPacket {
 }
 
 /// Represents an entity id packed into a `Census`.
-#[derive(FromPrimitive)]
+#[derive(FromPrimitive, Debug, Copy, Clone, PartialEq, Eq)]
 pub enum EntityType {
     Tank = 0,
     Shape = 1,
     Bullet = 2,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum Entity {
     Tank(TankPacket),
     Shape(ShapePacket),
     Bullet(BulletPacket),
 }
 
+impl Entity {
+    pub fn id(&self) -> u32 {
+        match self {
+            Entity::Tank(t) => t.id,
+            Entity::Shape(s) => s.id,
+            Entity::Bullet(b) => b.id,
+        }
+    }
+
+    /// The amount of bytes the entity takes up in a census, type id included.
+    pub fn encoded_size(&self) -> usize {
+        match self {
+            Entity::Tank(t) => 24 + 2 + t.name.len() + 2 + t.message.len(),
+            Entity::Shape(_) => 15,
+            Entity::Bullet(_) => 19,
+        }
+    }
+
+    /// Pack the entity into a census, type id included.
+    pub fn encode(&self, buf: &mut binary::StreamPeerBuffer) {
+        match self {
+            Entity::Tank(t) => {
+                buf.put_u8(EntityType::Tank as u8);
+                buf.put_u32(t.id);
+                buf.put_16(t.position.x);
+                buf.put_16(t.position.y);
+                buf.put_float(t.rotation);
+                buf.put_16(t.velocity.x);
+                buf.put_16(t.velocity.y);
+                buf.put_u8(t.mockup);
+                buf.put_float(t.health);
+                buf.put_u16(t.radius);
+                buf.put_utf8(&t.name);
+                buf.put_utf8(&t.message);
+            }
+            Entity::Shape(s) => {
+                buf.put_u8(EntityType::Shape as u8);
+                buf.put_u32(s.id);
+                buf.put_16(s.position.x);
+                buf.put_16(s.position.y);
+                buf.put_float(s.health);
+                buf.put_u16(s.radius);
+            }
+            Entity::Bullet(b) => {
+                buf.put_u8(EntityType::Bullet as u8);
+                buf.put_u32(b.id);
+                buf.put_16(b.position.x);
+                buf.put_16(b.position.y);
+                buf.put_u16(b.radius);
+                buf.put_16(b.velocity.x);
+                buf.put_16(b.velocity.y);
+                buf.put_u32(b.owner);
+            }
+        }
+    }
+}
+
+impl Census {
+    pub fn new(arena_size: u16, level: f32, entities: Vec<Entity>) -> Self {
+        Self {
+            entity_count: entities.len() as u16,
+            arena_size,
+            level,
+            entities: entities
+                .into_iter()
+                .map(|entity| (entity.id(), entity))
+                .collect(),
+        }
+    }
+}
+
 impl Protocol for Census {
+    /// The entities go in order of their id, so the same census always packs the same way.
     fn encode(&self) -> binary::StreamPeerBuffer {
-        unimplemented!()
+        let mut buf = binary::StreamPeerBuffer::new();
+        buf.put_u8(Self::id);
+        buf.put_u16(self.entities.len() as u16);
+        buf.put_u16(self.arena_size);
+        buf.put_float(self.level);
+        let mut entities: Vec<&Entity> = self.entities.values().collect();
+        entities.sort_by_key(|entity| entity.id());
+        for entity in entities {
+            entity.encode(&mut buf);
+        }
+        buf
     }
 
     const id: u8 = Packet::Census as u8;
 
-    fn decode(mut buf: binary::StreamPeerBuffer) -> Self {
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
         let entity_count = buf.get_u16();
         let arena_size = buf.get_u16();
         let level = buf.get_float();
         let mut entities = HashMap::new();
         for _ in 0..entity_count {
-            match FromPrimitive::from_u8(buf.get_u8()) {
+            let offset = buf.cursor.position() as usize;
+            let entity_type = buf.get_u8();
+            match FromPrimitive::from_u8(entity_type) {
                 Some(EntityType::Tank) => {
                     let game_id = buf.get_u32();
                     entities.insert(
@@ -309,7 +467,11 @@ impl Protocol for Census {
                     );
                 }
 
-                None => do_error_log!("Fatal Error: Unknown entity with id {}", buf.get_u32()),
+                None => {
+                    // the rest can't be found without knowing how long this one is
+                    buf.fail(offset, format!("unknown entity type {}", entity_type));
+                    break;
+                }
             }
             //entities.insert(buf.get_u32(), );
         }
@@ -323,21 +485,21 @@ impl Protocol for Census {
 }
 
 /// Packet that acknowledges `InitPacket`.
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct HandshakePacket {
     pub id: u32,
     pub mockups: Vec<TankMockup>,
 }
 
 /// Represents Barrel as packed into HandshakePacket
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct BarrelMockup {
     pub width: f32,
     pub length: f32,
     pub angle: f32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct TankMockup {
     pub name: String,
     pub fov: u8,
@@ -351,7 +513,7 @@ impl Protocol for HandshakePacket {
 
     const id: u8 = Packet::Handshake as u8;
 
-    fn decode(mut buf: binary::StreamPeerBuffer) -> Self {
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
         let my_id = buf.get_u32();
         let mockup_count = buf.get_u8();
         let mut mockups = vec![];
@@ -375,7 +537,7 @@ impl Protocol for HandshakePacket {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct RespawnPacket;
 
 impl Protocol for RespawnPacket {
@@ -387,12 +549,12 @@ impl Protocol for RespawnPacket {
 
     const id: u8 = Packet::Respawn as u8;
 
-    fn decode(_buf: binary::StreamPeerBuffer) -> Self {
-        unimplemented!()
+    fn decode(_buf: &mut binary::StreamPeerBuffer) -> Self {
+        Self
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct DeathPacket {
     pub time_alive: f64,
 }
@@ -404,19 +566,19 @@ impl Protocol for DeathPacket {
 
     const id: u8 = Packet::Death as u8;
 
-    fn decode(mut buf: binary::StreamPeerBuffer) -> Self {
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
         Self {
             time_alive: buf.get_double(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct LeaderboardPacket {
     pub entries: Vec<LeaderboardEntry>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct LeaderboardEntry {
     pub name: String,
     pub level: f32,
@@ -430,7 +592,7 @@ impl Protocol for LeaderboardPacket {
 
     const id: u8 = Packet::Leaderboard as u8;
 
-    fn decode(mut buf: binary::StreamPeerBuffer) -> Self {
+    fn decode(buf: &mut binary::StreamPeerBuffer) -> Self {
         let count = buf.get_u8();
         let mut leaderboard = vec![];
         for _ in 0..count {
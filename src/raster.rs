@@ -0,0 +1,777 @@
+//! The software backend, which draws into an image in memory with tiny-skia.
+//!
+//! It follows the Canvas 2D model closely enough for tests and tools to see what the browser
+//! would draw, give or take anti-aliasing: paths are transformed as they're built, styles and
+//! clips are saved and restored, and shadows are blurred copies of what casts them. There is
+//! no system to ask for fonts, so text is drawn with the outlines of the `Font` the renderer is
+//! given, at the size the CSS font asks for. Without one, text isn't drawn and measures 0 wide.
+
+use crate::engine::World;
+use crate::geometry::Aabb;
+use crate::renderer::{self, RadialGradient, Renderer, Texture};
+use crate::util::Vector2;
+use std::cell::RefCell;
+use std::f32::consts::{FRAC_PI_2, TAU};
+use std::rc::Rc;
+use tiny_skia::{
+    BlendMode, Color, FillRule, FilterQuality, GradientStop, LineJoin, Mask, Paint, Path,
+    PathBuilder, Pattern, Pixmap, PixmapPaint, Point, Rect, Shader, SpreadMode, Stroke, Transform,
+};
+
+/// A TrueType or OpenType font to draw text with.
+#[derive(Clone)]
+pub struct Font {
+    data: Rc<Vec<u8>>,
+}
+
+impl Font {
+    /// Load a font file, or `None` if it isn't one.
+    pub fn new(data: Vec<u8>) -> Option<Self> {
+        ttf_parser::Face::parse(&data, 0).ok()?;
+        Some(Self {
+            data: Rc::new(data),
+        })
+    }
+
+    fn face(&self) -> ttf_parser::Face<'_> {
+        ttf_parser::Face::parse(&self.data, 0).expect("checked in Font::new")
+    }
+}
+
+/// Parse a CSS color, as far as `color::Color` understands them.
+fn parse_color(color: &str) -> Option<Color> {
+    let (r, g, b, alpha) = crate::color::Color::parse(color)?.channels();
+    let mut color = Color::from_rgba8(r, g, b, 255);
+    color.apply_opacity(alpha as f32);
+    Some(color)
+}
+
+/// The size in pixels of a CSS font like `bold 48px "Fira Sans"`.
+fn font_size(font: &str) -> Option<f32> {
+    font.split_whitespace()
+        .find_map(|part| part.strip_suffix("px")?.parse().ok())
+}
+
+#[derive(Clone)]
+enum Style {
+    Color(Color),
+    Gradient(RadialGradient),
+}
+
+/// Everything `save` and `restore` keep track of.
+#[derive(Clone)]
+struct State {
+    transform: Transform,
+    alpha: f32,
+    blend_mode: BlendMode,
+    fill: Style,
+    stroke: Color,
+    line_width: f32,
+    line_join: LineJoin,
+    shadow_blur: f32,
+    shadow_color: Color,
+    font_size: f32,
+    clip: Option<Rc<Mask>>,
+}
+
+impl Default for State {
+    /// The defaults of a fresh canvas.
+    fn default() -> Self {
+        Self {
+            transform: Transform::identity(),
+            alpha: 1.,
+            blend_mode: BlendMode::SourceOver,
+            fill: Style::Color(Color::BLACK),
+            stroke: Color::BLACK,
+            line_width: 1.,
+            line_join: LineJoin::Miter,
+            shadow_blur: 0.,
+            shadow_color: Color::TRANSPARENT,
+            font_size: 10.,
+            clip: None,
+        }
+    }
+}
+
+/// Turns glyph outlines into a path, from font units to user space.
+struct GlyphOutline<'a> {
+    builder: &'a mut PathBuilder,
+    x: f32,
+    y: f32,
+    scale: f32,
+}
+
+impl ttf_parser::OutlineBuilder for GlyphOutline<'_> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.builder
+            .move_to(self.x + x * self.scale, self.y - y * self.scale);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.builder
+            .line_to(self.x + x * self.scale, self.y - y * self.scale);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.builder.quad_to(
+            self.x + x1 * self.scale,
+            self.y - y1 * self.scale,
+            self.x + x * self.scale,
+            self.y - y * self.scale,
+        );
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.builder.cubic_to(
+            self.x + x1 * self.scale,
+            self.y - y1 * self.scale,
+            self.x + x2 * self.scale,
+            self.y - y2 * self.scale,
+            self.x + x * self.scale,
+            self.y - y * self.scale,
+        );
+    }
+
+    fn close(&mut self) {
+        self.builder.close();
+    }
+}
+
+/// Blur `alpha` (`width` wide) with three box blurs, which come close to a gaussian blur with
+/// a standard deviation of `sigma`.
+fn blur(alpha: &mut [f32], width: usize, height: usize, sigma: f32) {
+    let radius = (((4. * sigma * sigma + 1.).sqrt() - 1.) / 2.).round() as usize;
+    if radius == 0 {
+        return;
+    }
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for y in 0..height {
+            line.clear();
+            line.extend_from_slice(&alpha[y * width..(y + 1) * width]);
+            box_blur(&line, &mut alpha[y * width..(y + 1) * width], 1, radius);
+        }
+        for x in 0..width {
+            line.clear();
+            line.extend((0..height).map(|y| alpha[y * width + x]));
+            box_blur(&line, &mut alpha[x..], width, radius);
+        }
+    }
+}
+
+/// Blur `source` into every `stride`th value of `target`, averaging `radius` values to either
+/// side, with zeros past the ends.
+fn box_blur(source: &[f32], target: &mut [f32], stride: usize, radius: usize) {
+    let size = (radius * 2 + 1) as f32;
+    let mut sum: f32 = source.iter().take(radius).sum();
+    for i in 0..source.len() {
+        if let Some(entering) = source.get(i + radius) {
+            sum += entering;
+        }
+        target[i * stride] = sum / size;
+        if i >= radius {
+            sum -= source[i - radius];
+        }
+    }
+}
+
+/// Draws into a `tiny_skia::Pixmap`.
+pub struct SoftwareRenderer {
+    pixmap: Pixmap,
+    state: State,
+    saved: Vec<State>,
+    /// The current path, in pixels.
+    path: PathBuilder,
+    font: Option<Font>,
+}
+
+impl SoftwareRenderer {
+    /// A transparent `width` by `height` image, drawing text with `font`.
+    pub fn new(width: u32, height: u32, font: Option<Font>) -> Self {
+        Self::with_pixmap(
+            Pixmap::new(width.max(1), height.max(1)).expect("image too large"),
+            font,
+        )
+    }
+
+    fn with_pixmap(pixmap: Pixmap, font: Option<Font>) -> Self {
+        Self {
+            pixmap,
+            state: State::default(),
+            saved: Vec::new(),
+            path: PathBuilder::new(),
+            font,
+        }
+    }
+
+    pub fn pixmap(&self) -> &Pixmap {
+        &self.pixmap
+    }
+
+    pub fn into_pixmap(self) -> Pixmap {
+        self.pixmap
+    }
+
+    /// The image as RGBA bytes, without premultiplied alpha.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let color = pixel.demultiply();
+                [color.red(), color.green(), color.blue(), color.alpha()]
+            })
+            .collect()
+    }
+
+    /// Draw the image of `layer` over this one, the way stacked canvases show on the page.
+    pub fn draw_layer(&mut self, layer: &SoftwareRenderer) {
+        self.pixmap.draw_pixmap(
+            0,
+            0,
+            layer.pixmap.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+
+    fn point(&self, x: f64, y: f64) -> Point {
+        let mut point = Point::from_xy(x as f32, y as f32);
+        self.state.transform.map_point(&mut point);
+        point
+    }
+
+    /// The current path in user space, or `None` if there's nothing to draw.
+    fn user_path(&self) -> Option<Path> {
+        self.path
+            .clone()
+            .finish()?
+            .transform(self.state.transform.invert()?)
+    }
+
+    fn paint(&self, style: &Style) -> Paint<'static> {
+        let alpha = self.state.alpha;
+        let shader = match style {
+            Style::Color(color) => {
+                let mut color = *color;
+                color.apply_opacity(alpha);
+                Shader::SolidColor(color)
+            }
+            Style::Gradient(gradient) => {
+                let stops = gradient
+                    .stops
+                    .iter()
+                    .filter_map(|(offset, color)| {
+                        let mut color = parse_color(color)?;
+                        color.apply_opacity(alpha);
+                        Some(GradientStop::new(*offset as f32, color))
+                    })
+                    .collect();
+                // tiny-skia's gradients start from a point, and ours start from a tiny circle
+                tiny_skia::RadialGradient::new(
+                    Point::from_xy(gradient.x0 as f32, gradient.y0 as f32),
+                    Point::from_xy(gradient.x1 as f32, gradient.y1 as f32),
+                    gradient.r1 as f32,
+                    stops,
+                    SpreadMode::Pad,
+                    Transform::identity(),
+                )
+                .unwrap_or(Shader::SolidColor(Color::TRANSPARENT))
+            }
+        };
+        Paint {
+            shader,
+            blend_mode: self.state.blend_mode,
+            ..Paint::default()
+        }
+    }
+
+    fn stroke_style(&self) -> Stroke {
+        Stroke {
+            width: self.state.line_width,
+            miter_limit: 10.,
+            line_join: self.state.line_join,
+            ..Stroke::default()
+        }
+    }
+
+    /// Draw something, and its shadow under it. `bounds` is the area `draw` draws into, in
+    /// pixels, and `draw` draws with the transform it's given, which puts it wherever the
+    /// shadow is being drawn.
+    fn draw_shadowed(&mut self, bounds: Rect, draw: &dyn Fn(&mut Pixmap, Transform)) {
+        let clip = self.state.clip.clone();
+        let sigma = self.state.shadow_blur / 2.;
+        if sigma > 0. && self.state.shadow_color.alpha() > 0. {
+            let margin = (sigma * 3.).ceil() + 1.;
+            let left = (bounds.left() - margin).floor().max(0.);
+            let top = (bounds.top() - margin).floor().max(0.);
+            let right = (bounds.right() + margin)
+                .ceil()
+                .min(self.pixmap.width() as f32);
+            let bottom = (bounds.bottom() + margin)
+                .ceil()
+                .min(self.pixmap.height() as f32);
+            if let Some(mut layer) = Pixmap::new((right - left) as u32, (bottom - top) as u32) {
+                draw(&mut layer, Transform::from_translate(-left, -top));
+                let (width, height) = (layer.width() as usize, layer.height() as usize);
+                let mut alpha: Vec<f32> = layer.pixels().iter().map(|p| p.alpha() as f32).collect();
+                blur(&mut alpha, width, height, sigma);
+                let mut shadow = self.state.shadow_color;
+                shadow.apply_opacity(self.state.alpha);
+                for (pixel, alpha) in layer.pixels_mut().iter_mut().zip(alpha) {
+                    let mut color = shadow;
+                    color.apply_opacity(alpha / 255.);
+                    *pixel = color.premultiply().to_color_u8();
+                }
+                let paint = PixmapPaint {
+                    blend_mode: self.state.blend_mode,
+                    ..PixmapPaint::default()
+                };
+                self.pixmap.draw_pixmap(
+                    left as i32,
+                    top as i32,
+                    layer.as_ref(),
+                    &paint,
+                    Transform::identity(),
+                    clip.as_deref(),
+                );
+            }
+        }
+        let mut target = Pixmap::new(1, 1).unwrap();
+        std::mem::swap(&mut target, &mut self.pixmap);
+        match clip {
+            // the clip applies to what's drawn, so draw onto a layer to mask it
+            Some(mask) => {
+                let mut layer = Pixmap::new(target.width(), target.height()).unwrap();
+                draw(&mut layer, Transform::identity());
+                layer.apply_mask(&mask);
+                let paint = PixmapPaint {
+                    blend_mode: self.state.blend_mode,
+                    ..PixmapPaint::default()
+                };
+                target.draw_pixmap(0, 0, layer.as_ref(), &paint, Transform::identity(), None);
+            }
+            None => draw(&mut target, Transform::identity()),
+        }
+        self.pixmap = target;
+    }
+
+    /// The area a path covers once drawn with the current transform, in pixels.
+    fn bounds(&self, path: &Path, stroked: bool) -> Option<Rect> {
+        let bounds = path.clone().transform(self.state.transform)?.bounds();
+        if stroked {
+            let (sx, sy) = self.state.transform.get_scale();
+            let margin = self.state.line_width * sx.max(sy) * 5.;
+            bounds.outset(margin, margin)
+        } else {
+            Some(bounds)
+        }
+    }
+
+    fn fill_user_path(&mut self, path: &Path) {
+        let paint = self.paint(&self.state.fill.clone());
+        let transform = self.state.transform;
+        if let Some(bounds) = self.bounds(path, false) {
+            self.draw_shadowed(bounds, &|pixmap, offset| {
+                pixmap.fill_path(
+                    path,
+                    &paint,
+                    FillRule::Winding,
+                    transform.post_concat(offset),
+                    None,
+                );
+            });
+        }
+    }
+
+    fn stroke_user_path(&mut self, path: &Path) {
+        let paint = self.paint(&Style::Color(self.state.stroke));
+        let stroke = self.stroke_style();
+        let transform = self.state.transform;
+        if let Some(bounds) = self.bounds(path, true) {
+            self.draw_shadowed(bounds, &|pixmap, offset| {
+                pixmap.stroke_path(path, &paint, &stroke, transform.post_concat(offset), None);
+            });
+        }
+    }
+
+    fn rect_path(x: f64, y: f64, width: f64, height: f64) -> Option<Path> {
+        let (left, right) = (x.min(x + width), x.max(x + width));
+        let (top, bottom) = (y.min(y + height), y.max(y + height));
+        Rect::from_ltrb(left as f32, top as f32, right as f32, bottom as f32)
+            .map(PathBuilder::from_rect)
+    }
+
+    /// `text` as a path in user space, with its baseline starting at (`x`, `y`).
+    fn text_path(&self, text: &str, x: f64, y: f64) -> Option<Path> {
+        let font = self.font.as_ref()?;
+        let face = font.face();
+        let scale = self.state.font_size / face.units_per_em() as f32;
+        let mut builder = PathBuilder::new();
+        let mut pen = x as f32;
+        for c in text.chars() {
+            let glyph = face.glyph_index(c).unwrap_or(ttf_parser::GlyphId(0));
+            face.outline_glyph(
+                glyph,
+                &mut GlyphOutline {
+                    builder: &mut builder,
+                    x: pen,
+                    y: y as f32,
+                    scale,
+                },
+            );
+            pen += face.glyph_hor_advance(glyph).unwrap_or(0) as f32 * scale;
+        }
+        builder.finish()
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    fn save(&mut self) {
+        self.saved.push(self.state.clone());
+    }
+
+    fn restore(&mut self) {
+        if let Some(state) = self.saved.pop() {
+            self.state = state;
+        }
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        self.state.transform = self.state.transform.pre_translate(x as f32, y as f32);
+    }
+
+    fn rotate(&mut self, angle: f64) {
+        self.state.transform = self
+            .state
+            .transform
+            .pre_concat(Transform::from_rotate(angle.to_degrees() as f32));
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        self.state.transform = self.state.transform.pre_scale(x as f32, y as f32);
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        if (0. ..=1.).contains(&alpha) {
+            self.state.alpha = alpha as f32;
+        }
+    }
+
+    fn set_blend_mode(&mut self, mode: renderer::BlendMode) {
+        self.state.blend_mode = match mode {
+            renderer::BlendMode::Normal => BlendMode::SourceOver,
+            renderer::BlendMode::Lighter => BlendMode::Plus,
+        };
+    }
+
+    fn set_fill_color(&mut self, color: &str) {
+        // like a canvas, ignore colors it doesn't understand
+        if let Some(color) = parse_color(color) {
+            self.state.fill = Style::Color(color);
+        }
+    }
+
+    fn set_fill_gradient(&mut self, gradient: &RadialGradient) {
+        self.state.fill = Style::Gradient(gradient.clone());
+    }
+
+    fn set_stroke_color(&mut self, color: &str) {
+        if let Some(color) = parse_color(color) {
+            self.state.stroke = color;
+        }
+    }
+
+    fn set_line_width(&mut self, width: f64) {
+        if width > 0. {
+            self.state.line_width = width as f32;
+        }
+    }
+
+    fn set_line_join(&mut self, join: renderer::LineJoin) {
+        self.state.line_join = match join {
+            renderer::LineJoin::Miter => LineJoin::Miter,
+            renderer::LineJoin::Round => LineJoin::Round,
+            renderer::LineJoin::Bevel => LineJoin::Bevel,
+        };
+    }
+
+    fn set_shadow_blur(&mut self, blur: f64) {
+        if blur >= 0. {
+            self.state.shadow_blur = blur as f32;
+        }
+    }
+
+    fn set_shadow_color(&mut self, color: &str) {
+        if let Some(color) = parse_color(color) {
+            self.state.shadow_color = color;
+        }
+    }
+
+    fn set_font(&mut self, font: &str) {
+        if let Some(size) = font_size(font) {
+            self.state.font_size = size;
+        }
+    }
+
+    fn begin_path(&mut self) {
+        self.path.clear();
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) {
+        let point = self.point(x, y);
+        self.path.move_to(point.x, point.y);
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        let point = self.point(x, y);
+        // like a canvas, a line from nowhere starts the path
+        if self.path.is_empty() {
+            self.path.move_to(point.x, point.y);
+        } else {
+            self.path.line_to(point.x, point.y);
+        }
+    }
+
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        let (x, y, radius, start) = (x as f32, y as f32, radius as f32, start as f32);
+        let sweep = end as f32 - start;
+        let sweep = if sweep >= TAU {
+            TAU
+        } else {
+            sweep.rem_euclid(TAU)
+        };
+        let at = |angle: f32, reach: f32| {
+            let (sin, cos) = angle.sin_cos();
+            (x + cos * reach, y + sin * reach)
+        };
+        let (start_x, start_y) = at(start, radius);
+        self.line_to(start_x as f64, start_y as f64);
+        // one cubic for every quarter turn or less
+        let segments = (sweep / FRAC_PI_2).ceil().max(1.) as usize;
+        let step = sweep / segments as f32;
+        let handle = radius * 4. / 3. * (step / 4.).tan();
+        for i in 0..segments {
+            let (a, b) = (start + step * i as f32, start + step * (i + 1) as f32);
+            let (ax, ay) = at(a, radius);
+            let (bx, by) = at(b, radius);
+            let mut points = [
+                Point::from_xy(ax - a.sin() * handle, ay + a.cos() * handle),
+                Point::from_xy(bx + b.sin() * handle, by - b.cos() * handle),
+                Point::from_xy(bx, by),
+            ];
+            self.state.transform.map_points(&mut points);
+            self.path.cubic_to(
+                points[0].x,
+                points[0].y,
+                points[1].x,
+                points[1].y,
+                points[2].x,
+                points[2].y,
+            );
+        }
+    }
+
+    fn close_path(&mut self) {
+        self.path.close();
+    }
+
+    fn fill(&mut self) {
+        if let Some(path) = self.user_path() {
+            self.fill_user_path(&path);
+        }
+    }
+
+    fn stroke(&mut self) {
+        if let Some(path) = self.user_path() {
+            self.stroke_user_path(&path);
+        }
+    }
+
+    fn clip(&mut self) {
+        let path = match self.path.clone().finish() {
+            Some(path) => path,
+            None => return,
+        };
+        let mut mask = match &self.state.clip {
+            Some(mask) => (**mask).clone(),
+            None => {
+                let mut mask = Mask::new(self.pixmap.width(), self.pixmap.height()).unwrap();
+                mask.data_mut().fill(255);
+                mask
+            }
+        };
+        mask.intersect_path(&path, FillRule::Winding, true, Transform::identity());
+        self.state.clip = Some(Rc::new(mask));
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(path) = Self::rect_path(x, y, width, height) {
+            self.fill_user_path(&path);
+        }
+    }
+
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(path) = Self::rect_path(x, y, width, height) {
+            self.stroke_user_path(&path);
+        }
+    }
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        if let Some(path) = Self::rect_path(x, y, width, height) {
+            let paint = Paint {
+                blend_mode: BlendMode::Clear,
+                ..Paint::default()
+            };
+            let clip = self.state.clip.clone();
+            self.pixmap.fill_path(
+                &path,
+                &paint,
+                FillRule::Winding,
+                self.state.transform,
+                clip.as_deref(),
+            );
+        }
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64) {
+        if let Some(path) = self.text_path(text, x, y) {
+            self.fill_user_path(&path);
+        }
+    }
+
+    fn stroke_text(&mut self, text: &str, x: f64, y: f64) {
+        if let Some(path) = self.text_path(text, x, y) {
+            self.stroke_user_path(&path);
+        }
+    }
+
+    fn measure_text(&self, text: &str) -> f64 {
+        let font = match &self.font {
+            Some(font) => font,
+            None => return 0.,
+        };
+        let face = font.face();
+        let advance: u32 = text
+            .chars()
+            .map(|c| {
+                let glyph = face.glyph_index(c).unwrap_or(ttf_parser::GlyphId(0));
+                face.glyph_hor_advance(glyph).unwrap_or(0) as u32
+            })
+            .sum();
+        (advance as f32 * self.state.font_size / face.units_per_em() as f32) as f64
+    }
+
+    fn create_texture(&mut self, width: u32, height: u32) -> Texture {
+        let pixmap = Pixmap::new(width.max(1), height.max(1)).expect("texture too large");
+        Texture::new(width, height, RefCell::new(pixmap))
+    }
+
+    fn draw_to_texture(&mut self, texture: &Texture, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        let cell = texture
+            .data::<RefCell<Pixmap>>()
+            .expect("Texture wasn't created by a software renderer");
+        let pixmap = cell.replace(Pixmap::new(1, 1).unwrap());
+        let mut renderer = SoftwareRenderer::with_pixmap(pixmap, self.font.clone());
+        draw(&mut renderer);
+        cell.replace(renderer.into_pixmap());
+    }
+
+    fn owns_texture(&self, texture: &Texture) -> bool {
+        texture.data::<RefCell<Pixmap>>().is_some()
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, x: f64, y: f64, width: f64, height: f64) {
+        let source = Aabb::new(
+            Vector2 { x: 0., y: 0. },
+            // the pixmap is at least a pixel, even for an empty texture
+            Vector2 {
+                x: texture.width().max(1) as f64,
+                y: texture.height().max(1) as f64,
+            },
+        );
+        let destination = Aabb::new(
+            Vector2 { x, y },
+            Vector2 {
+                x: x + width,
+                y: y + height,
+            },
+        );
+        self.draw_texture_region(texture, source, destination);
+    }
+
+    fn draw_texture_region(&mut self, texture: &Texture, source: Aabb, destination: Aabb) {
+        let pixmap = match texture.data::<RefCell<Pixmap>>() {
+            Some(cell) => cell.borrow(),
+            None => return,
+        };
+        let (from, to) = (source.size(), destination.size());
+        let path = match Self::rect_path(destination.min.x, destination.min.y, to.x, to.y) {
+            Some(path) => path,
+            None => return,
+        };
+        let (scale_x, scale_y) = (to.x / from.x, to.y / from.y);
+        let pattern = Pattern::new(
+            pixmap.as_ref(),
+            SpreadMode::Pad,
+            FilterQuality::Bilinear,
+            self.state.alpha,
+            Transform::from_row(
+                scale_x as f32,
+                0.,
+                0.,
+                scale_y as f32,
+                (destination.min.x - source.min.x * scale_x) as f32,
+                (destination.min.y - source.min.y * scale_y) as f32,
+            ),
+        );
+        let paint = Paint {
+            shader: pattern,
+            blend_mode: self.state.blend_mode,
+            ..Paint::default()
+        };
+        let transform = self.state.transform;
+        if let Some(bounds) = self.bounds(&path, false) {
+            self.draw_shadowed(bounds, &|target, offset| {
+                target.fill_path(
+                    &path,
+                    &paint,
+                    FillRule::Winding,
+                    transform.post_concat(offset),
+                    None,
+                );
+            });
+        }
+    }
+}
+
+/// Draw a frame of `world` onto a `width` by `height` image, like `World::draw_frame` in a
+/// browser window of that size: the composite layer goes over the canvas, which goes over the
+/// page.
+pub fn draw_world(
+    world: &mut World,
+    width: u32,
+    height: u32,
+    alpha: f64,
+    frame: u32,
+    font: Option<Font>,
+) -> SoftwareRenderer {
+    let mut canvas = SoftwareRenderer::new(width, height, font.clone());
+    let mut composite = SoftwareRenderer::new(width, height, font);
+    world.draw_frame(
+        &mut canvas,
+        &mut composite,
+        width as f64,
+        height as f64,
+        alpha,
+        frame,
+    );
+    let mut page = SoftwareRenderer::new(width, height, None);
+    // the page behind the canvas, which shows outside of the arena
+    page.set_fill_color(world.theme.page.css());
+    page.fill_rect(0., 0., width as f64, height as f64);
+    page.draw_layer(&canvas);
+    page.draw_layer(&composite);
+    page
+}
@@ -0,0 +1,158 @@
+//! The drawing primitives the game uses, independent of what they're drawn on.
+//!
+//! Everything in `draw.rs`, the entities and the HUD draws through a `&mut dyn Renderer`. The
+//! methods follow the Canvas 2D API, so `canvas::CanvasRenderer` is a thin wrapper, and other
+//! backends emulate the parts of it we need: a current transform, fill and stroke styles, one
+//! path at a time, and a stack of saved states.
+
+use crate::geometry::Aabb;
+use std::any::Any;
+use std::fmt;
+use std::rc::Rc;
+
+/// How drawn pixels combine with the ones already there.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Draw on top (`source-over`).
+    Normal,
+    /// Add the colors up (`lighter`), for lights.
+    Lighter,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// A gradient between two circles, like `createRadialGradient`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RadialGradient {
+    pub x0: f64,
+    pub y0: f64,
+    pub r0: f64,
+    pub x1: f64,
+    pub y1: f64,
+    pub r1: f64,
+    /// Offsets from 0 to 1 and CSS colors, in order.
+    pub stops: Vec<(f64, String)>,
+}
+
+impl RadialGradient {
+    pub fn new(x0: f64, y0: f64, r0: f64, x1: f64, y1: f64, r1: f64) -> Self {
+        Self {
+            x0,
+            y0,
+            r0,
+            x1,
+            y1,
+            r1,
+            stops: Vec::new(),
+        }
+    }
+
+    pub fn add_color_stop(&mut self, offset: f64, color: &str) {
+        self.stops.push((offset, String::from(color)));
+    }
+}
+
+/// An image drawn ahead of time, which can be drawn again cheaply.
+///
+/// What's inside depends on the backend that created it: a texture can only be drawn by the
+/// renderer that made it (see `Renderer::owns_texture`), and is freed once the last clone of
+/// it is dropped.
+#[derive(Clone)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    data: Rc<dyn Any>,
+}
+
+impl Texture {
+    /// Wrap the backend's own image. Only backends need this.
+    pub fn new<T: Any>(width: u32, height: u32, data: T) -> Self {
+        Self {
+            width,
+            height,
+            data: Rc::new(data),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The backend's image, if it is a `T`.
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref()
+    }
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Texture({}x{})", self.width, self.height)
+    }
+}
+
+/// Something to draw on. Colors are CSS color strings and fonts are CSS font shorthands, like
+/// `bold 48px "Fira Sans"`.
+pub trait Renderer {
+    /// Push the transform, clip and styles onto the state stack.
+    fn save(&mut self);
+    /// Pop the state stack.
+    fn restore(&mut self);
+
+    fn translate(&mut self, x: f64, y: f64);
+    fn rotate(&mut self, angle: f64);
+    fn scale(&mut self, x: f64, y: f64);
+
+    fn set_global_alpha(&mut self, alpha: f64);
+    fn set_blend_mode(&mut self, mode: BlendMode);
+
+    fn set_fill_color(&mut self, color: &str);
+    fn set_fill_gradient(&mut self, gradient: &RadialGradient);
+    fn set_stroke_color(&mut self, color: &str);
+    fn set_line_width(&mut self, width: f64);
+    fn set_line_join(&mut self, join: LineJoin);
+    fn set_shadow_blur(&mut self, blur: f64);
+    fn set_shadow_color(&mut self, color: &str);
+    fn set_font(&mut self, font: &str);
+
+    fn begin_path(&mut self);
+    fn move_to(&mut self, x: f64, y: f64);
+    fn line_to(&mut self, x: f64, y: f64);
+    /// Add a clockwise arc around (`x`, `y`) from angle `start` to `end`.
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64);
+    fn close_path(&mut self);
+    fn fill(&mut self);
+    fn stroke(&mut self);
+    /// Intersect the clip with the current path.
+    fn clip(&mut self);
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+    /// Make the rectangle fully transparent.
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64);
+
+    /// Draw text with its baseline starting at (`x`, `y`).
+    fn fill_text(&mut self, text: &str, x: f64, y: f64);
+    fn stroke_text(&mut self, text: &str, x: f64, y: f64);
+    /// How wide `text` is in the current font.
+    fn measure_text(&self, text: &str) -> f64;
+
+    /// Create a transparent texture of `width` by `height` pixels.
+    fn create_texture(&mut self, width: u32, height: u32) -> Texture;
+    /// Draw onto `texture`, with its top left corner at the origin.
+    fn draw_to_texture(&mut self, texture: &Texture, draw: &mut dyn FnMut(&mut dyn Renderer));
+    /// Whether `texture` was created by this renderer, and so can be drawn by it.
+    fn owns_texture(&self, texture: &Texture) -> bool;
+    /// Draw `texture` stretched over the rectangle.
+    fn draw_texture(&mut self, texture: &Texture, x: f64, y: f64, width: f64, height: f64);
+    /// Draw the `source` part of `texture` stretched over the `destination` rectangle.
+    fn draw_texture_region(&mut self, texture: &Texture, source: Aabb, destination: Aabb);
+}
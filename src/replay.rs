@@ -0,0 +1,360 @@
+//! Session recordings, used to attach exact reproductions to bug reports.
+//!
+//! ## Format
+//! All numbers are big endian, as written by `binary::StreamPeerBuffer`.
+//!
+//! * Magic (`CWRP`)
+//! * Version (u8)
+//! * Recorded at (f64, milliseconds since the unix epoch)
+//! * Handshake length (u32)
+//! * Handshake (the raw `HandshakePacket`, id included, so the mockups come with it)
+//!
+//! Followed by records until the end of the file:
+//!
+//! * Kind (u8, see `RecordKind`)
+//! * Time since the previous record (varint, microseconds)
+//! * Packet: length (varint) and the raw packet, id included
+//! * Input: the `InputPacket` payload (u8 flags, i16, i16)
+
+use crate::binary::StreamPeerBuffer;
+use crate::engine;
+use crate::protocol::{self, Protocol};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use std::ops::Range;
+
+pub const MAGIC: &[u8; 4] = b"CWRP";
+pub const VERSION: u8 = 1;
+
+/// The type of a record, as written to the file.
+#[derive(FromPrimitive)]
+pub enum RecordKind {
+    Packet = 0,
+    Input = 1,
+}
+
+#[derive(Debug)]
+pub enum Record {
+    /// A packet received from the server.
+    Packet(Vec<u8>),
+    /// The local input for a frame.
+    Input(engine::Input),
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    /// Milliseconds since the recording started.
+    pub time: f64,
+    pub record: Record,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ReplayError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    /// The file ends in the middle of something, at the given offset.
+    Truncated(usize),
+    /// Something at `offset` can't be read, like a varint that doesn't end.
+    Corrupt {
+        offset: usize,
+    },
+    UnknownRecord(u8),
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplayError::BadMagic => write!(f, "not a replay file"),
+            ReplayError::UnsupportedVersion(v) => write!(f, "unsupported replay version {}", v),
+            ReplayError::Truncated(offset) => write!(f, "replay is truncated at byte {}", offset),
+            ReplayError::Corrupt { offset } => write!(f, "replay is corrupt at byte {}", offset),
+            ReplayError::UnknownRecord(kind) => write!(f, "unknown record kind {}", kind),
+        }
+    }
+}
+
+/// A decoded recording.
+#[derive(Debug)]
+pub struct Replay {
+    pub version: u8,
+    pub recorded_at: f64,
+    pub handshake: Vec<u8>,
+    pub entries: Vec<Entry>,
+}
+
+fn read_bytes(buf: &mut StreamPeerBuffer, len: usize) -> Result<Vec<u8>, ReplayError> {
+    if buf.remaining() < len {
+        return Err(ReplayError::Truncated(buf.cursor.position() as usize));
+    }
+    Ok((0..len).map(|_| buf.get_u8()).collect())
+}
+
+/// Fail if a read from `buf` ran past the end of the file or found something unreadable.
+fn check(buf: &StreamPeerBuffer) -> Result<(), ReplayError> {
+    match &buf.error {
+        Some(error) if error.truncated => Err(ReplayError::Truncated(error.offset)),
+        Some(error) => Err(ReplayError::Corrupt {
+            offset: error.offset,
+        }),
+        None => Ok(()),
+    }
+}
+
+impl Replay {
+    pub fn decode(data: Vec<u8>) -> Result<Self, ReplayError> {
+        let mut buf = StreamPeerBuffer::new();
+        buf.set_data_array(data);
+
+        if read_bytes(&mut buf, MAGIC.len())? != MAGIC {
+            return Err(ReplayError::BadMagic);
+        }
+        let version = read_bytes(&mut buf, 1)?[0];
+        if version != VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+        if buf.remaining() < 12 {
+            return Err(ReplayError::Truncated(buf.cursor.position() as usize));
+        }
+        let recorded_at = buf.get_double();
+        let handshake_len = buf.get_u32() as usize;
+        check(&buf)?;
+        let handshake = read_bytes(&mut buf, handshake_len)?;
+
+        let mut entries = Vec::new();
+        let mut time_us: u64 = 0;
+        while buf.remaining() > 0 {
+            let kind = buf.get_u8();
+            check(&buf)?;
+            time_us += buf.get_varint_mc() as u32 as u64;
+            check(&buf)?;
+            let record = match FromPrimitive::from_u8(kind) {
+                Some(RecordKind::Packet) => {
+                    let len = buf.get_varint_mc() as u32 as usize;
+                    check(&buf)?;
+                    Record::Packet(read_bytes(&mut buf, len)?)
+                }
+                Some(RecordKind::Input) => {
+                    let mut payload = StreamPeerBuffer::new();
+                    payload.set_data_array(read_bytes(&mut buf, 5)?);
+                    Record::Input(protocol::InputPacket::decode(&mut payload).to_input())
+                }
+                None => return Err(ReplayError::UnknownRecord(kind)),
+            };
+            entries.push(Entry {
+                time: time_us as f64 / 1000.,
+                record,
+            });
+        }
+
+        Ok(Self {
+            version,
+            recorded_at,
+            handshake,
+            entries,
+        })
+    }
+
+    /// The handshake the recording started with.
+    pub fn handshake(&self) -> protocol::HandshakePacket {
+        let mut buf = StreamPeerBuffer::new();
+        buf.set_data_array(self.handshake.clone());
+        buf.get_u8();
+        protocol::HandshakePacket::decode(&mut buf)
+    }
+
+    /// The length of the recording in milliseconds.
+    pub fn duration(&self) -> f64 {
+        self.entries.last().map(|e| e.time).unwrap_or(0.)
+    }
+}
+
+/// Writes a recording as the session goes on.
+pub struct Recorder {
+    start: f64,
+    last_us: u64,
+    buf: StreamPeerBuffer,
+}
+
+impl Recorder {
+    /// Start recording. `now` is a high resolution timestamp in milliseconds, which every
+    /// later call has to use the same clock for.
+    pub fn new(now: f64, recorded_at: f64, handshake: &[u8]) -> Self {
+        let mut buf = StreamPeerBuffer::new();
+        for byte in MAGIC.iter() {
+            buf.put_u8(*byte);
+        }
+        buf.put_u8(VERSION);
+        buf.put_double(recorded_at);
+        buf.put_u32(handshake.len() as u32);
+        for byte in handshake {
+            buf.put_u8(*byte);
+        }
+        Self {
+            start: now,
+            last_us: 0,
+            buf,
+        }
+    }
+
+    fn put_header(&mut self, kind: RecordKind, now: f64) {
+        let now_us = ((now - self.start).max(0.) * 1000.).round() as u64;
+        let delta = now_us.saturating_sub(self.last_us).min(i32::MAX as u64);
+        self.last_us += delta;
+        self.buf.put_u8(kind as u8);
+        self.buf.put_varint_mc(delta as i32);
+    }
+
+    /// Record a packet received from the server.
+    pub fn record_packet(&mut self, now: f64, data: &[u8]) {
+        self.put_header(RecordKind::Packet, now);
+        self.buf.put_varint_mc(data.len() as i32);
+        for byte in data {
+            self.buf.put_u8(*byte);
+        }
+    }
+
+    /// Record the local input for a frame.
+    pub fn record_input(&mut self, now: f64, input: engine::Input) {
+        self.put_header(RecordKind::Input, now);
+        let packet = protocol::InputPacket::from_input(input).encode();
+        // skip the packet id
+        for byte in &packet.cursor.get_ref()[1..] {
+            self.buf.put_u8(*byte);
+        }
+    }
+
+    /// The size of the recording so far, in bytes.
+    pub fn size(&self) -> usize {
+        self.buf.cursor.get_ref().len()
+    }
+
+    /// Stop recording and return the file.
+    pub fn finish(self) -> Vec<u8> {
+        self.buf.cursor.into_inner()
+    }
+}
+
+/// Every `KEYFRAME_INTERVAL`th census is a keyframe that seeking rebuilds the world from.
+pub const KEYFRAME_INTERVAL: usize = 30;
+
+/// The playback speeds that can be picked, as multiples of real time.
+pub const SPEEDS: [f64; 5] = [0.25, 0.5, 1., 2., 4.];
+
+/// Plays a recording back.
+///
+/// The player only decides which entries are due; the caller feeds them through the normal
+/// packet handling. A "tick" is the index of a census in the recording.
+pub struct Player {
+    pub replay: Replay,
+    /// The index of the next entry to play.
+    pub cursor: usize,
+    /// The playback position in milliseconds.
+    pub time: f64,
+    pub speed: usize,
+    pub paused: bool,
+    /// The camera stops following `yourself` while this is set.
+    pub free_camera: bool,
+    /// The direction the free camera is panning in.
+    pub pan: crate::util::Vector2<f64>,
+    /// The entry index of every census.
+    ticks: Vec<usize>,
+}
+
+impl Player {
+    pub fn new(replay: Replay) -> Self {
+        let ticks = replay
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| match &entry.record {
+                Record::Packet(data) => data.first() == Some(&protocol::Census::id),
+                Record::Input(_) => false,
+            })
+            .map(|(index, _)| index)
+            .collect();
+        Self {
+            replay,
+            cursor: 0,
+            time: 0.,
+            speed: 2,
+            paused: false,
+            free_camera: false,
+            pan: crate::util::Vector2 { x: 0., y: 0. },
+            ticks,
+        }
+    }
+
+    /// The playback speed as a multiple of real time.
+    pub fn speed(&self) -> f64 {
+        SPEEDS[self.speed]
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = self.speed.saturating_sub(1);
+    }
+
+    /// The amount of ticks in the recording.
+    pub fn tick_count(&self) -> usize {
+        self.ticks.len()
+    }
+
+    /// The last tick that has been played.
+    pub fn tick(&self) -> usize {
+        match self.ticks.binary_search(&self.cursor) {
+            Ok(tick) => tick.saturating_sub(1),
+            Err(tick) => tick.saturating_sub(1),
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.replay.entries.len()
+    }
+
+    /// Move the playback position forward by `delta` milliseconds of real time and return the
+    /// range of entries that are now due.
+    pub fn advance(&mut self, delta: f64) -> Range<usize> {
+        let start = self.cursor;
+        if self.paused || self.finished() {
+            return start..start;
+        }
+        self.time += delta * self.speed();
+        while self.cursor < self.replay.entries.len()
+            && self.replay.entries[self.cursor].time <= self.time
+        {
+            self.cursor += 1;
+        }
+        start..self.cursor
+    }
+
+    /// Jump to `tick` and return the range of entries from the nearest keyframe up to and
+    /// including the census of that tick. The world has to be cleared before they are applied.
+    pub fn seek(&mut self, tick: usize) -> Range<usize> {
+        if self.ticks.is_empty() {
+            return self.cursor..self.cursor;
+        }
+        let tick = tick.min(self.ticks.len() - 1);
+        let keyframe = self.ticks[tick - tick % KEYFRAME_INTERVAL];
+        let target = self.ticks[tick];
+        self.cursor = target + 1;
+        self.time = self.replay.entries[target].time;
+        keyframe..self.cursor
+    }
+
+    /// The most recent leaderboard before `entry`, which a seek has to restore as well since
+    /// leaderboards are only sent when they change.
+    pub fn leaderboard_before(&self, entry: usize) -> Option<&[u8]> {
+        self.replay.entries[..entry]
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.record {
+                Record::Packet(data) if data.first() == Some(&protocol::LeaderboardPacket::id) => {
+                    Some(data.as_slice())
+                }
+                _ => None,
+            })
+    }
+}
@@ -0,0 +1,300 @@
+//! The player's settings, which are kept in `localStorage` between sessions.
+//!
+//! Settings are stored as JSON with a `version`. Settings saved by an older version of the game
+//! are brought up to date by `MIGRATIONS` before they're read, and anything they don't have
+//! comes from the defaults, so adding a setting doesn't need a new version; renaming or changing
+//! the meaning of one does. Settings from a newer version of the game aren't read at all.
+
+use crate::hitflash::HitFlash;
+use crate::lighting;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The version of the settings this build saves.
+pub const VERSION: u64 = 1;
+/// Where the settings are kept in `localStorage`.
+pub const STORAGE_KEY: &str = "cactuswar-settings";
+
+/// Upgrades stored settings from one version to the next.
+pub type Migration = fn(&mut Value);
+
+/// Upgrades stored settings by one version each: the first from version 1 to 2, and so on.
+/// Bump `VERSION` with each one.
+const MIGRATIONS: [Migration; VERSION as usize - 1] = [];
+
+/// How much goes into drawing the arena.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Quality {
+    Low,
+    Medium,
+    High,
+}
+
+impl Quality {
+    pub const ALL: [Quality; 3] = [Quality::Low, Quality::Medium, Quality::High];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Quality::Low => "Low",
+            Quality::Medium => "Medium",
+            Quality::High => "High",
+        }
+    }
+
+    /// The most lights drawn in a frame.
+    pub fn light_budget(self) -> usize {
+        match self {
+            Quality::Low => 4,
+            Quality::Medium => 8,
+            Quality::High => lighting::DEFAULT_BUDGET,
+        }
+    }
+
+    /// Whether lights cast shadows.
+    pub fn shadows(self) -> bool {
+        self != Quality::Low
+    }
+}
+
+/// What a key can be bound to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Action {
+    Up,
+    Left,
+    Down,
+    Right,
+    Chat,
+    NetStats,
+    Theme,
+    Screenshot,
+    Record,
+    NetSim,
+}
+
+impl Action {
+    pub const ALL: [Action; 10] = [
+        Action::Up,
+        Action::Left,
+        Action::Down,
+        Action::Right,
+        Action::Chat,
+        Action::NetStats,
+        Action::Theme,
+        Action::Screenshot,
+        Action::Record,
+        Action::NetSim,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "Move up",
+            Action::Left => "Move left",
+            Action::Down => "Move down",
+            Action::Right => "Move right",
+            Action::Chat => "Chat",
+            Action::NetStats => "Network stats",
+            Action::Theme => "Next theme",
+            Action::Screenshot => "Screenshot",
+            Action::Record => "Record",
+            Action::NetSim => "Next netsim",
+        }
+    }
+}
+
+/// Keys that do the same thing whatever the bindings, so can't be bound: Escape opens the menu,
+/// and Space, C, the arrows, comma and period control replays and the killcam.
+pub const RESERVED: [u32; 9] = [27, 32, 37, 38, 39, 40, 67, 188, 190];
+
+/// The key for each `Action`, as DOM `keyCode`s.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub up: u32,
+    pub left: u32,
+    pub down: u32,
+    pub right: u32,
+    pub chat: u32,
+    pub netstats: u32,
+    pub theme: u32,
+    pub screenshot: u32,
+    pub record: u32,
+    pub netsim: u32,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        Self {
+            up: 87,
+            left: 65,
+            down: 83,
+            right: 68,
+            chat: 13,
+            netstats: 113,
+            theme: 115,
+            screenshot: 118,
+            record: 119,
+            netsim: 120,
+        }
+    }
+}
+
+impl KeyBindings {
+    fn key_mut(&mut self, action: Action) -> &mut u32 {
+        match action {
+            Action::Up => &mut self.up,
+            Action::Left => &mut self.left,
+            Action::Down => &mut self.down,
+            Action::Right => &mut self.right,
+            Action::Chat => &mut self.chat,
+            Action::NetStats => &mut self.netstats,
+            Action::Theme => &mut self.theme,
+            Action::Screenshot => &mut self.screenshot,
+            Action::Record => &mut self.record,
+            Action::NetSim => &mut self.netsim,
+        }
+    }
+
+    /// The key bound to `action`.
+    pub fn key(&self, action: Action) -> u32 {
+        match action {
+            Action::Up => self.up,
+            Action::Left => self.left,
+            Action::Down => self.down,
+            Action::Right => self.right,
+            Action::Chat => self.chat,
+            Action::NetStats => self.netstats,
+            Action::Theme => self.theme,
+            Action::Screenshot => self.screenshot,
+            Action::Record => self.record,
+            Action::NetSim => self.netsim,
+        }
+    }
+
+    /// The action `key_code` is bound to, if any.
+    pub fn action(&self, key_code: u32) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| self.key(*action) == key_code)
+    }
+
+    /// Bind `key_code` to `action`. Whatever it was bound to before gets the old key of
+    /// `action`, so no key does two things. Returns false, and binds nothing, if the key is
+    /// `RESERVED`.
+    pub fn bind(&mut self, action: Action, key_code: u32) -> bool {
+        if RESERVED.contains(&key_code) {
+            return false;
+        }
+        let old = self.key(action);
+        if let Some(other) = self.action(key_code) {
+            *self.key_mut(other) = old;
+        }
+        *self.key_mut(action) = key_code;
+        true
+    }
+}
+
+/// What a key is called, from its DOM `keyCode`.
+pub fn key_name(key_code: u32) -> String {
+    match key_code {
+        8 => String::from("Backspace"),
+        9 => String::from("Tab"),
+        13 => String::from("Enter"),
+        16 => String::from("Shift"),
+        17 => String::from("Ctrl"),
+        18 => String::from("Alt"),
+        32 => String::from("Space"),
+        37 => String::from("Left"),
+        38 => String::from("Up"),
+        39 => String::from("Right"),
+        40 => String::from("Down"),
+        48..=57 | 65..=90 => char::from(key_code as u8).to_string(),
+        112..=123 => format!("F{}", key_code - 111),
+        188 => String::from(","),
+        190 => String::from("."),
+        _ => format!("Key {}", key_code),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub quality: Quality,
+    /// Whether tanks and bullets light up the arena at all.
+    pub lights: bool,
+    /// Names over other tanks.
+    pub show_names: bool,
+    /// Chat messages over tanks.
+    pub show_chat: bool,
+    /// How big the HUD is drawn, on top of the size that fits the window.
+    pub ui_scale: f64,
+    pub show_netstats: bool,
+    /// The name of a built-in theme.
+    pub theme: String,
+    /// How entities show that they've been hit, like `?hitflash=` (see `HitFlash::parse`).
+    pub hit_flash: String,
+    pub keys: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            quality: Quality::High,
+            lights: true,
+            show_names: true,
+            show_chat: true,
+            ui_scale: 1.,
+            show_netstats: false,
+            theme: String::from("desert"),
+            hit_flash: HitFlash::DEFAULT.to_string(),
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+impl Settings {
+    /// The smallest and largest UI scale.
+    pub const UI_SCALES: (f64, f64) = (0.5, 2.);
+
+    /// Read settings saved by `to_json`, from this version of the game or an older one.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::parse_with(text, &MIGRATIONS)
+    }
+
+    /// Parse settings as if this build saved version `migrations.len() + 1`, bringing older ones
+    /// up to date with `migrations`. `parse` is this with `MIGRATIONS`.
+    pub fn parse_with(text: &str, migrations: &[Migration]) -> Result<Self, String> {
+        let latest = migrations.len() as u64 + 1;
+        let mut value: Value =
+            serde_json::from_str(text).map_err(|e| format!("Invalid settings: {}", e))?;
+        let version = match value.get("version").and_then(Value::as_u64) {
+            Some(version) if version > latest => {
+                return Err(format!("Settings from a newer version ({})", version))
+            }
+            Some(version) if version > 0 => version,
+            _ => return Err(String::from("Settings without a version")),
+        };
+        for migrate in &migrations[version as usize - 1..] {
+            migrate(&mut value);
+        }
+        if let Value::Object(fields) = &mut value {
+            fields.remove("version");
+        }
+        serde_json::from_value(value).map_err(|e| format!("Invalid settings: {}", e))
+    }
+
+    /// The settings as JSON, with the version they're from.
+    pub fn to_json(&self) -> String {
+        let mut value = serde_json::to_value(self).expect("settings are always valid JSON");
+        if let Value::Object(fields) = &mut value {
+            fields.insert(String::from("version"), Value::from(VERSION));
+        }
+        value.to_string()
+    }
+
+    /// The hit flash, or the default one if it doesn't parse.
+    pub fn hit_flash(&self) -> HitFlash {
+        HitFlash::parse(&self.hit_flash).unwrap_or_default()
+    }
+}
@@ -0,0 +1,190 @@
+//! A spatial index over the entity cache.
+//!
+//! The world is cut into square cells, and every entity is filed under the cell its position
+//! is in, so that finding what's near something only looks at the cells around it rather than
+//! at every entity. Entities are filed by their last census position, which only changes when
+//! a census comes in: what's drawn lags behind or runs ahead of it a little, so queries for
+//! drawing should allow some slack (see `engine::INDEX_SLACK`).
+
+use crate::geometry::Aabb;
+use crate::protocol::EntityType;
+use crate::util::Vector2;
+use std::collections::HashMap;
+
+/// How big a cell is, in world units.
+pub const DEFAULT_CELL_SIZE: f64 = 500.;
+
+type Cell = (i32, i32);
+
+struct Entry {
+    position: Vector2<f64>,
+    entity_type: EntityType,
+    cell: Cell,
+}
+
+/// A uniform grid spatial hash of entity ids.
+pub struct SpatialHash {
+    cell_size: f64,
+    cells: HashMap<Cell, Vec<u32>>,
+    entries: HashMap<u32, Entry>,
+}
+
+impl SpatialHash {
+    /// An empty index with cells `cell_size` units wide.
+    pub fn new(cell_size: f64) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.entries.clear();
+    }
+
+    fn cell(&self, position: Vector2<f64>) -> Cell {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Add an entity, or move it if it's already in the index.
+    pub fn insert(&mut self, id: u32, entity_type: EntityType, position: Vector2<f64>) {
+        let cell = self.cell(position);
+        if let Some(entry) = self.entries.get_mut(&id) {
+            entry.position = position;
+            entry.entity_type = entity_type;
+            if entry.cell == cell {
+                return;
+            }
+            let previous = std::mem::replace(&mut entry.cell, cell);
+            self.unfile(id, previous);
+        } else {
+            self.entries.insert(
+                id,
+                Entry {
+                    position,
+                    entity_type,
+                    cell,
+                },
+            );
+        }
+        self.cells.entry(cell).or_default().push(id);
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        if let Some(entry) = self.entries.remove(&id) {
+            self.unfile(id, entry.cell);
+        }
+    }
+
+    /// Take `id` out of the list of `cell`.
+    fn unfile(&mut self, id: u32, cell: Cell) {
+        if let Some(ids) = self.cells.get_mut(&cell) {
+            ids.retain(|other| *other != id);
+            if ids.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Where the index has `id`.
+    pub fn position(&self, id: u32) -> Option<Vector2<f64>> {
+        self.entries.get(&id).map(|entry| entry.position)
+    }
+
+    /// The entities in the cells that `rect` touches, whether or not they're in it.
+    fn candidates<'a>(&'a self, rect: &Aabb) -> impl Iterator<Item = (u32, &'a Entry)> + 'a {
+        let (min, max) = (self.cell(rect.min), self.cell(rect.max));
+        (min.0..=max.0)
+            .flat_map(move |x| (min.1..=max.1).map(move |y| (x, y)))
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .map(move |id| (*id, &self.entries[id]))
+    }
+
+    /// The entities positioned inside `rect`.
+    pub fn query_rect(&self, rect: &Aabb) -> Vec<u32> {
+        self.candidates(rect)
+            .filter(|(_, entry)| rect.contains(entry.position))
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// The entities no further than `radius` from `center`.
+    pub fn within_radius(&self, center: Vector2<f64>, radius: f64) -> Vec<u32> {
+        let bounds = Aabb::from_center(center, Vector2::new(radius, radius));
+        self.candidates(&bounds)
+            .filter(|(_, entry)| entry.position.distance(center) <= radius)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// The `n` entities of `entity_type` (or of any type) nearest to `point`, nearest first.
+    pub fn nearest(
+        &self,
+        point: Vector2<f64>,
+        n: usize,
+        entity_type: Option<EntityType>,
+    ) -> Vec<u32> {
+        let center = self.cell(point);
+        // no cell further out than this has anything in it
+        let last_ring = self
+            .cells
+            .keys()
+            .map(|cell| (cell.0 - center.0).abs().max((cell.1 - center.1).abs()))
+            .max();
+        let last_ring = match last_ring {
+            Some(ring) if n > 0 => ring,
+            _ => return Vec::new(),
+        };
+
+        let mut found: Vec<(f64, u32)> = Vec::new();
+        for ring in 0..=last_ring {
+            for cell in ring_cells(center, ring) {
+                for id in self.cells.get(&cell).into_iter().flatten() {
+                    let entry = &self.entries[id];
+                    if entity_type.is_none_or(|t| t == entry.entity_type) {
+                        found.push((entry.position.distance(point), *id));
+                    }
+                }
+            }
+            // anything in the rings further out is at least this far away
+            let reach = ring as f64 * self.cell_size;
+            if found.len() >= n {
+                found.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                if found[n - 1].0 <= reach {
+                    break;
+                }
+            }
+        }
+        found.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        found.into_iter().take(n).map(|(_, id)| id).collect()
+    }
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(DEFAULT_CELL_SIZE)
+    }
+}
+
+/// The cells `ring` cells away from `center`, around it.
+fn ring_cells(center: Cell, ring: i32) -> impl Iterator<Item = Cell> {
+    (-ring..=ring).flat_map(move |dx| {
+        (-ring..=ring)
+            .filter(move |dy| dx.abs() == ring || dy.abs() == ring)
+            .map(move |dy| (center.0 + dx, center.1 + dy))
+    })
+}
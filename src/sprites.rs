@@ -0,0 +1,333 @@
+//! Entity sprites, drawn once into shared atlas pages and copied from there.
+//!
+//! Every cactus or bullet looks like any other of its kind with the same radius, color and
+//! sides, so rather than a texture per entity there is one `SpriteCache`. Sprites are keyed by
+//! what they look like, drawn onto a big texture page the first time they're needed, and only
+//! copied after that. Radii are rounded up to a multiple of `RADIUS_STEP`, so that entities of
+//! about the same size share a sprite, scaled down a little to fit. There are never more than
+//! `budget` pages: when they're full, the sprites used least recently make room.
+
+use crate::color::Color;
+use crate::geometry::Aabb;
+use crate::renderer::{Renderer, Texture};
+use crate::util::Vector2;
+use std::collections::HashMap;
+
+/// How wide and high a page is, in pixels.
+pub const PAGE_SIZE: u32 = 1024;
+
+/// How many pages the game's cache may have.
+pub const DEFAULT_BUDGET: usize = 6;
+
+/// Sprites are drawn at radii that are a multiple of this.
+pub const RADIUS_STEP: u16 = 4;
+
+/// Transparent pixels around each sprite, so that smoothing doesn't pick up its neighbours.
+const PADDING: u32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SpriteKind {
+    Cactus,
+    Bullet,
+}
+
+/// What a sprite looks like.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SpriteKey {
+    pub kind: SpriteKind,
+    /// The radius the sprite is drawn at (see `SpriteKey::bucket`).
+    pub radius: u16,
+    pub color: Color,
+    pub sides: u8,
+    /// How far into its hit flash it is, in steps of `FLASH_STEPS`.
+    pub flash: u8,
+}
+
+/// How many steps a hit flash is cut into, so that each gets a sprite of its own rather than
+/// every frame of it getting one.
+pub const FLASH_STEPS: u8 = 4;
+
+impl SpriteKey {
+    /// The radius that the sprite of something `radius` big is drawn at.
+    pub fn bucket(radius: u16) -> u16 {
+        radius.div_ceil(RADIUS_STEP).max(1) * RADIUS_STEP
+    }
+
+    /// The step of a hit flash as strong as `intensity` (0 to 1), rounded up so that a flash
+    /// doesn't end early.
+    pub fn flash_step(intensity: f64) -> u8 {
+        (intensity.clamp(0., 1.) * FLASH_STEPS as f64).ceil() as u8
+    }
+}
+
+/// A square of `size` pixels, padding included, with its top left corner at (`x`, `y`) on
+/// page `page`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Slot {
+    page: usize,
+    x: u32,
+    y: u32,
+    size: u32,
+}
+
+/// A row of slots as high as the first one put on it.
+struct Shelf {
+    y: u32,
+    height: u32,
+    /// Where the next slot goes.
+    x: u32,
+}
+
+struct Page {
+    texture: Texture,
+    shelves: Vec<Shelf>,
+    /// Where the next shelf goes.
+    bottom: u32,
+}
+
+impl Page {
+    /// Find room for a slot of `size` pixels on a page `page_size` pixels wide.
+    fn allocate(&mut self, size: u32, page_size: u32) -> Option<(u32, u32)> {
+        // the shelf that wastes the least height
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= size && shelf.x + size <= page_size)
+            .min_by_key(|shelf| shelf.height);
+        if let Some(shelf) = shelf {
+            let x = shelf.x;
+            shelf.x += size;
+            return Some((x, shelf.y));
+        }
+        if self.bottom + size > page_size {
+            return None;
+        }
+        let y = self.bottom;
+        self.shelves.push(Shelf {
+            y,
+            height: size,
+            x: size,
+        });
+        self.bottom += size;
+        Some((0, y))
+    }
+
+    fn reset(&mut self) {
+        self.shelves.clear();
+        self.bottom = 0;
+    }
+}
+
+struct Sprite {
+    slot: Slot,
+    /// When the sprite was last drawn, in `SpriteCache::draw` calls.
+    last_used: u64,
+}
+
+/// Sprites drawn onto texture pages, to be copied from there (see the module documentation).
+///
+/// Pages belong to the renderer that created them. Drawing with a renderer that doesn't own
+/// them throws the cache away and starts over.
+pub struct SpriteCache {
+    budget: usize,
+    page_size: u32,
+    pages: Vec<Page>,
+    sprites: HashMap<SpriteKey, Sprite>,
+    /// Slots of evicted sprites, free to be used again.
+    free: Vec<Slot>,
+    clock: u64,
+}
+
+impl SpriteCache {
+    /// A cache of at most `budget` pages of `PAGE_SIZE` pixels. With a budget of 0, sprites are
+    /// drawn every time they're needed.
+    pub fn new(budget: usize) -> Self {
+        Self::with_page_size(budget, PAGE_SIZE)
+    }
+
+    /// A cache of at most `budget` pages of `page_size` pixels.
+    pub fn with_page_size(budget: usize, page_size: u32) -> Self {
+        Self {
+            budget,
+            page_size,
+            pages: Vec::new(),
+            sprites: HashMap::new(),
+            free: Vec::new(),
+            clock: 0,
+        }
+    }
+
+    /// How many sprites are in the cache.
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// How many pages the sprites are on.
+    pub fn pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn contains(&self, key: &SpriteKey) -> bool {
+        self.sprites.contains_key(key)
+    }
+
+    /// Throw away every sprite and page.
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.sprites.clear();
+        self.free.clear();
+    }
+
+    /// Draw the sprite for `key`, which is `size` pixels square, stretched over `destination`.
+    /// If it isn't in the cache yet, `render` draws it first, with its top left corner at the
+    /// origin.
+    pub fn draw(
+        &mut self,
+        ctx: &mut dyn Renderer,
+        key: &SpriteKey,
+        size: u32,
+        destination: Aabb,
+        render: &mut dyn FnMut(&mut dyn Renderer),
+    ) {
+        self.clock += 1;
+        if let Some(page) = self.pages.first() {
+            if !ctx.owns_texture(&page.texture) {
+                self.clear();
+            }
+        }
+
+        if let Some(sprite) = self.sprites.get_mut(key) {
+            sprite.last_used = self.clock;
+            let slot = sprite.slot;
+            self.copy(ctx, slot, size, destination);
+            return;
+        }
+
+        let slot = match self.allocate(ctx, size + 2 * PADDING) {
+            Some(slot) => slot,
+            None => {
+                let texture = ctx.create_texture(size, size);
+                ctx.draw_to_texture(&texture, render);
+                let extent = destination.size();
+                ctx.draw_texture(
+                    &texture,
+                    destination.min.x,
+                    destination.min.y,
+                    extent.x,
+                    extent.y,
+                );
+                return;
+            }
+        };
+        let (x, y, side) = (slot.x as f64, slot.y as f64, slot.size as f64);
+        ctx.draw_to_texture(&self.pages[slot.page].texture, &mut |page| {
+            page.save();
+            page.clear_rect(x, y, side, side);
+            page.begin_path();
+            page.move_to(x, y);
+            page.line_to(x + side, y);
+            page.line_to(x + side, y + side);
+            page.line_to(x, y + side);
+            page.close_path();
+            page.clip();
+            page.translate(x + PADDING as f64, y + PADDING as f64);
+            render(page);
+            page.restore();
+        });
+        self.sprites.insert(
+            key.clone(),
+            Sprite {
+                slot,
+                last_used: self.clock,
+            },
+        );
+        self.copy(ctx, slot, size, destination);
+    }
+
+    /// Draw the `size` pixels of sprite in `slot` over `destination`.
+    fn copy(&self, ctx: &mut dyn Renderer, slot: Slot, size: u32, destination: Aabb) {
+        let min = Vector2 {
+            x: (slot.x + PADDING) as f64,
+            y: (slot.y + PADDING) as f64,
+        };
+        let max = Vector2 {
+            x: min.x + size as f64,
+            y: min.y + size as f64,
+        };
+        ctx.draw_texture_region(
+            &self.pages[slot.page].texture,
+            Aabb::new(min, max),
+            destination,
+        );
+    }
+
+    /// Find a slot of `size` pixels, making room if there isn't any. `None` if the budget or
+    /// the page size doesn't allow for one.
+    fn allocate(&mut self, ctx: &mut dyn Renderer, size: u32) -> Option<Slot> {
+        if self.budget == 0 || size > self.page_size {
+            return None;
+        }
+        loop {
+            let free = self
+                .free
+                .iter()
+                .enumerate()
+                .filter(|(_, slot)| slot.size >= size)
+                .min_by_key(|(_, slot)| slot.size)
+                .map(|(i, _)| i);
+            if let Some(i) = free {
+                return Some(self.free.swap_remove(i));
+            }
+
+            for (i, page) in self.pages.iter_mut().enumerate() {
+                if let Some((x, y)) = page.allocate(size, self.page_size) {
+                    return Some(Slot {
+                        page: i,
+                        x,
+                        y,
+                        size,
+                    });
+                }
+            }
+
+            if self.pages.len() < self.budget {
+                self.pages.push(Page {
+                    texture: ctx.create_texture(self.page_size, self.page_size),
+                    shelves: Vec::new(),
+                    bottom: 0,
+                });
+                continue;
+            }
+
+            let oldest = self
+                .sprites
+                .iter()
+                .min_by_key(|(_, sprite)| sprite.last_used)
+                .map(|(key, sprite)| (key.clone(), sprite.slot));
+            let (key, oldest) = match oldest {
+                Some(oldest) => oldest,
+                None => {
+                    // only free slots that are too small are left
+                    self.free.clear();
+                    self.pages.iter_mut().for_each(Page::reset);
+                    continue;
+                }
+            };
+            if oldest.size >= size {
+                self.sprites.remove(&key);
+                self.free.push(oldest);
+            } else {
+                // its slot is too small, and so might every other free one be: start its page
+                // over instead
+                let page = oldest.page;
+                self.sprites.retain(|_, sprite| sprite.slot.page != page);
+                self.free.retain(|slot| slot.page != page);
+                self.pages[page].reset();
+            }
+        }
+    }
+}
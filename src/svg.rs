@@ -0,0 +1,721 @@
+//! The recording backend, which writes what's drawn into an SVG document.
+//!
+//! Paths keep the transform they were drawn with, and textures are written out in full where
+//! they're drawn, so the document stays sharp at any size. Canvas features map onto SVG ones:
+//! clips become `clipPath`s, shadows `feDropShadow` filters and `lighter` the `plus-lighter`
+//! blend mode. Two things can't be recorded: `clear_rect` only works on the whole document, by
+//! throwing away what was drawn so far, and text is measured by whoever creates the renderer,
+//! since the font is up to whatever shows the document.
+
+use crate::engine::World;
+use crate::geometry::Aabb;
+use crate::renderer::{BlendMode, LineJoin, RadialGradient, Renderer, Texture};
+use crate::sprites::SpriteCache;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Measures the width of some text (the second argument) in a CSS font (the first).
+pub type MeasureText = dyn Fn(&str, &str) -> f64;
+
+/// A 2D affine transform, in the order of Canvas's `setTransform(a, b, c, d, e, f)`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Matrix {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+}
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix {
+        a: 1.,
+        b: 0.,
+        c: 0.,
+        d: 1.,
+        e: 0.,
+        f: 0.,
+    };
+
+    /// `other` applied first, then `self`.
+    fn then(self, other: Matrix) -> Matrix {
+        Matrix {
+            a: self.a * other.a + self.c * other.b,
+            b: self.b * other.a + self.d * other.b,
+            c: self.a * other.c + self.c * other.d,
+            d: self.b * other.c + self.d * other.d,
+            e: self.a * other.e + self.c * other.f + self.e,
+            f: self.b * other.e + self.d * other.f + self.f,
+        }
+    }
+
+    fn invert(self) -> Option<Matrix> {
+        let determinant = self.a * self.d - self.b * self.c;
+        if determinant == 0. || !determinant.is_finite() {
+            return None;
+        }
+        Some(Matrix {
+            a: self.d / determinant,
+            b: -self.b / determinant,
+            c: -self.c / determinant,
+            d: self.a / determinant,
+            e: (self.c * self.f - self.d * self.e) / determinant,
+            f: (self.b * self.e - self.a * self.f) / determinant,
+        })
+    }
+
+    fn apply(self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+}
+
+/// A number as short as SVG allows, to two decimal places.
+fn number(n: f64) -> String {
+    let n = (n * 100.).round() / 100.;
+    if n == 0. {
+        // no "-0"
+        return String::from("0");
+    }
+    let mut text = format!("{:.2}", n);
+    while text.ends_with('0') {
+        text.pop();
+    }
+    if text.ends_with('.') {
+        text.pop();
+    }
+    text
+}
+
+/// Escape text for use in XML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn transform_attribute(matrix: Matrix) -> String {
+    if matrix == Matrix::IDENTITY {
+        return String::new();
+    }
+    format!(
+        " transform=\"matrix({} {} {} {} {} {})\"",
+        number(matrix.a),
+        number(matrix.b),
+        number(matrix.c),
+        number(matrix.d),
+        number(matrix.e),
+        number(matrix.f)
+    )
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Segment {
+    Move(f64, f64),
+    Line(f64, f64),
+    Cubic(f64, f64, f64, f64, f64, f64),
+    Close,
+}
+
+/// Everything `save` and `restore` keep track of.
+#[derive(Clone)]
+struct State {
+    transform: Matrix,
+    alpha: f64,
+    blend_mode: BlendMode,
+    /// A color, or a `url()` of a gradient.
+    fill: String,
+    stroke: String,
+    line_width: f64,
+    line_join: LineJoin,
+    shadow_blur: f64,
+    shadow_color: Option<String>,
+    font: String,
+    /// The id of the `clipPath` everything is clipped to.
+    clip: Option<String>,
+}
+
+impl Default for State {
+    /// The defaults of a fresh canvas.
+    fn default() -> Self {
+        Self {
+            transform: Matrix::IDENTITY,
+            alpha: 1.,
+            blend_mode: BlendMode::Normal,
+            fill: String::from("#000000"),
+            stroke: String::from("#000000"),
+            line_width: 1.,
+            line_join: LineJoin::Miter,
+            shadow_blur: 0.,
+            shadow_color: None,
+            font: String::from("10px sans-serif"),
+            clip: None,
+        }
+    }
+}
+
+/// What every layer and texture of a document shares.
+struct Document {
+    defs: RefCell<String>,
+    next_id: Cell<u32>,
+    /// Shadow filters, by blur and color.
+    filters: RefCell<HashMap<(String, String), String>>,
+    measure_text: Box<MeasureText>,
+}
+
+impl Document {
+    fn id(&self, prefix: &str) -> String {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        format!("{}{}", prefix, id)
+    }
+}
+
+/// Records drawing as SVG.
+pub struct SvgRenderer {
+    document: Rc<Document>,
+    body: String,
+    state: State,
+    saved: Vec<State>,
+    /// The current path, in pixels.
+    path: Vec<Segment>,
+    width: f64,
+    height: f64,
+}
+
+impl SvgRenderer {
+    /// An empty `width` by `height` document. `measure_text` is asked how wide text is.
+    pub fn new(width: f64, height: f64, measure_text: Box<MeasureText>) -> Self {
+        Self::with_document(
+            Rc::new(Document {
+                defs: RefCell::new(String::new()),
+                next_id: Cell::new(0),
+                filters: RefCell::new(HashMap::new()),
+                measure_text,
+            }),
+            width,
+            height,
+        )
+    }
+
+    fn with_document(document: Rc<Document>, width: f64, height: f64) -> Self {
+        Self {
+            document,
+            body: String::new(),
+            state: State::default(),
+            saved: Vec::new(),
+            path: Vec::new(),
+            width,
+            height,
+        }
+    }
+
+    /// Another layer of the same document, to be stacked over this one by `layers_to_svg`.
+    pub fn layer(&self) -> Self {
+        Self::with_document(self.document.clone(), self.width, self.height)
+    }
+
+    /// The document with only this layer in it.
+    pub fn to_svg(&self) -> String {
+        layers_to_svg(&[self])
+    }
+
+    /// The attributes that clip, shadow, fade and blend what's drawn, for the group around it.
+    fn group_attributes(&self) -> String {
+        let mut attributes = String::new();
+        if let Some(clip) = &self.state.clip {
+            write!(attributes, " clip-path=\"url(#{})\"", clip).unwrap();
+        }
+        if let (Some(color), true) = (&self.state.shadow_color, self.state.shadow_blur > 0.) {
+            let key = (number(self.state.shadow_blur), color.clone());
+            let mut filters = self.document.filters.borrow_mut();
+            let id = filters.entry(key).or_insert_with(|| {
+                let id = self.document.id("shadow");
+                write!(
+                    self.document.defs.borrow_mut(),
+                    "<filter id=\"{}\" x=\"-50%\" y=\"-50%\" width=\"200%\" height=\"200%\" \
+                     color-interpolation-filters=\"sRGB\"><feDropShadow dx=\"0\" dy=\"0\" \
+                     stdDeviation=\"{}\" flood-color=\"{}\"/></filter>",
+                    id,
+                    number(self.state.shadow_blur / 2.),
+                    escape(color)
+                )
+                .unwrap();
+                id
+            });
+            write!(attributes, " filter=\"url(#{})\"", id).unwrap();
+        }
+        if self.state.alpha < 1. {
+            write!(attributes, " opacity=\"{}\"", number(self.state.alpha)).unwrap();
+        }
+        if self.state.blend_mode == BlendMode::Lighter {
+            attributes.push_str(" style=\"mix-blend-mode:plus-lighter\"");
+        }
+        attributes
+    }
+
+    /// Add `element` to the document, inside a group that applies the clip, shadow, alpha and
+    /// blend mode, if any of them are set.
+    fn push(&mut self, element: &str) {
+        let attributes = self.group_attributes();
+        if attributes.is_empty() {
+            self.body.push_str(element);
+        } else {
+            write!(self.body, "<g{}>{}</g>", attributes, element).unwrap();
+        }
+    }
+
+    /// The current path as path data, mapped by `matrix`.
+    fn path_data(&self, matrix: Matrix) -> String {
+        let mut data = String::new();
+        let point = |x, y| {
+            let (x, y) = matrix.apply(x, y);
+            format!("{} {}", number(x), number(y))
+        };
+        for segment in &self.path {
+            match *segment {
+                Segment::Move(x, y) => write!(data, "M{}", point(x, y)),
+                Segment::Line(x, y) => write!(data, "L{}", point(x, y)),
+                Segment::Cubic(x1, y1, x2, y2, x, y) => {
+                    write!(data, "C{} {} {}", point(x1, y1), point(x2, y2), point(x, y))
+                }
+                Segment::Close => write!(data, "Z"),
+            }
+            .unwrap();
+        }
+        data
+    }
+
+    /// The current path in user space, with the transform that puts it back, or `None` if
+    /// there's nothing to draw.
+    fn user_path(&self) -> Option<String> {
+        if self.path.is_empty() {
+            return None;
+        }
+        let data = self.path_data(self.state.transform.invert()?);
+        Some(format!(
+            "d=\"{}\"{}",
+            data,
+            transform_attribute(self.state.transform)
+        ))
+    }
+
+    fn fill_attributes(&self) -> String {
+        format!("fill=\"{}\"", escape(&self.state.fill))
+    }
+
+    fn stroke_attributes(&self) -> String {
+        let join = match self.state.line_join {
+            LineJoin::Miter => "miter",
+            LineJoin::Round => "round",
+            LineJoin::Bevel => "bevel",
+        };
+        format!(
+            "fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"{}\"",
+            escape(&self.state.stroke),
+            number(self.state.line_width),
+            join
+        )
+    }
+
+    fn rect(&self, x: f64, y: f64, width: f64, height: f64) -> String {
+        format!(
+            "x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"{}",
+            number(x.min(x + width)),
+            number(y.min(y + height)),
+            number(width.abs()),
+            number(height.abs()),
+            transform_attribute(self.state.transform)
+        )
+    }
+
+    /// The end of a `<text>` element, from its position on.
+    fn text(&self, text: &str, x: f64, y: f64) -> String {
+        format!(
+            "x=\"{}\" y=\"{}\" style=\"font:{}\"{}>{}</text>",
+            number(x),
+            number(y),
+            escape(&self.state.font),
+            transform_attribute(self.state.transform),
+            escape(text)
+        )
+    }
+
+    fn add_point(&mut self, x: f64, y: f64, line: bool) {
+        let (x, y) = self.state.transform.apply(x, y);
+        // like a canvas, a line from nowhere starts the path
+        if line && !self.path.is_empty() {
+            self.path.push(Segment::Line(x, y));
+        } else {
+            self.path.push(Segment::Move(x, y));
+        }
+    }
+}
+
+impl Renderer for SvgRenderer {
+    fn save(&mut self) {
+        self.saved.push(self.state.clone());
+    }
+
+    fn restore(&mut self) {
+        if let Some(state) = self.saved.pop() {
+            self.state = state;
+        }
+    }
+
+    fn translate(&mut self, x: f64, y: f64) {
+        self.state.transform = self.state.transform.then(Matrix {
+            e: x,
+            f: y,
+            ..Matrix::IDENTITY
+        });
+    }
+
+    fn rotate(&mut self, angle: f64) {
+        let (sin, cos) = angle.sin_cos();
+        self.state.transform = self.state.transform.then(Matrix {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            e: 0.,
+            f: 0.,
+        });
+    }
+
+    fn scale(&mut self, x: f64, y: f64) {
+        self.state.transform = self.state.transform.then(Matrix {
+            a: x,
+            d: y,
+            ..Matrix::IDENTITY
+        });
+    }
+
+    fn set_global_alpha(&mut self, alpha: f64) {
+        if (0. ..=1.).contains(&alpha) {
+            self.state.alpha = alpha;
+        }
+    }
+
+    fn set_blend_mode(&mut self, mode: BlendMode) {
+        self.state.blend_mode = mode;
+    }
+
+    fn set_fill_color(&mut self, color: &str) {
+        self.state.fill = String::from(color);
+    }
+
+    fn set_fill_gradient(&mut self, gradient: &RadialGradient) {
+        let id = self.document.id("gradient");
+        let mut defs = self.document.defs.borrow_mut();
+        write!(
+            defs,
+            "<radialGradient id=\"{}\" gradientUnits=\"userSpaceOnUse\" cx=\"{}\" cy=\"{}\" \
+             r=\"{}\" fx=\"{}\" fy=\"{}\" fr=\"{}\">",
+            id,
+            number(gradient.x1),
+            number(gradient.y1),
+            number(gradient.r1),
+            number(gradient.x0),
+            number(gradient.y0),
+            number(gradient.r0)
+        )
+        .unwrap();
+        for (offset, color) in &gradient.stops {
+            write!(
+                defs,
+                "<stop offset=\"{}\" stop-color=\"{}\"/>",
+                number(*offset),
+                escape(color)
+            )
+            .unwrap();
+        }
+        defs.push_str("</radialGradient>");
+        self.state.fill = format!("url(#{})", id);
+    }
+
+    fn set_stroke_color(&mut self, color: &str) {
+        self.state.stroke = String::from(color);
+    }
+
+    fn set_line_width(&mut self, width: f64) {
+        if width > 0. {
+            self.state.line_width = width;
+        }
+    }
+
+    fn set_line_join(&mut self, join: LineJoin) {
+        self.state.line_join = join;
+    }
+
+    fn set_shadow_blur(&mut self, blur: f64) {
+        if blur >= 0. {
+            self.state.shadow_blur = blur;
+        }
+    }
+
+    fn set_shadow_color(&mut self, color: &str) {
+        self.state.shadow_color = Some(String::from(color));
+    }
+
+    fn set_font(&mut self, font: &str) {
+        self.state.font = String::from(font);
+    }
+
+    fn begin_path(&mut self) {
+        self.path.clear();
+    }
+
+    fn move_to(&mut self, x: f64, y: f64) {
+        self.add_point(x, y, false);
+    }
+
+    fn line_to(&mut self, x: f64, y: f64) {
+        self.add_point(x, y, true);
+    }
+
+    fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64) {
+        let sweep = end - start;
+        let sweep = if sweep >= 2. * PI {
+            2. * PI
+        } else {
+            sweep.rem_euclid(2. * PI)
+        };
+        let at = |angle: f64| {
+            let (sin, cos) = angle.sin_cos();
+            (x + cos * radius, y + sin * radius)
+        };
+        let (start_x, start_y) = at(start);
+        self.line_to(start_x, start_y);
+        // one cubic for every quarter turn or less
+        let segments = (sweep / FRAC_PI_2).ceil().max(1.) as usize;
+        let step = sweep / segments as f64;
+        let handle = radius * 4. / 3. * (step / 4.).tan();
+        let transform = self.state.transform;
+        for i in 0..segments {
+            let (a, b) = (start + step * i as f64, start + step * (i + 1) as f64);
+            let (ax, ay) = at(a);
+            let (bx, by) = at(b);
+            let (x1, y1) = transform.apply(ax - a.sin() * handle, ay + a.cos() * handle);
+            let (x2, y2) = transform.apply(bx + b.sin() * handle, by - b.cos() * handle);
+            let (x, y) = transform.apply(bx, by);
+            self.path.push(Segment::Cubic(x1, y1, x2, y2, x, y));
+        }
+    }
+
+    fn close_path(&mut self) {
+        if !self.path.is_empty() {
+            self.path.push(Segment::Close);
+        }
+    }
+
+    fn fill(&mut self) {
+        if let Some(path) = self.user_path() {
+            let element = format!("<path {} {}/>", path, self.fill_attributes());
+            self.push(&element);
+        }
+    }
+
+    fn stroke(&mut self) {
+        if let Some(path) = self.user_path() {
+            let element = format!("<path {} {}/>", path, self.stroke_attributes());
+            self.push(&element);
+        }
+    }
+
+    fn clip(&mut self) {
+        if self.path.is_empty() {
+            return;
+        }
+        let id = self.document.id("clip");
+        // clipping to a clip path that's clipped itself takes the intersection
+        let parent = match &self.state.clip {
+            Some(parent) => format!(" clip-path=\"url(#{})\"", parent),
+            None => String::new(),
+        };
+        write!(
+            self.document.defs.borrow_mut(),
+            "<clipPath id=\"{}\"{}><path d=\"{}\"/></clipPath>",
+            id,
+            parent,
+            self.path_data(Matrix::IDENTITY)
+        )
+        .unwrap();
+        self.state.clip = Some(id);
+    }
+
+    fn fill_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let element = format!(
+            "<rect {} {}/>",
+            self.rect(x, y, width, height),
+            self.fill_attributes()
+        );
+        self.push(&element);
+    }
+
+    fn stroke_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let element = format!(
+            "<rect {} {}/>",
+            self.rect(x, y, width, height),
+            self.stroke_attributes()
+        );
+        self.push(&element);
+    }
+
+    fn clear_rect(&mut self, x: f64, y: f64, width: f64, height: f64) {
+        let (left, top) = self
+            .state
+            .transform
+            .apply(x.min(x + width), y.min(y + height));
+        let (right, bottom) = self
+            .state
+            .transform
+            .apply(x.max(x + width), y.max(y + height));
+        let axis_aligned = self.state.transform.b == 0. && self.state.transform.c == 0.;
+        if axis_aligned
+            && left.min(right) <= 0.
+            && top.min(bottom) <= 0.
+            && left.max(right) >= self.width
+            && top.max(bottom) >= self.height
+        {
+            self.body.clear();
+        }
+    }
+
+    fn fill_text(&mut self, text: &str, x: f64, y: f64) {
+        let element = format!("<text {} {}", self.fill_attributes(), self.text(text, x, y));
+        self.push(&element);
+    }
+
+    fn stroke_text(&mut self, text: &str, x: f64, y: f64) {
+        let element = format!(
+            "<text {} {}",
+            self.stroke_attributes(),
+            self.text(text, x, y)
+        );
+        self.push(&element);
+    }
+
+    fn measure_text(&self, text: &str) -> f64 {
+        (self.document.measure_text)(&self.state.font, text)
+    }
+
+    fn create_texture(&mut self, width: u32, height: u32) -> Texture {
+        Texture::new(width, height, RefCell::new(String::new()))
+    }
+
+    fn draw_to_texture(&mut self, texture: &Texture, draw: &mut dyn FnMut(&mut dyn Renderer)) {
+        let body = texture
+            .data::<RefCell<String>>()
+            .expect("Texture wasn't created by an SVG renderer");
+        let mut renderer = Self::with_document(
+            self.document.clone(),
+            texture.width() as f64,
+            texture.height() as f64,
+        );
+        renderer.body = body.replace(String::new());
+        draw(&mut renderer);
+        body.replace(renderer.body);
+    }
+
+    fn owns_texture(&self, texture: &Texture) -> bool {
+        texture.data::<RefCell<String>>().is_some()
+    }
+
+    fn draw_texture(&mut self, texture: &Texture, x: f64, y: f64, width: f64, height: f64) {
+        let body = match texture.data::<RefCell<String>>() {
+            Some(body) => body.borrow().clone(),
+            None => return,
+        };
+        let placement = self.state.transform.then(Matrix {
+            a: width / texture.width().max(1) as f64,
+            b: 0.,
+            c: 0.,
+            d: height / texture.height().max(1) as f64,
+            e: x,
+            f: y,
+        });
+        let element = format!("<g{}>{}</g>", transform_attribute(placement), body);
+        self.push(&element);
+    }
+
+    fn draw_texture_region(&mut self, texture: &Texture, source: Aabb, destination: Aabb) {
+        let body = match texture.data::<RefCell<String>>() {
+            Some(body) => body.borrow().clone(),
+            None => return,
+        };
+        let (from, to) = (source.size(), destination.size());
+        let element = format!(
+            "<g{}><svg x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+             viewBox=\"{} {} {} {}\" preserveAspectRatio=\"none\">{}</svg></g>",
+            transform_attribute(self.state.transform),
+            number(destination.min.x),
+            number(destination.min.y),
+            number(to.x),
+            number(to.y),
+            number(source.min.x),
+            number(source.min.y),
+            number(from.x),
+            number(from.y),
+            body
+        );
+        self.push(&element);
+    }
+}
+
+/// A document with `layers` stacked over each other, the first one at the bottom. The layers
+/// must be from the same document (see `SvgRenderer::layer`).
+pub fn layers_to_svg(layers: &[&SvgRenderer]) -> String {
+    let first = layers.first().expect("no layers");
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+         viewBox=\"0 0 {0} {1}\"><defs>{2}</defs>",
+        number(first.width),
+        number(first.height),
+        first.document.defs.borrow()
+    );
+    for layer in layers {
+        // isolated, so lights only add up with what's on their own layer
+        write!(svg, "<g style=\"isolation:isolate\">{}</g>", layer.body).unwrap();
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Record a frame of `world` as an SVG document, like `World::draw_frame` in a browser window
+/// of `width` by `height` pixels.
+pub fn draw_world(
+    world: &mut World,
+    width: f64,
+    height: f64,
+    alpha: f64,
+    frame: u32,
+    measure_text: Box<MeasureText>,
+) -> String {
+    let mut page = SvgRenderer::new(width, height, measure_text);
+    // the page behind the canvas, which shows outside of the arena
+    page.set_fill_color(world.theme.page.css());
+    page.fill_rect(0., 0., width, height);
+    let mut canvas = page.layer();
+    let mut composite = page.layer();
+    // sprites are written out where they're drawn, rather than cut out of whole atlas pages
+    let sprites = std::mem::replace(&mut world.sprites, SpriteCache::new(0));
+    world.draw_frame(&mut canvas, &mut composite, width, height, alpha, frame);
+    world.sprites = sprites;
+    layers_to_svg(&[&page, &canvas, &composite])
+}
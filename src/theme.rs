@@ -0,0 +1,348 @@
+//! Themes: every color the game is drawn with.
+//!
+//! A `Theme` covers the arena, the entities, their lights and the HUD. Themes are plain data,
+//! so they load from JSON (see `Theme::parse`), and any color a JSON theme leaves out comes from
+//! the default desert theme: a custom theme only has to list what it changes. Colors are CSS
+//! colors, like `"#002606"` or `"rgba(252,250,157,0.25)"`.
+
+use crate::color::Color;
+use serde::{Deserialize, Serialize};
+
+/// The names of the built-in themes, in the order they're cycled through.
+pub const BUILT_IN: [&str; 5] = [
+    "desert",
+    "night",
+    "high-contrast",
+    "deuteranopia",
+    "tritanopia",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub name: String,
+    /// Around the arena.
+    pub page: Color,
+    pub arena: Color,
+    pub grid: Color,
+    pub cactus: CactusColors,
+    pub tank: TankColors,
+    pub bullet: BulletColors,
+    pub hud: HudColors,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CactusColors {
+    pub body: Color,
+    /// The star in the middle.
+    pub core: Color,
+    pub spikes: Color,
+    /// What a hit flashes the cactus to (see `hitflash`).
+    pub damaged: Color,
+    pub damaged_core: Color,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TankColors {
+    pub body: Color,
+    pub barrel: Color,
+    /// What a hit flashes the tank to (see `hitflash`).
+    pub damaged: Color,
+    pub light: Color,
+    pub health: Color,
+    pub health_background: Color,
+    pub name: Color,
+    pub name_outline: Color,
+    pub message_background: Color,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BulletColors {
+    /// Our own bullets.
+    pub own: Color,
+    pub own_glow: Color,
+    /// Everyone else's.
+    pub enemy: Color,
+    pub enemy_glow: Color,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HudColors {
+    pub title: Color,
+    pub text: Color,
+    pub text_outline: Color,
+    pub text_shadow: Color,
+    pub panel: Color,
+    pub bar_background: Color,
+    pub level: Color,
+    /// Replay and killcam progress, and the killcam title.
+    pub progress: Color,
+    pub leaderboard_level: Color,
+    pub recording: Color,
+    pub netsim: Color,
+    /// Over the world when we're dead.
+    pub death_overlay: Color,
+    /// Buttons, switches and the rest of the widgets in menus.
+    pub control: Color,
+    /// Around the widget with keyboard focus.
+    pub focus: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::desert()
+    }
+}
+
+impl Default for CactusColors {
+    fn default() -> Self {
+        Self {
+            body: Color::hex(0x002606),
+            core: Color::hex(0x001d03),
+            spikes: Color::hex(0x1a1a1a),
+            damaged: Color::hex(0x780000),
+            damaged_core: Color::hex(0x570000),
+        }
+    }
+}
+
+impl Default for TankColors {
+    fn default() -> Self {
+        Self {
+            body: Color::rgba(50, 50, 50, 1.),
+            barrel: Color::rgba(20, 20, 20, 1.),
+            damaged: Color::hex(0x780000),
+            light: Color::rgba(252, 250, 157, 0.25),
+            health: Color::hex(0x3ea832),
+            health_background: Color::hex(0x000000),
+            name: Color::hex(0xffffff),
+            name_outline: Color::hex(0x000000),
+            message_background: Color::hex(0x000000),
+        }
+    }
+}
+
+impl Default for BulletColors {
+    fn default() -> Self {
+        Self {
+            own: Color::hex(0x00e6f2),
+            own_glow: Color::rgba(0, 230, 242, 0.3),
+            enemy: Color::hex(0xf28900),
+            enemy_glow: Color::rgba(242, 137, 0, 0.3),
+        }
+    }
+}
+
+impl Default for HudColors {
+    fn default() -> Self {
+        Self {
+            title: Color::hex(0xf28900),
+            text: Color::hex(0xffffff),
+            text_outline: Color::hex(0x000000),
+            text_shadow: Color::hex(0x232323),
+            panel: Color::hex_with_alpha(0x121212aa),
+            bar_background: Color::hex(0x000000),
+            level: Color::hex(0x00ffff),
+            progress: Color::hex(0xf28900),
+            leaderboard_level: Color::rgba(140, 140, 140, 1.),
+            recording: Color::hex(0xff2020),
+            netsim: Color::hex(0xffd000),
+            death_overlay: Color::rgba(0, 0, 0, 0.2),
+            control: Color::hex_with_alpha(0x3a3a3add),
+            focus: Color::hex(0xffffff),
+        }
+    }
+}
+
+impl Theme {
+    /// Brown sand under a warm light, the way the game has always looked.
+    pub fn desert() -> Self {
+        Self {
+            name: String::from("desert"),
+            page: Color::rgba(20, 14, 0, 1.),
+            arena: Color::rgba(30, 23, 0, 1.),
+            grid: Color::rgba(20, 14, 0, 1.),
+            cactus: CactusColors::default(),
+            tank: TankColors::default(),
+            bullet: BulletColors::default(),
+            hud: HudColors::default(),
+        }
+    }
+
+    /// The desert after dark, under cold moonlight.
+    pub fn night() -> Self {
+        Self {
+            name: String::from("night"),
+            page: Color::hex(0x04060d),
+            arena: Color::hex(0x0a0f1f),
+            grid: Color::hex(0x050811),
+            cactus: CactusColors {
+                body: Color::hex(0x0d3b2c),
+                core: Color::hex(0x082a1f),
+                spikes: Color::hex(0x161c28),
+                ..CactusColors::default()
+            },
+            tank: TankColors {
+                body: Color::hex(0x2b3142),
+                barrel: Color::hex(0x151923),
+                light: Color::rgba(170, 200, 255, 0.22),
+                ..TankColors::default()
+            },
+            bullet: BulletColors {
+                own: Color::hex(0x3de9ff),
+                own_glow: Color::rgba(61, 233, 255, 0.35),
+                enemy: Color::hex(0xff9d2e),
+                enemy_glow: Color::rgba(255, 157, 46, 0.35),
+            },
+            hud: HudColors {
+                title: Color::hex(0x7fb2ff),
+                panel: Color::hex_with_alpha(0x0a0f1fcc),
+                progress: Color::hex(0x7fb2ff),
+                control: Color::hex_with_alpha(0x2b3142dd),
+                ..HudColors::default()
+            },
+        }
+    }
+
+    /// Pure colors on black, for the most contrast between everything.
+    pub fn high_contrast() -> Self {
+        Self {
+            name: String::from("high-contrast"),
+            page: Color::hex(0x000000),
+            arena: Color::hex(0x000000),
+            grid: Color::hex(0x404040),
+            cactus: CactusColors {
+                body: Color::hex(0x00c000),
+                core: Color::hex(0x006000),
+                spikes: Color::hex(0xffffff),
+                damaged: Color::hex(0xff0000),
+                damaged_core: Color::hex(0x800000),
+            },
+            tank: TankColors {
+                body: Color::hex(0x909090),
+                barrel: Color::hex(0xffffff),
+                damaged: Color::hex(0xff0000),
+                light: Color::rgba(255, 255, 255, 0.3),
+                health: Color::hex(0x00ff00),
+                ..TankColors::default()
+            },
+            bullet: BulletColors {
+                own: Color::hex(0x00ffff),
+                own_glow: Color::rgba(0, 255, 255, 0.4),
+                enemy: Color::hex(0xff00ff),
+                enemy_glow: Color::rgba(255, 0, 255, 0.4),
+            },
+            hud: HudColors {
+                title: Color::hex(0xffff00),
+                panel: Color::hex_with_alpha(0x000000e6),
+                progress: Color::hex(0xffff00),
+                leaderboard_level: Color::hex(0xffffff),
+                death_overlay: Color::rgba(0, 0, 0, 0.6),
+                control: Color::hex(0x202020),
+                focus: Color::hex(0xffff00),
+                ..HudColors::default()
+            },
+        }
+    }
+
+    /// The desert for red-green colorblindness (deuteranopia and protanopia): hits flash
+    /// white instead of red, and bullets are blue or orange.
+    pub fn deuteranopia() -> Self {
+        Self {
+            name: String::from("deuteranopia"),
+            cactus: CactusColors {
+                damaged: Color::hex(0xf0f0f0),
+                damaged_core: Color::hex(0xa0a0a0),
+                ..CactusColors::default()
+            },
+            tank: TankColors {
+                damaged: Color::hex(0xf0f0f0),
+                health: Color::hex(0x56b4e9),
+                ..TankColors::default()
+            },
+            bullet: BulletColors {
+                own: Color::hex(0x56b4e9),
+                own_glow: Color::rgba(86, 180, 233, 0.3),
+                enemy: Color::hex(0xe69f00),
+                enemy_glow: Color::rgba(230, 159, 0, 0.3),
+            },
+            hud: HudColors {
+                level: Color::hex(0x56b4e9),
+                ..HudColors::default()
+            },
+            ..Self::desert()
+        }
+    }
+
+    /// The desert for blue-yellow colorblindness (tritanopia): hits flash white, and bullets
+    /// are white or red.
+    pub fn tritanopia() -> Self {
+        Self {
+            name: String::from("tritanopia"),
+            cactus: CactusColors {
+                damaged: Color::hex(0xf0f0f0),
+                damaged_core: Color::hex(0xa0a0a0),
+                ..CactusColors::default()
+            },
+            tank: TankColors {
+                damaged: Color::hex(0xf0f0f0),
+                ..TankColors::default()
+            },
+            bullet: BulletColors {
+                own: Color::hex(0xf0f0f0),
+                own_glow: Color::rgba(240, 240, 240, 0.3),
+                enemy: Color::hex(0xe8175d),
+                enemy_glow: Color::rgba(232, 23, 93, 0.3),
+            },
+            hud: HudColors {
+                title: Color::hex(0xe8175d),
+                progress: Color::hex(0xe8175d),
+                level: Color::hex(0xf0f0f0),
+                ..HudColors::default()
+            },
+            ..Self::desert()
+        }
+    }
+
+    /// One of the `BUILT_IN` themes, by name.
+    pub fn built_in(name: &str) -> Option<Self> {
+        match name {
+            "desert" => Some(Self::desert()),
+            "night" => Some(Self::night()),
+            "high-contrast" => Some(Self::high_contrast()),
+            "deuteranopia" => Some(Self::deuteranopia()),
+            "tritanopia" => Some(Self::tritanopia()),
+            _ => None,
+        }
+    }
+
+    /// Parse the name of a built-in theme, or a theme in JSON.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(theme) = Self::built_in(text) {
+            return Ok(theme);
+        }
+        if !text.starts_with('{') {
+            return Err(format!("Unknown theme: {}", text));
+        }
+        serde_json::from_str(text).map_err(|e| format!("Invalid theme: {}", e))
+    }
+
+    /// The theme as JSON, which `parse` reads back.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("themes are always valid JSON")
+    }
+
+    /// The built-in theme after this one, going by name.
+    pub fn next(&self) -> Self {
+        let next = match BUILT_IN.iter().position(|name| *name == self.name) {
+            Some(index) => BUILT_IN[(index + 1) % BUILT_IN.len()],
+            None => BUILT_IN[0],
+        };
+        Self::built_in(next).expect("built-in themes exist")
+    }
+}
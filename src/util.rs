@@ -1,7 +1,9 @@
 #![allow(unused_imports)]
+#[cfg(target_arch = "wasm32")]
 use js_sys::Math;
 use num_traits::{Float, One, Zero};
-use std::ops::{Add, Mul, Sub};
+use serde::Serialize;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub, SubAssign};
 
 pub trait Lerp<F> {
     /// Interpolate and extrapolate between `self` and `other` using `t` as the parameter.
@@ -17,7 +19,7 @@ pub trait Lerp<F> {
     /// Basic lerping on floating points:
     ///
     /// ```
-    /// use lerp::Lerp;
+    /// use cactuswar_client::util::Lerp;
     ///
     /// let four_32 = 3.0_f32.lerp(5.0, 0.5);
     /// assert_eq!(four_32, 4.0);
@@ -28,21 +30,21 @@ pub trait Lerp<F> {
     /// Extrapolation:
     ///
     /// ```
-    /// # use lerp::Lerp;
+    /// # use cactuswar_client::util::Lerp;
     /// assert_eq!(3.0.lerp(4.0, 2.0), 5.0);
     /// ```
     ///
     /// Negative extrapolation:
     ///
     /// ```
-    /// # use lerp::Lerp;
+    /// # use cactuswar_client::util::Lerp;
     /// assert_eq!(3.0.lerp(4.0, -1.0), 2.0);
     /// ```
     ///
     /// Reverse interpolation:
     ///
     /// ```
-    /// # use lerp::Lerp;
+    /// # use cactuswar_client::util::Lerp;
     /// assert_eq!(5.0.lerp(3.0, 0.5), 4.0);
     /// ```
     fn lerp(self, other: Self, t: F) -> Self;
@@ -55,14 +57,14 @@ pub trait Lerp<F> {
     /// Bounding on numbers greater than one:
     ///
     /// ```
-    /// # use lerp::Lerp;
+    /// # use cactuswar_client::util::Lerp;
     /// assert_eq!(3.0.lerp_bounded(4.0, 2.0), 4.0);
     /// ```
     ///
     /// Bounding on numbers less than zero:
     ///
     /// ```
-    /// # use lerp::Lerp;
+    /// # use cactuswar_client::util::Lerp;
     /// assert_eq!(3.0.lerp_bounded(5.0, -2.0), 3.0);
     /// ```
     fn lerp_bounded(self, other: Self, t: F) -> Self
@@ -106,17 +108,269 @@ pub fn lerp_angle(a: f64, b: f64, t: f64) -> f64 {
 }
 
 /// Holds 2 numbers.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
 pub struct Vector2<T: PartialOrd + Copy + Zero + One> {
     pub x: T,
     pub y: T,
 }
 
+impl<T: PartialOrd + Copy + Zero + One> Vector2<T> {
+    pub fn new(x: T, y: T) -> Self {
+        Vector2 { x, y }
+    }
+
+    pub fn zero() -> Self {
+        Vector2 {
+            x: T::zero(),
+            y: T::zero(),
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Add<Output = T>> Add for Vector2<T> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Vector2 {
+            x: self.x + other.x,
+            y: self.y + other.y,
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Sub<Output = T>> Sub for Vector2<T> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Vector2 {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Mul<Output = T>> Mul<T> for Vector2<T> {
+    type Output = Self;
+
+    fn mul(self, t: T) -> Self {
+        Vector2 {
+            x: self.x * t,
+            y: self.y * t,
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Div<Output = T>> Div<T> for Vector2<T> {
+    type Output = Self;
+
+    fn div(self, t: T) -> Self {
+        Vector2 {
+            x: self.x / t,
+            y: self.y / t,
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Neg<Output = T>> Neg for Vector2<T> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Vector2 {
+            x: -self.x,
+            y: -self.y,
+        }
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Add<Output = T>> AddAssign for Vector2<T> {
+    fn add_assign(&mut self, other: Self) {
+        *self = *self + other;
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Sub<Output = T>> SubAssign for Vector2<T> {
+    fn sub_assign(&mut self, other: Self) {
+        *self = *self - other;
+    }
+}
+
+impl<T: PartialOrd + Copy + Zero + One + Mul<Output = T>> MulAssign<T> for Vector2<T> {
+    fn mul_assign(&mut self, t: T) {
+        *self = *self * t;
+    }
+}
+
+/// Vector math on floating point vectors. Lerping comes from [`Lerp`], which every vector gets
+/// through its operators.
+///
+/// ```
+/// # use cactuswar_client::util::{Lerp, Vector2};
+/// let a = Vector2::new(3., 4.);
+/// assert_eq!(a.length(), 5.);
+/// assert_eq!(a.normalize(), Vector2::new(0.6, 0.8));
+/// assert_eq!(a.perp(), Vector2::new(-4., 3.));
+/// assert_eq!(a.lerp(Vector2::zero(), 0.5), Vector2::new(1.5, 2.));
+/// ```
+impl<T: Float> Vector2<T> {
+    /// A vector of length 1 pointing at `angle` radians.
+    pub fn from_angle(angle: T) -> Self {
+        Vector2 {
+            x: angle.cos(),
+            y: angle.sin(),
+        }
+    }
+
+    pub fn dot(self, other: Self) -> T {
+        self.x * other.x + self.y * other.y
+    }
+
+    /// The 2D cross product: positive when `other` is counterclockwise from `self`, in a
+    /// y-down canvas that's clockwise on screen.
+    pub fn cross(self, other: Self) -> T {
+        self.x * other.y - self.y * other.x
+    }
+
+    pub fn length_squared(self) -> T {
+        self.dot(self)
+    }
+
+    pub fn length(self) -> T {
+        self.x.hypot(self.y)
+    }
+
+    pub fn distance(self, other: Self) -> T {
+        (other - self).length()
+    }
+
+    /// The vector scaled to a length of 1, or zero if it has no length.
+    pub fn normalize(self) -> Self {
+        let length = self.length();
+        if length == T::zero() {
+            self
+        } else {
+            self / length
+        }
+    }
+
+    /// The vector rotated a quarter turn.
+    pub fn perp(self) -> Self {
+        Vector2 {
+            x: -self.y,
+            y: self.x,
+        }
+    }
+
+    /// The vector rotated by `angle` radians.
+    pub fn rotate(self, angle: T) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Vector2 {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
+
+    /// The angle of the vector in radians, as given by `atan2`.
+    pub fn angle(self) -> T {
+        self.y.atan2(self.x)
+    }
+}
+
+impl From<Vector2<i16>> for Vector2<f64> {
+    fn from(v: Vector2<i16>) -> Self {
+        Vector2 {
+            x: v.x as f64,
+            y: v.y as f64,
+        }
+    }
+}
+
+/// Rounds to the nearest integer, saturating at the bounds of `i16`.
+impl From<Vector2<f64>> for Vector2<i16> {
+    fn from(v: Vector2<f64>) -> Self {
+        Vector2 {
+            x: v.x.round() as i16,
+            y: v.y.round() as i16,
+        }
+    }
+}
+
+/// A named easing curve, mapping the progress of a tween (0 to 1) to how far along the value is.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    /// Overshoots and wobbles around the target before settling.
+    OutElastic,
+    /// Pulls back a little before heading for the target.
+    InBack,
+    /// Overshoots the target a little and comes back.
+    OutBack,
+}
+
+impl Easing {
+    /// Apply the curve to `t`, which is clamped to `[0, 1]`.
+    ///
+    /// ```
+    /// # use cactuswar_client::util::Easing;
+    /// assert_eq!(Easing::OutCubic.apply(0.), 0.);
+    /// assert_eq!(Easing::OutCubic.apply(0.5), 0.875);
+    /// assert_eq!(Easing::OutBack.apply(1.), 1.);
+    /// ```
+    pub fn apply(self, t: f64) -> f64 {
+        const BACK: f64 = 1.70158;
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::InCubic => t * t * t,
+            Easing::OutCubic => 1. - (1. - t).powi(3),
+            Easing::InOutCubic => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (2. - 2. * t).powi(3) / 2.
+                }
+            }
+            Easing::OutElastic => {
+                if t == 0. || t == 1. {
+                    t
+                } else {
+                    2f64.powf(-10. * t)
+                        * ((t * 10. - 0.75) * (2. * std::f64::consts::PI / 3.)).sin()
+                        + 1.
+                }
+            }
+            Easing::InBack => (BACK + 1.) * t * t * t - BACK * t * t,
+            Easing::OutBack => 1. + (BACK + 1.) * (t - 1.).powi(3) + BACK * (t - 1.).powi(2),
+        }
+    }
+}
+
+/// A tween in progress.
+#[derive(Debug, Copy, Clone)]
+struct Tween<T: Float> {
+    from: T,
+    elapsed: T,
+    duration: T,
+    easing: Easing,
+}
+
 /// Holds a number and a target number, useful for lerp targets.
+///
+/// There are a few ways of moving `value` towards `tv`, all of which take the time since the
+/// last call in milliseconds and give the same result whether they're called often with small
+/// steps or rarely with big ones: [`smooth`](Scalar::smooth) for exponential smoothing,
+/// [`spring`](Scalar::spring) for a spring with some bounce, and [`tween`](Scalar::tween) for
+/// a fixed length animation along an [`Easing`] curve, started with
+/// [`tween_to`](Scalar::tween_to).
 #[derive(Debug, Copy, Clone)]
 pub struct Scalar<T: Float> {
     pub value: T,
     pub tv: T,
+    /// How fast `value` is changing per millisecond, for springs.
+    pub velocity: T,
+    tween: Option<Tween<T>>,
 }
 
 impl<T> Scalar<T>
@@ -124,9 +378,16 @@ where
     T: Float,
 {
     pub fn new(e: T) -> Self {
-        Scalar { value: e, tv: e }
+        Scalar {
+            value: e,
+            tv: e,
+            velocity: T::zero(),
+            tween: None,
+        }
     }
 
+    /// Move `e` of the way to the target. This depends on how often it's called, so it's only
+    /// meant for one-off nudges.
     pub fn update(&mut self, e: T) {
         self.value = self.value.lerp(self.tv, e);
     }
@@ -135,6 +396,164 @@ where
         self.tv = e;
         self.update(t);
     }
+
+    /// Jump straight to the target.
+    pub fn snap(&mut self) {
+        self.value = self.tv;
+        self.velocity = T::zero();
+        self.tween = None;
+    }
+
+    /// Exponential smoothing towards the target: after `time_constant` milliseconds `value` has
+    /// covered about 63% of the distance, and 95% after three times that.
+    ///
+    /// ```
+    /// # use cactuswar_client::util::Scalar;
+    /// let mut once = Scalar::new(0_f64);
+    /// once.tv = 1.;
+    /// once.smooth(100., 50.);
+    ///
+    /// let mut often = Scalar::new(0_f64);
+    /// often.tv = 1.;
+    /// for _ in 0..10 {
+    ///     often.smooth(10., 50.);
+    /// }
+    /// assert!((once.value - often.value).abs() < 1e-9);
+    /// ```
+    pub fn smooth(&mut self, dt: T, time_constant: T) {
+        if time_constant <= T::zero() {
+            self.value = self.tv;
+            return;
+        }
+        let t = T::one() - (-dt / time_constant).exp();
+        self.value = self.value.lerp(self.tv, t);
+    }
+
+    /// A spring pulling `value` towards the target. `period` is how long (in milliseconds) a
+    /// swing would take without damping, and `damping` is the damping ratio: 1 settles as fast
+    /// as possible without overshooting, less than that bounces.
+    ///
+    /// ```
+    /// # use cactuswar_client::util::Scalar;
+    /// let mut zoom = Scalar::new(1_f64);
+    /// zoom.tv = 2.;
+    /// for _ in 0..120 {
+    ///     zoom.spring(1000. / 60., 500., 1.);
+    /// }
+    /// assert!((zoom.value - 2.).abs() < 1e-3);
+    /// ```
+    pub fn spring(&mut self, dt: T, period: T, damping: T) {
+        if period <= T::zero() {
+            self.snap();
+            return;
+        }
+        let two = T::one() + T::one();
+        let frequency = two * T::from(std::f64::consts::PI).unwrap() / period;
+        // implicit Euler, which stays stable however long `dt` gets
+        let pull = frequency * frequency;
+        self.velocity = (self.velocity + dt * pull * (self.tv - self.value))
+            / (T::one() + two * damping * frequency * dt + pull * dt * dt);
+        self.value = self.value + self.velocity * dt;
+    }
+
+    /// Start a tween from the current value to `target` that takes `duration` milliseconds.
+    /// Nothing changes if it's already heading for `target`, so this can be called every frame.
+    pub fn tween_to(&mut self, target: T, duration: T, easing: Easing) {
+        if self.tv == target {
+            return;
+        }
+        self.tv = target;
+        self.tween = Some(Tween {
+            from: self.value,
+            elapsed: T::zero(),
+            duration,
+            easing,
+        });
+    }
+
+    /// Advance the tween by `dt` milliseconds. Without a tween the value jumps to the target.
+    ///
+    /// ```
+    /// # use cactuswar_client::util::{Easing, Scalar};
+    /// let mut fade = Scalar::new(0.);
+    /// fade.tween_to(1., 400., Easing::Linear);
+    /// fade.tween(100.);
+    /// assert_eq!(fade.value, 0.25);
+    /// fade.tween(1000.);
+    /// assert_eq!(fade.value, 1.);
+    /// assert!(!fade.is_tweening());
+    /// ```
+    pub fn tween(&mut self, dt: T) {
+        let mut tween = match self.tween {
+            Some(tween) => tween,
+            None => {
+                self.value = self.tv;
+                return;
+            }
+        };
+        tween.elapsed = tween.elapsed + dt;
+        if tween.elapsed >= tween.duration {
+            self.value = self.tv;
+            self.tween = None;
+            return;
+        }
+        let progress = (tween.elapsed / tween.duration).to_f64().unwrap_or(1.);
+        let eased = T::from(tween.easing.apply(progress)).unwrap();
+        self.value = tween.from.lerp(self.tv, eased);
+        self.tween = Some(tween);
+    }
+
+    pub fn is_tweening(&self) -> bool {
+        self.tween.is_some()
+    }
+}
+
+/// A random number in `[0, 1)` for visual effects.
+#[cfg(target_arch = "wasm32")]
+pub fn random() -> f64 {
+    Math::random()
+}
+
+/// Renders outside the browser (see `raster`) have to be reproducible, so effects always get 0
+/// there.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn random() -> f64 {
+    0.
+}
+
+/// A small xorshift random number generator.
+///
+/// Native code (bots, tools, tests) can't reach `js_sys::Math::random`, and tests want
+/// reproducible sequences anyway.
+#[derive(Debug, Copy, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift gets stuck on zero
+        Rng {
+            state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// A random number in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A random number in `[min, max)`.
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.next_f64()
+    }
 }
 
 use crate::protocol::Protocol;
@@ -142,3 +561,52 @@ use crate::protocol::Protocol;
 pub fn talk<M: Protocol>(ws: &web_sys::WebSocket, data: &M) {
     ws.send_with_u8_array(data.encode().cursor.get_ref().as_slice());
 }
+
+/// Offer `data` to the user as a file download.
+pub fn download(data: &[u8], file_name: &str, mime_type: &str) {
+    use wasm_bindgen::JsCast;
+
+    let parts = js_sys::Array::new();
+    parts.push(&js_sys::Uint8Array::from(data));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .expect("Failed to create blob");
+    let url = web_sys::Url::create_object_url_with_blob(&blob).expect("Failed to create url");
+
+    let anchor = crate::document()
+        .create_element("a")
+        .unwrap()
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .unwrap();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    // the download may not have started yet, and revoking the url now can cancel it
+    let revoke = wasm_bindgen::closure::Closure::once_into_js(move || {
+        web_sys::Url::revoke_object_url(&url).ok();
+    });
+    crate::window()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 10_000)
+        .ok();
+}
+
+/// A high resolution timestamp in milliseconds, for measuring how long things take.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    web_sys::window()
+        .and_then(|window| window.performance())
+        .map(|performance| performance.now())
+        .unwrap_or(0.)
+}
+
+/// A high resolution timestamp in milliseconds, for measuring how long things take.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    static START: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    START
+        .get_or_init(std::time::Instant::now)
+        .elapsed()
+        .as_secs_f64()
+        * 1000.
+}
@@ -0,0 +1,489 @@
+//! Widgets for in-game menus, drawn on the composite canvas with the HUD.
+//!
+//! The toolkit is immediate-mode: every frame, a menu starts a `Frame` and calls its `button`,
+//! `toggle`, `slider`, `list` and `tabs` with an id and where the widget goes, and each call
+//! draws the widget and says whether it was used. The little that lasts between frames is kept
+//! in the `Ui`: where the widgets went last frame, which the mouse handlers hit-test against,
+//! the widget the mouse is pressing, the one with keyboard focus, and the input that came in
+//! since.
+//!
+//! Tab and Shift+Tab move the focus through the widgets in the order they were drawn, Enter and
+//! Space press the focused one, and the arrow keys move sliders, lists and tabs.
+
+use crate::color::Color;
+use crate::draw::*;
+use crate::geometry::Aabb;
+use crate::renderer::Renderer;
+use crate::theme::HudColors;
+use crate::util::Vector2;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+const FONT: &str = "36px \"Fira Sans\"";
+/// How far text is from the left edge of its widget.
+const PADDING: f64 = 20.;
+
+/// A widget, by the name its menu gives it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Id(u64);
+
+impl Id {
+    fn new(name: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        Id(hasher.finish())
+    }
+}
+
+/// The keys widgets take.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    /// Tab, to focus the next widget.
+    Next,
+    /// Shift+Tab, to focus the one before.
+    Previous,
+    /// Enter or Space.
+    Press,
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Key {
+    /// The key with a DOM `keyCode`, if widgets take it.
+    pub fn from_code(key_code: u32, shift: bool) -> Option<Self> {
+        match key_code {
+            9 if shift => Some(Key::Previous),
+            9 => Some(Key::Next),
+            13 | 32 => Some(Key::Press),
+            37 => Some(Key::Left),
+            38 => Some(Key::Up),
+            39 => Some(Key::Right),
+            40 => Some(Key::Down),
+            _ => None,
+        }
+    }
+}
+
+/// What lasts of the widgets between frames.
+pub struct Ui {
+    /// Pixels per design unit, as of the last frame.
+    scale: f64,
+    /// Where the mouse is, in design units.
+    pointer: Vector2<f64>,
+    /// The widgets drawn last frame and where, in order.
+    widgets: Vec<(Id, Aabb)>,
+    /// The panels drawn last frame, which take clicks that miss their widgets.
+    panels: Vec<Aabb>,
+    /// The widget the mouse went down on, while it's held.
+    pressed: Option<Id>,
+    /// The widget clicked since the last frame.
+    clicked: Option<Id>,
+    focus: Option<Id>,
+    /// Keys for the focused widget since the last frame.
+    keys: Vec<Key>,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Self {
+            scale: 1.,
+            pointer: Vector2::zero(),
+            widgets: Vec::new(),
+            panels: Vec::new(),
+            pressed: None,
+            clicked: None,
+            focus: None,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Whether anything was drawn last frame, so that the mouse and keyboard might be ours.
+    pub fn is_shown(&self) -> bool {
+        !self.widgets.is_empty() || !self.panels.is_empty()
+    }
+
+    /// Whether the widget called `name` has keyboard focus.
+    pub fn is_focused(&self, name: &str) -> bool {
+        self.focus == Some(Id::new(name))
+    }
+
+    /// The widget under the mouse, going by where they were drawn last frame. Later widgets
+    /// are on top.
+    fn hit(&self) -> Option<Id> {
+        self.widgets
+            .iter()
+            .rev()
+            .find(|(_, rect)| rect.contains(self.pointer))
+            .map(|(id, _)| *id)
+    }
+
+    /// The mouse moved to (`x`, `y`), in pixels.
+    pub fn pointer_move(&mut self, x: f64, y: f64) {
+        self.pointer = Vector2::new(x, y) / self.scale;
+    }
+
+    /// The mouse went down at (`x`, `y`), in pixels. Returns whether it went down on a widget or
+    /// a panel, in which case the game shouldn't see it.
+    pub fn pointer_down(&mut self, x: f64, y: f64) -> bool {
+        self.pointer_move(x, y);
+        match self.hit() {
+            Some(id) => {
+                self.pressed = Some(id);
+                self.focus = Some(id);
+                true
+            }
+            None => {
+                self.focus = None;
+                self.panels.iter().any(|panel| panel.contains(self.pointer))
+            }
+        }
+    }
+
+    /// The mouse went up. Returns whether it went down on a widget, which is clicked if the
+    /// mouse is still over it.
+    pub fn pointer_up(&mut self) -> bool {
+        match self.pressed.take() {
+            Some(id) => {
+                if self.hit() == Some(id) {
+                    self.clicked = Some(id);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// A key went down. Returns whether a widget took it: Tab and Shift+Tab whenever there are
+    /// widgets, the rest when one of them has focus.
+    pub fn key(&mut self, key: Key) -> bool {
+        if self.widgets.is_empty() {
+            return false;
+        }
+        let count = self.widgets.len();
+        let current = self
+            .focus
+            .and_then(|focus| self.widgets.iter().position(|(id, _)| *id == focus));
+        let next = match (key, current) {
+            (Key::Next, Some(index)) => (index + 1) % count,
+            (Key::Next, None) => 0,
+            (Key::Previous, Some(index)) => (index + count - 1) % count,
+            (Key::Previous, None) => count - 1,
+            _ if current.is_some() => {
+                self.keys.push(key);
+                return true;
+            }
+            _ => return false,
+        };
+        self.focus = Some(self.widgets[next].0);
+        true
+    }
+
+    /// Start drawing widgets on `ctx`, in `colors`, `scale` pixels to the design unit. Widgets
+    /// that aren't drawn again by the time the frame is dropped lose focus.
+    pub fn frame<'a>(
+        &'a mut self,
+        ctx: &'a mut dyn Renderer,
+        colors: &HudColors,
+        scale: f64,
+    ) -> Frame<'a> {
+        self.widgets.clear();
+        self.panels.clear();
+        self.pointer = self.pointer * self.scale / scale;
+        self.scale = scale;
+        Frame {
+            clicked: self.clicked.take(),
+            keys: std::mem::take(&mut self.keys),
+            ui: self,
+            ctx,
+            colors: colors.clone(),
+        }
+    }
+}
+
+/// What happened to a widget since the last frame.
+struct Interaction {
+    hovered: bool,
+    pressed: bool,
+    focused: bool,
+    clicked: bool,
+    /// The keys pressed while it had focus.
+    keys: Vec<Key>,
+}
+
+impl Interaction {
+    fn pressed_key(&self, key: Key) -> bool {
+        self.keys.contains(&key)
+    }
+
+    /// `selected` moved by the arrow keys, `back` towards 0 and `forward` towards `count`.
+    fn step(&self, selected: usize, count: usize, back: Key, forward: Key) -> usize {
+        self.keys.iter().fold(selected, |selected, key| match *key {
+            key if key == back => selected.saturating_sub(1).min(count - 1),
+            key if key == forward && selected >= count => 0,
+            key if key == forward => (selected + 1).min(count - 1),
+            _ => selected,
+        })
+    }
+}
+
+/// The widgets of a frame, drawn as they're declared.
+pub struct Frame<'a> {
+    ui: &'a mut Ui,
+    ctx: &'a mut dyn Renderer,
+    colors: HudColors,
+    clicked: Option<Id>,
+    keys: Vec<Key>,
+}
+
+impl<'a> Frame<'a> {
+    /// Where the mouse is, in design units.
+    pub fn pointer(&self) -> Vector2<f64> {
+        self.ui.pointer
+    }
+
+    fn interact(&mut self, name: &str, rect: Aabb) -> Interaction {
+        let id = Id::new(name);
+        self.ui.widgets.push((id, rect));
+        let focused = self.ui.focus == Some(id);
+        Interaction {
+            hovered: rect.contains(self.ui.pointer),
+            pressed: self.ui.pressed == Some(id),
+            focused,
+            clicked: self.clicked == Some(id),
+            keys: if focused {
+                self.keys.clone()
+            } else {
+                Vec::new()
+            },
+        }
+    }
+
+    /// Fill `rect` in `color`, lighter under the mouse and darker while pressed.
+    fn fill(&mut self, rect: &Aabb, color: &Color, hovered: bool, pressed: bool) {
+        let color = if pressed {
+            color.shade(0.25)
+        } else if hovered {
+            color.tint(0.15)
+        } else {
+            color.clone()
+        };
+        self.ctx.set_fill_color(color.css());
+        let size = rect.size();
+        self.ctx.fill_rect(rect.min.x, rect.min.y, size.x, size.y);
+    }
+
+    /// Outline `rect` if it has keyboard focus.
+    fn focus_ring(&mut self, rect: &Aabb, focused: bool) {
+        if !focused {
+            return;
+        }
+        self.ctx.save();
+        self.ctx.set_stroke_color(self.colors.focus.css());
+        self.ctx.set_line_width(6.);
+        let size = rect.size();
+        self.ctx.stroke_rect(rect.min.x, rect.min.y, size.x, size.y);
+        self.ctx.restore();
+    }
+
+    /// Write `text` with its left edge at `x`, or centered on `x`, and its middle at `y`.
+    fn text(&mut self, text: &str, x: f64, y: f64, centered: bool) {
+        self.ctx.save();
+        self.ctx.set_font(FONT);
+        self.ctx.set_fill_color(self.colors.text.css());
+        self.ctx.set_stroke_color(self.colors.text_outline.css());
+        self.ctx.set_line_width(6.);
+        let x = if centered {
+            x - self.ctx.measure_text(text) / 2.
+        } else {
+            x
+        };
+        self.ctx.stroke_text(text, x, y + 13.);
+        self.ctx.fill_text(text, x, y + 13.);
+        self.ctx.restore();
+    }
+
+    /// Draw a panel for widgets to go on. Clicks on it don't reach the game.
+    pub fn panel(&mut self, rect: Aabb) {
+        let size = rect.size();
+        draw_rect_no_correction(
+            self.ctx,
+            rect.min.x,
+            rect.min.y,
+            size.x,
+            size.y,
+            0.,
+            &self.colors.panel,
+        );
+        self.ui.panels.push(rect);
+    }
+
+    /// Write `text` in `rect`, which can't be interacted with.
+    pub fn label(&mut self, rect: Aabb, text: &str) {
+        self.text(text, rect.min.x + PADDING, rect.center().y, false);
+    }
+
+    /// Draw a button, and return whether it was pressed.
+    pub fn button(&mut self, name: &str, rect: Aabb, text: &str) -> bool {
+        let interaction = self.interact(name, rect);
+        let control = self.colors.control.clone();
+        self.fill(&rect, &control, interaction.hovered, interaction.pressed);
+        self.text(text, rect.center().x, rect.center().y, true);
+        self.focus_ring(&rect, interaction.focused);
+        interaction.clicked || interaction.pressed_key(Key::Press)
+    }
+
+    /// Draw a switch for `value`, and return whether it was flipped. Left turns it off and right
+    /// turns it on.
+    pub fn toggle(&mut self, name: &str, rect: Aabb, text: &str, value: &mut bool) -> bool {
+        let interaction = self.interact(name, rect);
+        let old = *value;
+        if interaction.clicked || interaction.pressed_key(Key::Press) {
+            *value = !*value;
+        }
+        if interaction.pressed_key(Key::Left) {
+            *value = false;
+        }
+        if interaction.pressed_key(Key::Right) {
+            *value = true;
+        }
+
+        let control = self.colors.control.clone();
+        self.fill(&rect, &control, interaction.hovered, interaction.pressed);
+        self.text(text, rect.min.x + PADDING, rect.center().y, false);
+        let (left, right, y) = (rect.max.x - 110., rect.max.x - 50., rect.center().y);
+        draw_bar(self.ctx, left, right, y, 40., &self.colors.bar_background);
+        if *value {
+            draw_bar(self.ctx, left, right, y, 30., &self.colors.progress);
+        }
+        let knob = if *value { right } else { left };
+        draw_circle(self.ctx, knob, y, 16., &self.colors.text);
+        self.focus_ring(&rect, interaction.focused);
+        *value != old
+    }
+
+    /// Draw a slider for `value`, from `min` to `max` in steps of `step`, and return whether it
+    /// moved. It follows the mouse while pressed, and left and right move it by a step.
+    #[allow(clippy::too_many_arguments)]
+    pub fn slider(
+        &mut self,
+        name: &str,
+        rect: Aabb,
+        text: &str,
+        value: &mut f64,
+        min: f64,
+        max: f64,
+        step: f64,
+    ) -> bool {
+        let interaction = self.interact(name, rect);
+        let old = *value;
+        let (left, right, y) = (rect.min.x + 40., rect.max.x - 40., rect.max.y - 30.);
+        if interaction.pressed {
+            let along = ((self.ui.pointer.x - left) / (right - left)).clamp(0., 1.);
+            *value = min + along * (max - min);
+        }
+        let mut moved = interaction.pressed;
+        for key in &interaction.keys {
+            match key {
+                Key::Left => *value -= step,
+                Key::Right => *value += step,
+                _ => continue,
+            }
+            moved = true;
+        }
+        // values set elsewhere stay as they are until the slider is moved
+        if moved {
+            *value = (min + ((*value - min) / step).round() * step).clamp(min, max);
+        }
+
+        let control = self.colors.control.clone();
+        self.fill(&rect, &control, interaction.hovered, interaction.pressed);
+        self.text(text, rect.min.x + PADDING, rect.min.y + 35., false);
+        let knob = left + (*value - min) / (max - min) * (right - left);
+        draw_bar(self.ctx, left, right, y, 20., &self.colors.bar_background);
+        draw_bar(self.ctx, left, knob, y, 12., &self.colors.progress);
+        draw_circle(self.ctx, knob, y, 18., &self.colors.text);
+        self.focus_ring(&rect, interaction.focused);
+        *value != old
+    }
+
+    /// Draw `items` in rows, with the `selected` one highlighted, and return whether another
+    /// was picked. Up and down move the selection.
+    pub fn list(&mut self, name: &str, rect: Aabb, items: &[&str], selected: &mut usize) -> bool {
+        let interaction = self.interact(name, rect);
+        let old = *selected;
+        let row = rect.size().y / items.len() as f64;
+        let under = ((self.ui.pointer.y - rect.min.y) / row) as usize;
+        if interaction.clicked && under < items.len() {
+            *selected = under;
+        }
+        *selected = interaction.step(*selected, items.len(), Key::Up, Key::Down);
+
+        let control = self.colors.control.clone();
+        self.fill(&rect, &control, false, false);
+        for (index, item) in items.iter().enumerate() {
+            let top = rect.min.y + index as f64 * row;
+            let cell = Aabb::new(
+                Vector2::new(rect.min.x, top),
+                Vector2::new(rect.max.x, top + row),
+            );
+            if index == *selected {
+                let progress = self.colors.progress.with_alpha(0.6);
+                self.fill(&cell, &progress, false, false);
+            } else if interaction.hovered && index == under {
+                self.fill(&cell, &control, true, interaction.pressed);
+            }
+            self.text(item, cell.min.x + PADDING, cell.center().y, false);
+        }
+        self.focus_ring(&rect, interaction.focused);
+        *selected != old
+    }
+
+    /// Draw a row of tabs with the `selected` one highlighted, and return whether another was
+    /// picked. Left and right move the selection.
+    pub fn tabs(&mut self, name: &str, rect: Aabb, labels: &[&str], selected: &mut usize) -> bool {
+        let interaction = self.interact(name, rect);
+        let old = *selected;
+        let tab = rect.size().x / labels.len() as f64;
+        let under = ((self.ui.pointer.x - rect.min.x) / tab) as usize;
+        if interaction.clicked && under < labels.len() {
+            *selected = under;
+        }
+        *selected = interaction.step(*selected, labels.len(), Key::Left, Key::Right);
+
+        let control = self.colors.control.clone();
+        for (index, label) in labels.iter().enumerate() {
+            let left = rect.min.x + index as f64 * tab;
+            let cell = Aabb::new(
+                Vector2::new(left, rect.min.y),
+                Vector2::new(left + tab, rect.max.y),
+            );
+            let hovered = interaction.hovered && index == under;
+            if index == *selected {
+                let progress = self.colors.progress.with_alpha(0.6);
+                self.fill(&cell, &progress, hovered, false);
+            } else {
+                self.fill(&cell, &control, hovered, hovered && interaction.pressed);
+            }
+            self.text(label, cell.center().x, cell.center().y, true);
+        }
+        self.focus_ring(&rect, interaction.focused);
+        *selected != old
+    }
+}
+
+impl<'a> Drop for Frame<'a> {
+    fn drop(&mut self) {
+        let widgets = &self.ui.widgets;
+        let drawn = |id: Option<Id>| id.filter(|id| widgets.iter().any(|(other, _)| other == id));
+        self.ui.focus = drawn(self.ui.focus);
+        self.ui.pressed = drawn(self.ui.pressed);
+    }
+}
@@ -2,16 +2,50 @@ use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(module = "/wrapper.js")]
 extern "C" {
-    pub fn pSBC(p: f64, c0: &str, c1: bool, l: bool) -> JsValue;
+    pub fn query_name() -> String;
+    pub fn query_server_url() -> String;
+    pub fn query_replay() -> Option<js_sys::Uint8Array>;
+    pub fn query_netsim() -> String;
+    pub fn query_lights() -> Option<u32>;
+    pub fn query_theme() -> String;
+    pub fn query_hitflash() -> String;
+    pub fn query_ui_scale() -> Option<f64>;
+    pub fn load_settings(key: &str) -> Option<String>;
+    pub fn save_settings(key: &str, settings: &str);
+
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen(module = "/wrapper.js")]
+extern "C" {
     pub fn log(s: String);
 
     pub fn info_log(s: String);
     pub fn error_log(s: String);
     pub fn success_log(s: String);
+}
 
-    pub fn query_name() -> String;
-    pub fn query_server_url() -> String;
+// Native builds (the bot client, tools and tests) have no console to talk to, so the log
+// functions fall back to stdout/stderr.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn log(s: String) {
+    println!("{}", s);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn info_log(s: String) {
+    println!("[INFO] {}", s);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn error_log(s: String) {
+    eprintln!("[ERR] {}", s);
+}
 
+#[cfg(not(target_arch = "wasm32"))]
+pub fn success_log(s: String) {
+    println!("[SUCCESS] {}", s);
 }
 
 #[macro_export]
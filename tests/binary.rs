@@ -0,0 +1,60 @@
+use cactuswar_client::binary::StreamPeerBuffer;
+
+fn buffer(data: &[u8]) -> StreamPeerBuffer {
+    let mut buf = StreamPeerBuffer::new();
+    buf.set_data_array(data.to_vec());
+    buf
+}
+
+#[test]
+fn varints_round_trip() {
+    let values = [0, 1, 127, 128, 300, 1_000_000, i32::MAX, -1, i32::MIN];
+    let mut buf = StreamPeerBuffer::new();
+    for value in values {
+        buf.put_varint_mc(value);
+    }
+    let mut buf = buffer(buf.cursor.get_ref());
+    for value in values {
+        assert_eq!(buf.get_varint_mc(), value);
+    }
+    assert_eq!(buf.remaining(), 0);
+    assert_eq!(buf.error, None);
+}
+
+#[test]
+fn overlong_varints_are_errors() {
+    let mut buf = buffer(&[1, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0]);
+    buf.get_u8();
+    buf.get_varint_mc();
+    let error = buf.error.unwrap();
+    assert_eq!(error.offset, 1);
+    assert!(!error.truncated);
+    assert_eq!(error.to_string(), "varint is longer than 5 bytes at byte 1");
+
+    let mut buf = buffer(&[0xff; 11]);
+    buf.get_varlong();
+    assert!(!buf.error.unwrap().truncated);
+}
+
+#[test]
+fn varints_cut_short_are_truncated() {
+    let mut buf = buffer(&[0x80, 0x80]);
+    buf.get_varint_mc();
+    let error = buf.error.unwrap();
+    assert_eq!(error.offset, 2);
+    assert!(error.truncated);
+}
+
+#[test]
+fn varint_strings_longer_than_the_data_are_truncated() {
+    let mut buf = StreamPeerBuffer::new();
+    buf.put_varint_utf8(String::from("cactus"));
+    assert_eq!(buffer(buf.cursor.get_ref()).get_varint_utf8(), "cactus");
+
+    // a length of 2^31 - 1
+    let mut buf = buffer(&[0xff, 0xff, 0xff, 0xff, 0x07, b'a']);
+    buf.get_varint_utf8();
+    let error = buf.error.unwrap();
+    assert_eq!(error.offset, 6);
+    assert!(error.truncated);
+}
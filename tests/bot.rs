@@ -0,0 +1,130 @@
+use cactuswar_client::binary::StreamPeerBuffer;
+use cactuswar_client::bot::{self, BotView, Chase, Farm, Policy, Wander};
+use cactuswar_client::protocol::{self, Census, Entity, Protocol, ShapePacket, TankPacket};
+use cactuswar_client::util::Vector2;
+
+fn tank(id: u32, x: i16, y: i16) -> Entity {
+    Entity::Tank(TankPacket {
+        id,
+        position: Vector2 { x, y },
+        rotation: 0.,
+        velocity: Vector2 { x: 0, y: 0 },
+        mockup: 0,
+        health: 1.,
+        radius: 50,
+        name: String::new(),
+        message: String::new(),
+    })
+}
+
+fn shape(id: u32, x: i16, y: i16) -> Entity {
+    Entity::Shape(ShapePacket {
+        id,
+        position: Vector2 { x, y },
+        health: 1.,
+        radius: 80,
+    })
+}
+
+/// A view of a 2000 unit arena from the middle of it, with `entities` in it.
+fn view(entities: Vec<Entity>) -> BotView {
+    let mut view = BotView::new();
+    view.id = 1;
+    view.arena_size = 2000;
+    view.position = Vector2 { x: 1000., y: 1000. };
+    view.census = Some(Census::new(2000, 1., entities));
+    view
+}
+
+#[test]
+fn views_follow_the_packets() {
+    let mut view = BotView::new();
+    let mut buf = StreamPeerBuffer::new();
+    buf.put_u8(protocol::Packet::Handshake as u8);
+    buf.put_u32(7);
+    buf.put_u8(0);
+    assert!(matches!(
+        view.handle(buf.cursor.into_inner()),
+        Some(protocol::Packet::Handshake)
+    ));
+    assert_eq!(view.id, 7);
+
+    let census = Census::new(3000, 4.5, vec![tank(7, 120, -40)]);
+    view.handle(census.encode().cursor.into_inner());
+    assert_eq!(view.arena_size, 3000);
+    assert_eq!(view.level, 4.5);
+    assert_eq!(view.position, Vector2 { x: 120., y: -40. });
+    // ourselves
+    assert_eq!(view.tanks().count(), 0);
+
+    assert!(view.alive);
+    view.handle(vec![protocol::Packet::Death as u8, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(!view.alive);
+    assert!(view.handle(vec![99]).is_none());
+}
+
+#[test]
+fn policies_are_found_by_name() {
+    for name in ["wander", "chase", "farm"] {
+        assert!(bot::policy_from_name(name, 1).is_some());
+    }
+    assert!(bot::policy_from_name("camp", 1).is_none());
+}
+
+#[test]
+fn wanderers_turn_back_from_the_edge() {
+    let mut view = view(Vec::new());
+    view.position = Vector2 { x: 50., y: 50. };
+    let input = Wander::new(1).next_input(&view);
+    assert!(input.S && input.D);
+    assert!(!input.W && !input.A);
+}
+
+#[test]
+fn chasers_hunt_the_nearest_tank() {
+    let view = view(vec![
+        tank(1, 1000, 1000),
+        tank(2, 1000, 1600),
+        tank(3, 100, 100),
+    ]);
+    let input = Chase::new(1).next_input(&view);
+    assert_eq!(input.mouse_position, Vector2 { x: 1000, y: 1600 });
+    assert!(input.mouse_down);
+    assert!(input.S && !input.W && !input.A && !input.D);
+
+    // close enough to stop and shoot
+    let view = self::view(vec![tank(2, 1200, 1000)]);
+    let input = Chase::new(1).next_input(&view);
+    assert_eq!(input.mouse_position, Vector2 { x: 1200, y: 1000 });
+    assert!(!input.W && !input.A && !input.S && !input.D);
+}
+
+#[test]
+fn farmers_shoot_the_nearest_cactus() {
+    let view = view(vec![
+        tank(2, 1100, 1000),
+        shape(3, 100, 1000),
+        shape(4, 2000, 2000),
+    ]);
+    let input = Farm::new(1).next_input(&view);
+    assert_eq!(input.mouse_position, Vector2 { x: 100, y: 1000 });
+    assert!(input.mouse_down);
+    assert!(input.A && !input.D);
+}
+
+#[test]
+fn rates_that_cant_be_kept_are_rejected() {
+    for rate in ["nan", "inf", "0", "-1", "1001"] {
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_bot"))
+            .args(["--rate", rate])
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "{}", rate);
+        let errors = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            errors.starts_with("--rate must be above 0 and at most 1000"),
+            "{}",
+            errors
+        );
+    }
+}
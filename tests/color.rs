@@ -0,0 +1,289 @@
+use cactuswar_client::color::Color;
+
+// pSBC(p, color, false, true), from the pSBC in wrapper.js that `Color` replaced, run in node.
+// A negative `p` shades, a positive one tints.
+const SHADES: &[(&str, f64, &str)] = &[
+    ("#3ea832", -1.0, "#000000"),
+    ("#3ea832", -0.4, "#25651e"),
+    ("#3ea832", -0.15, "#358f2b"),
+    ("#3ea832", 0.0, "#3ea832"),
+    ("#3ea832", 0.25, "#6ebe65"),
+    ("#3ea832", 0.5, "#9fd499"),
+    ("#3ea832", 1.0, "#ffffff"),
+    ("#f28900", -1.0, "#000000"),
+    ("#f28900", -0.4, "#915200"),
+    ("#f28900", -0.15, "#ce7400"),
+    ("#f28900", 0.0, "#f28900"),
+    ("#f28900", 0.25, "#f5a740"),
+    ("#f28900", 0.5, "#f9c480"),
+    ("#f28900", 1.0, "#ffffff"),
+    ("#00e6f2", -1.0, "#000000"),
+    ("#00e6f2", -0.4, "#008a91"),
+    ("#00e6f2", -0.15, "#00c4ce"),
+    ("#00e6f2", 0.0, "#00e6f2"),
+    ("#00e6f2", 0.25, "#40ecf5"),
+    ("#00e6f2", 0.5, "#80f3f9"),
+    ("#00e6f2", 1.0, "#ffffff"),
+    ("#780000", -1.0, "#000000"),
+    ("#780000", -0.4, "#480000"),
+    ("#780000", -0.15, "#660000"),
+    ("#780000", 0.0, "#780000"),
+    ("#780000", 0.25, "#9a4040"),
+    ("#780000", 0.5, "#bc8080"),
+    ("#780000", 1.0, "#ffffff"),
+    ("#8a4900", -1.0, "#000000"),
+    ("#8a4900", -0.4, "#532c00"),
+    ("#8a4900", -0.15, "#753e00"),
+    ("#8a4900", 0.0, "#8a4900"),
+    ("#8a4900", 0.25, "#a77740"),
+    ("#8a4900", 0.5, "#c5a480"),
+    ("#8a4900", 1.0, "#ffffff"),
+    ("#fff", -1.0, "#000000"),
+    ("#fff", -0.4, "#999999"),
+    ("#fff", -0.15, "#d9d9d9"),
+    ("#fff", 0.0, "#ffffff"),
+    ("#fff", 0.25, "#ffffff"),
+    ("#fff", 0.5, "#ffffff"),
+    ("#fff", 1.0, "#ffffff"),
+    ("#0f08", -1.0, "#00000088"),
+    ("#0f08", -0.4, "#00990088"),
+    ("#0f08", -0.15, "#00d90088"),
+    ("#0f08", 0.0, "#00ff0088"),
+    ("#0f08", 0.25, "#40ff4088"),
+    ("#0f08", 0.5, "#80ff8088"),
+    ("#0f08", 1.0, "#ffffff88"),
+    ("#121212aa", -1.0, "#000000aa"),
+    ("#121212aa", -0.4, "#0b0b0baa"),
+    ("#121212aa", -0.15, "#0f0f0faa"),
+    ("#121212aa", 0.0, "#121212aa"),
+    ("#121212aa", 0.25, "#4d4d4daa"),
+    ("#121212aa", 0.5, "#898989aa"),
+    ("#121212aa", 1.0, "#ffffffaa"),
+    ("#00FFFF", -1.0, "#000000"),
+    ("#00FFFF", -0.4, "#009999"),
+    ("#00FFFF", -0.15, "#00d9d9"),
+    ("#00FFFF", 0.0, "#00ffff"),
+    ("#00FFFF", 0.25, "#40ffff"),
+    ("#00FFFF", 0.5, "#80ffff"),
+    ("#00FFFF", 1.0, "#ffffff"),
+    ("rgb(50,50,50)", -1.0, "rgb(0,0,0)"),
+    ("rgb(50,50,50)", -0.4, "rgb(30,30,30)"),
+    ("rgb(50,50,50)", -0.15, "rgb(43,43,43)"),
+    ("rgb(50,50,50)", 0.0, "rgb(50,50,50)"),
+    ("rgb(50,50,50)", 0.25, "rgb(101,101,101)"),
+    ("rgb(50,50,50)", 0.5, "rgb(153,153,153)"),
+    ("rgb(50,50,50)", 1.0, "rgb(255,255,255)"),
+    ("rgba(50, 50, 50, 1.0)", -1.0, "rgba(0,0,0,1)"),
+    ("rgba(50, 50, 50, 1.0)", -0.4, "rgba(30,30,30,1)"),
+    ("rgba(50, 50, 50, 1.0)", -0.15, "rgba(43,43,43,1)"),
+    ("rgba(50, 50, 50, 1.0)", 0.0, "rgba(50,50,50,1)"),
+    ("rgba(50, 50, 50, 1.0)", 0.25, "rgba(101,101,101,1)"),
+    ("rgba(50, 50, 50, 1.0)", 0.5, "rgba(153,153,153,1)"),
+    ("rgba(50, 50, 50, 1.0)", 1.0, "rgba(255,255,255,1)"),
+    ("rgba(252, 250, 157, 0.2)", -1.0, "rgba(0,0,0,0.2)"),
+    ("rgba(252, 250, 157, 0.2)", -0.4, "rgba(151,150,94,0.2)"),
+    ("rgba(252, 250, 157, 0.2)", -0.15, "rgba(214,213,133,0.2)"),
+    ("rgba(252, 250, 157, 0.2)", 0.0, "rgba(252,250,157,0.2)"),
+    ("rgba(252, 250, 157, 0.2)", 0.25, "rgba(253,251,182,0.2)"),
+    ("rgba(252, 250, 157, 0.2)", 0.5, "rgba(254,253,206,0.2)"),
+    ("rgba(252, 250, 157, 0.2)", 1.0, "rgba(255,255,255,0.2)"),
+    ("rgba(20, 20, 20, 1.0)", -1.0, "rgba(0,0,0,1)"),
+    ("rgba(20, 20, 20, 1.0)", -0.4, "rgba(12,12,12,1)"),
+    ("rgba(20, 20, 20, 1.0)", -0.15, "rgba(17,17,17,1)"),
+    ("rgba(20, 20, 20, 1.0)", 0.0, "rgba(20,20,20,1)"),
+    ("rgba(20, 20, 20, 1.0)", 0.25, "rgba(79,79,79,1)"),
+    ("rgba(20, 20, 20, 1.0)", 0.5, "rgba(138,138,138,1)"),
+    ("rgba(20, 20, 20, 1.0)", 1.0, "rgba(255,255,255,1)"),
+    ("rgba(140, 140, 140, 1.0)", -1.0, "rgba(0,0,0,1)"),
+    ("rgba(140, 140, 140, 1.0)", -0.4, "rgba(84,84,84,1)"),
+    ("rgba(140, 140, 140, 1.0)", -0.15, "rgba(119,119,119,1)"),
+    ("rgba(140, 140, 140, 1.0)", 0.0, "rgba(140,140,140,1)"),
+    ("rgba(140, 140, 140, 1.0)", 0.25, "rgba(169,169,169,1)"),
+    ("rgba(140, 140, 140, 1.0)", 0.5, "rgba(198,198,198,1)"),
+    ("rgba(140, 140, 140, 1.0)", 1.0, "rgba(255,255,255,1)"),
+    ("rgb(255, 0, 128)", -1.0, "rgb(0,0,0)"),
+    ("rgb(255, 0, 128)", -0.4, "rgb(153,0,77)"),
+    ("rgb(255, 0, 128)", -0.15, "rgb(217,0,109)"),
+    ("rgb(255, 0, 128)", 0.0, "rgb(255,0,128)"),
+    ("rgb(255, 0, 128)", 0.25, "rgb(255,64,160)"),
+    ("rgb(255, 0, 128)", 0.5, "rgb(255,128,192)"),
+    ("rgb(255, 0, 128)", 1.0, "rgb(255,255,255)"),
+    ("rgba(0,0,0,0)", -1.0, "rgba(0,0,0,0)"),
+    ("rgba(0,0,0,0)", -0.4, "rgba(0,0,0,0)"),
+    ("rgba(0,0,0,0)", -0.15, "rgba(0,0,0,0)"),
+    ("rgba(0,0,0,0)", 0.0, "rgba(0,0,0,0)"),
+    ("rgba(0,0,0,0)", 0.25, "rgba(64,64,64,0)"),
+    ("rgba(0,0,0,0)", 0.5, "rgba(128,128,128,0)"),
+    ("rgba(0,0,0,0)", 1.0, "rgba(255,255,255,0)"),
+    ("#000000", -1.0, "#000000"),
+    ("#000000", -0.4, "#000000"),
+    ("#000000", -0.15, "#000000"),
+    ("#000000", 0.0, "#000000"),
+    ("#000000", 0.25, "#404040"),
+    ("#000000", 0.5, "#808080"),
+    ("#000000", 1.0, "#ffffff"),
+    ("#ffffff", -1.0, "#000000"),
+    ("#ffffff", -0.4, "#999999"),
+    ("#ffffff", -0.15, "#d9d9d9"),
+    ("#ffffff", 0.0, "#ffffff"),
+    ("#ffffff", 0.25, "#ffffff"),
+    ("#ffffff", 0.5, "#ffffff"),
+    ("#ffffff", 1.0, "#ffffff"),
+    ("#6a0dad", -1.0, "#000000"),
+    ("#6a0dad", -0.4, "#400868"),
+    ("#6a0dad", -0.15, "#5a0b93"),
+    ("#6a0dad", 0.0, "#6a0dad"),
+    ("#6a0dad", 0.25, "#8f4ac2"),
+    ("#6a0dad", 0.5, "#b586d6"),
+    ("#6a0dad", 1.0, "#ffffff"),
+];
+
+// pSBC(p, color, other, true)
+const MIXES: &[(&str, &str, f64, &str)] = &[
+    ("#3ea832", "#000000", 0.25, "#2f7e26"),
+    ("#3ea832", "#000000", 0.5, "#1f5419"),
+    ("#3ea832", "#000000", 0.8, "#0c220a"),
+    ("#3ea832", "#ff0000", 0.25, "#6e7e26"),
+    ("#3ea832", "#ff0000", 0.5, "#9f5419"),
+    ("#3ea832", "#ff0000", 0.8, "#d8220a"),
+    ("#3ea832", "rgba(0,0,255,0.5)", 0.25, "rgba(47,126,101,0.5)"),
+    ("#3ea832", "rgba(0,0,255,0.5)", 0.5, "rgba(31,84,153,0.5)"),
+    ("#3ea832", "rgba(0,0,255,0.5)", 0.8, "rgba(12,34,214,0.5)"),
+    ("#3ea832", "#ffffff80", 0.25, "#6ebe6580"),
+    ("#3ea832", "#ffffff80", 0.5, "#9fd49980"),
+    ("#3ea832", "#ffffff80", 0.8, "#d8eed680"),
+    ("rgb(10,20,30)", "#000000", 0.25, "#080f17"),
+    ("rgb(10,20,30)", "#000000", 0.5, "#050a0f"),
+    ("rgb(10,20,30)", "#000000", 0.8, "#020406"),
+    ("rgb(10,20,30)", "#ff0000", 0.25, "#470f17"),
+    ("rgb(10,20,30)", "#ff0000", 0.5, "#850a0f"),
+    ("rgb(10,20,30)", "#ff0000", 0.8, "#ce0406"),
+    (
+        "rgb(10,20,30)",
+        "rgba(0,0,255,0.5)",
+        0.25,
+        "rgba(8,15,86,0.5)",
+    ),
+    (
+        "rgb(10,20,30)",
+        "rgba(0,0,255,0.5)",
+        0.5,
+        "rgba(5,10,143,0.5)",
+    ),
+    (
+        "rgb(10,20,30)",
+        "rgba(0,0,255,0.5)",
+        0.8,
+        "rgba(2,4,210,0.5)",
+    ),
+    ("rgb(10,20,30)", "#ffffff80", 0.25, "#474f5680"),
+    ("rgb(10,20,30)", "#ffffff80", 0.5, "#858a8f80"),
+    ("rgb(10,20,30)", "#ffffff80", 0.8, "#ced0d280"),
+    ("#0f08", "#000000", 0.25, "#00bf0088"),
+    ("#0f08", "#000000", 0.5, "#00800088"),
+    ("#0f08", "#000000", 0.8, "#00330088"),
+    ("#0f08", "#ff0000", 0.25, "#40bf0088"),
+    ("#0f08", "#ff0000", 0.5, "#80800088"),
+    ("#0f08", "#ff0000", 0.8, "#cc330088"),
+    ("#0f08", "rgba(0,0,255,0.5)", 0.25, "rgba(0,191,64,0.525)"),
+    ("#0f08", "rgba(0,0,255,0.5)", 0.5, "rgba(0,128,128,0.517)"),
+    ("#0f08", "rgba(0,0,255,0.5)", 0.8, "rgba(0,51,204,0.507)"),
+    ("#0f08", "#ffffff80", 0.25, "#40ff4086"),
+    ("#0f08", "#ffffff80", 0.5, "#80ff8084"),
+    ("#0f08", "#ffffff80", 0.8, "#ccffcc82"),
+    ("rgba(252, 250, 157, 0.2)", "#000000", 0.25, "#bdbc7633"),
+    ("rgba(252, 250, 157, 0.2)", "#000000", 0.5, "#7e7d4f33"),
+    ("rgba(252, 250, 157, 0.2)", "#000000", 0.8, "#32321f33"),
+    ("rgba(252, 250, 157, 0.2)", "#ff0000", 0.25, "#fdbc7633"),
+    ("rgba(252, 250, 157, 0.2)", "#ff0000", 0.5, "#fe7d4f33"),
+    ("rgba(252, 250, 157, 0.2)", "#ff0000", 0.8, "#fe321f33"),
+    (
+        "rgba(252, 250, 157, 0.2)",
+        "rgba(0,0,255,0.5)",
+        0.25,
+        "rgba(189,188,182,0.275)",
+    ),
+    (
+        "rgba(252, 250, 157, 0.2)",
+        "rgba(0,0,255,0.5)",
+        0.5,
+        "rgba(126,125,206,0.35)",
+    ),
+    (
+        "rgba(252, 250, 157, 0.2)",
+        "rgba(0,0,255,0.5)",
+        0.8,
+        "rgba(50,50,235,0.44)",
+    ),
+    ("rgba(252, 250, 157, 0.2)", "#ffffff80", 0.25, "#fdfbb646"),
+    ("rgba(252, 250, 157, 0.2)", "#ffffff80", 0.5, "#fefdce5a"),
+    ("rgba(252, 250, 157, 0.2)", "#ffffff80", 0.8, "#fefeeb71"),
+];
+
+// pSBC returned null for these
+const INVALID: &[&str] = &[
+    "#12",
+    "#12345",
+    "#1234567",
+    "red",
+    "rgb(1,2)",
+    "rgb(1,2,3,4,5)",
+];
+
+#[test]
+fn shades_and_tints_like_psbc() {
+    for &(color, p, expected) in SHADES {
+        let color = Color::parse(color).unwrap();
+        let result = if p < 0. {
+            color.shade(-p)
+        } else {
+            color.tint(p)
+        };
+        assert_eq!(result.css(), expected, "pSBC({}, {:?})", p, color);
+    }
+}
+
+#[test]
+fn mixes_like_psbc() {
+    for &(color, other, p, expected) in MIXES {
+        let result = Color::parse(color)
+            .unwrap()
+            .mix(&Color::parse(other).unwrap(), p);
+        assert_eq!(result.css(), expected, "pSBC({}, {}, {})", p, color, other);
+    }
+}
+
+#[test]
+fn rejects_what_psbc_rejects() {
+    for color in INVALID {
+        assert_eq!(Color::parse(color), None, "{}", color);
+    }
+}
+
+#[test]
+fn keeps_its_notation() {
+    assert_eq!(Color::hex(0x3ea832).css(), "#3ea832");
+    assert_eq!(Color::hex_with_alpha(0x121212aa).css(), "#121212aa");
+    assert_eq!(
+        Color::rgba(252, 250, 157, 0.2).css(),
+        "rgba(252,250,157,0.2)"
+    );
+    assert_eq!(Color::parse("#0f8").unwrap().css(), "#00ff88");
+    assert_eq!(
+        Color::parse("rgba(50, 50, 50, 1.0)").unwrap().css(),
+        "rgba(50,50,50,1)"
+    );
+    assert_eq!(Color::parse("rgb(1, 2, 3)").unwrap().css(), "rgb(1,2,3)");
+}
+
+#[test]
+fn alpha() {
+    let color = Color::hex(0xf28900);
+    assert_eq!(color.channels(), (242, 137, 0, 1.));
+    assert_eq!(color.with_alpha(0.5).css(), "#f2890080");
+    assert_eq!(
+        Color::rgba(0, 230, 242, 0.3).with_alpha(0.75).css(),
+        "rgba(0,230,242,0.75)"
+    );
+    assert_eq!(Color::parse("#00000080").unwrap().channels().3, 0.502);
+}
@@ -0,0 +1,106 @@
+use cactuswar_client::color::Color;
+use cactuswar_client::draw::draw_grid;
+use cactuswar_client::engine::{Entity, Tank, World};
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::svg::SvgRenderer;
+use cactuswar_client::util::Vector2;
+
+fn renderer() -> SvgRenderer {
+    SvgRenderer::new(
+        1000.,
+        1000.,
+        Box::new(|_: &str, text: &str| text.len() as f64),
+    )
+}
+
+fn view(x: f64, y: f64, width: f64, height: f64) -> Aabb {
+    Aabb::new(
+        Vector2 { x, y },
+        Vector2 {
+            x: x + width,
+            y: y + height,
+        },
+    )
+}
+
+fn grid() -> Color {
+    Color::rgba(20, 14, 0, 1.)
+}
+
+fn tank(id: u32, name: &str, x: f64) -> Tank {
+    Tank::new(id, String::from(name), Vector2 { x, y: 0. })
+}
+
+#[test]
+fn the_grid_is_drawn_inside_the_view() {
+    let mut ctx = renderer();
+    draw_grid(
+        &mut ctx,
+        10000.,
+        10000.,
+        &view(250., 150., 400., 200.),
+        &grid(),
+    );
+    let svg = ctx.to_svg();
+    // x = 300, 400, 500 and 600, and y = 200 and 300
+    assert!(svg.contains(
+        "d=\"M300 150L300 350M400 150L400 350M500 150L500 350M600 150L600 350\
+         M250 200L650 200M250 300L650 300\""
+    ));
+}
+
+#[test]
+fn the_grid_stops_at_the_arena() {
+    let mut ctx = renderer();
+    draw_grid(
+        &mut ctx,
+        300.,
+        300.,
+        &view(-150., -150., 400., 1000.),
+        &grid(),
+    );
+    assert!(ctx.to_svg().contains(
+        "d=\"M0 0L0 300M100 0L100 300M200 0L200 300M0 0L250 0M0 100L250 100M0 200L250 200\""
+    ));
+
+    let mut ctx = renderer();
+    draw_grid(&mut ctx, 300., 300., &view(500., 0., 400., 400.), &grid());
+    assert!(!ctx.to_svg().contains("<path"));
+}
+
+#[test]
+fn the_view_follows_the_camera() {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.camera = Vector2 { x: 1000., y: 500. };
+    world.previous_camera = world.camera;
+    let view = world.view(1600., 900., 1.);
+    assert_eq!(view.center(), world.camera);
+    let scale = world.fov_scale(1600., 900.);
+    assert_eq!(view.size(), Vector2::new(1600., 900.) / scale);
+}
+
+#[test]
+fn entities_out_of_view_are_not_drawn() {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.add_entity(Entity::Tank(tank(1, "nearby", 300.)));
+    // a name reaches out past the radius
+    world.add_entity(Entity::Tank(tank(2, "at the edge", 1300.)));
+    world.add_entity(Entity::Tank(tank(3, "out of sight", 2000.)));
+    world.add_entity(Entity::Tank(tank(4, "far away", 5000.)));
+
+    let mut ctx = renderer();
+    let scene = world.draw_entities(&mut ctx, 1., &view(-1000., -1000., 2000., 2000.));
+    let svg = ctx.to_svg();
+    assert!(svg.contains(">nearby</text>"));
+    assert!(svg.contains(">at the edge</text>"));
+    assert!(!svg.contains(">out of sight</text>"));
+    assert!(!svg.contains(">far away</text>"));
+
+    // a tank out of sight still shines into view, and casts a shadow, but one further away
+    // doesn't
+    let lit_from = |x: f64| scene.lights.iter().any(|(_, light)| light.x == x);
+    let shaded_by = |x: f64| scene.occluders.iter().any(|circle| circle.center.x == x);
+    assert!(lit_from(2000.) && shaded_by(2000.));
+    assert!(!lit_from(5000.) && !shaded_by(5000.));
+}
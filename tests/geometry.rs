@@ -0,0 +1,114 @@
+use cactuswar_client::geometry::{Aabb, Circle, Segment};
+use cactuswar_client::util::{Lerp, Vector2};
+use std::f64::consts::PI;
+
+fn v(x: f64, y: f64) -> Vector2<f64> {
+    Vector2::new(x, y)
+}
+
+fn assert_close(a: Vector2<f64>, b: Vector2<f64>) {
+    assert!(a.distance(b) < 1e-9, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn vector_operators() {
+    let mut a = v(1., 2.);
+    assert_eq!(a + v(3., 4.), v(4., 6.));
+    assert_eq!(a - v(3., 4.), v(-2., -2.));
+    assert_eq!(a * 2., v(2., 4.));
+    assert_eq!(a / 2., v(0.5, 1.));
+    assert_eq!(-a, v(-1., -2.));
+    a += v(1., 1.);
+    a -= v(0., 2.);
+    a *= 3.;
+    assert_eq!(a, v(6., 3.));
+    assert_eq!(
+        Vector2::new(1i16, 2) + Vector2::new(3, 4),
+        Vector2::new(4, 6)
+    );
+}
+
+#[test]
+fn vector_math() {
+    assert_eq!(v(1., 2.).dot(v(3., 4.)), 11.);
+    assert_eq!(v(1., 0.).cross(v(0., 1.)), 1.);
+    assert_eq!(v(0., 0.).normalize(), v(0., 0.));
+    assert_eq!(v(0., 5.).normalize(), v(0., 1.));
+    assert_eq!(v(1., 1.).distance(v(4., 5.)), 5.);
+    assert_eq!(v(1., 0.).perp(), v(0., 1.));
+    assert_close(v(1., 0.).rotate(PI / 2.), v(0., 1.));
+    assert_close(Vector2::from_angle(PI), v(-1., 0.));
+    assert_eq!(v(-1., 0.).angle(), PI);
+    assert_eq!(v(0., 0.).lerp(v(10., -10.), 0.25), v(2.5, -2.5));
+}
+
+#[test]
+fn vector_conversions() {
+    assert_eq!(Vector2::<f64>::from(Vector2::new(3i16, -4)), v(3., -4.));
+    assert_eq!(Vector2::<i16>::from(v(2.6, -2.6)), Vector2::new(3, -3));
+    assert_eq!(
+        Vector2::<i16>::from(v(1e9, -1e9)),
+        Vector2::new(i16::MAX, i16::MIN)
+    );
+}
+
+#[test]
+fn circle_intersections() {
+    let circle = Circle::new(v(0., 0.), 10.);
+    assert!(circle.contains(v(10., 0.)));
+    assert!(!circle.contains(v(10., 1.)));
+    assert!(circle.intersects_circle(&Circle::new(v(15., 0.), 5.)));
+    assert!(!circle.intersects_circle(&Circle::new(v(15.1, 0.), 5.)));
+
+    assert!(circle.intersects_segment(&Segment::new(v(-20., 5.), v(20., 5.))));
+    assert!(!circle.intersects_segment(&Segment::new(v(-20., 11.), v(20., 11.))));
+    // the line through the segment passes the circle, the segment stops short
+    assert!(!circle.intersects_segment(&Segment::new(v(20., 0.), v(30., 0.))));
+
+    assert!(circle.intersects_aabb(&Aabb::new(v(5., 5.), v(20., 20.))));
+    assert!(!circle.intersects_aabb(&Aabb::new(v(8., 8.), v(20., 20.))));
+    assert_eq!(circle.bounds(), Aabb::new(v(-10., -10.), v(10., 10.)));
+}
+
+#[test]
+fn segment_intersections() {
+    let a = Segment::new(v(0., 0.), v(10., 10.));
+    assert_close(
+        a.intersection(&Segment::new(v(0., 10.), v(10., 0.)))
+            .unwrap(),
+        v(5., 5.),
+    );
+    assert_eq!(a.intersection(&Segment::new(v(0., 10.), v(4., 6.))), None);
+    assert_eq!(a.intersection(&Segment::new(v(1., 0.), v(11., 10.))), None);
+    assert!(a.intersects_segment(&Segment::new(v(10., 10.), v(20., 0.))));
+
+    assert_eq!(a.closest_point(v(10., 0.)), v(5., 5.));
+    assert_eq!(a.closest_point(v(-5., -5.)), v(0., 0.));
+    assert_eq!(
+        Segment::new(v(0., 0.), v(0., 10.)).distance_to(v(3., 14.)),
+        5.
+    );
+    assert_eq!(
+        Segment::new(v(1., 1.), v(1., 1.)).closest_point(v(9., 9.)),
+        v(1., 1.)
+    );
+}
+
+#[test]
+fn aabb_intersections() {
+    let aabb = Aabb::from_center(v(0., 0.), v(10., 5.));
+    assert_eq!(aabb.size(), v(20., 10.));
+    assert_eq!(aabb.center(), v(0., 0.));
+    assert!(aabb.contains(v(10., -5.)));
+    assert!(!aabb.contains(v(10., 6.)));
+    assert!(aabb.intersects_aabb(&Aabb::new(v(10., 5.), v(20., 20.))));
+    assert!(!aabb.intersects_aabb(&Aabb::new(v(11., 0.), v(20., 20.))));
+    assert_eq!(aabb.expand(1.), Aabb::new(v(-11., -6.), v(11., 6.)));
+
+    assert!(aabb.intersects_segment(&Segment::new(v(-20., 0.), v(20., 0.))));
+    assert!(aabb.intersects_segment(&Segment::new(v(1., 1.), v(2., 2.))));
+    assert!(aabb.intersects_segment(&Segment::new(v(-20., -20.), v(20., 20.))));
+    assert!(!aabb.intersects_segment(&Segment::new(v(-20., 6.), v(20., 6.))));
+    assert!(!aabb.intersects_segment(&Segment::new(v(11., -20.), v(11., 20.))));
+    assert!(!aabb.intersects_segment(&Segment::new(v(-30., 0.), v(-20., 0.))));
+}
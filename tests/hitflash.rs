@@ -0,0 +1,116 @@
+use cactuswar_client::engine::{Entity, Shape, World, STEP_MS};
+use cactuswar_client::hitflash::{HitFlash, Style};
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::svg;
+use cactuswar_client::util::Vector2;
+
+#[test]
+fn flashes_are_parsed() {
+    assert_eq!(HitFlash::parse("fill"), Ok(HitFlash::DEFAULT));
+    assert_eq!(
+        HitFlash::parse(" outline:400ms "),
+        Ok(HitFlash {
+            style: Style::Outline,
+            duration: 400.,
+        })
+    );
+    assert_eq!(HitFlash::parse("off").unwrap().style, Style::Off);
+    for flash in ["fill", "outline:150", "off:1000"] {
+        let flash = HitFlash::parse(flash).unwrap();
+        assert_eq!(HitFlash::parse(&flash.to_string()), Ok(flash));
+    }
+
+    assert_eq!(
+        HitFlash::parse("blink"),
+        Err(String::from("Unknown hit flash style: blink"))
+    );
+    for duration in ["fill:0", "fill:-20", "fill:soon"] {
+        assert!(HitFlash::parse(duration)
+            .unwrap_err()
+            .starts_with("Invalid hit flash duration"));
+    }
+}
+
+#[test]
+fn flashes_hold_then_fade() {
+    let flash = HitFlash {
+        style: Style::Fill,
+        duration: 200.,
+    };
+    assert_eq!(flash.intensity(0.), 1.);
+    assert_eq!(flash.intensity(100.), 1.);
+    assert_eq!(flash.intensity(150.), 0.5);
+    assert_eq!(flash.intensity(200.), 0.);
+    assert_eq!(flash.intensity(f64::INFINITY), 0.);
+
+    let off = HitFlash {
+        style: Style::Off,
+        ..flash
+    };
+    assert_eq!(off.intensity(0.), 0.);
+}
+
+fn cactus(since_hit: f64) -> Shape {
+    Shape {
+        health: 0.5,
+        since_hit,
+        ..Shape::new(1, Vector2 { x: 0., y: 0. }, 8, 80)
+    }
+}
+
+/// An SVG of a cactus hit `since_hit` milliseconds ago, flashing like `flash`.
+fn draw(flash: HitFlash, since_hit: f64) -> String {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.hit_flash = flash;
+    world.add_entity(Entity::Shape(cactus(since_hit)));
+    world.yourself.position = Vector2 { x: 0., y: 10000. };
+    world.yourself.previous_position = world.yourself.position;
+    svg::draw_world(
+        &mut world,
+        400.,
+        400.,
+        1.,
+        0,
+        Box::new(|_: &str, text: &str| text.len() as f64),
+    )
+}
+
+#[test]
+fn hits_are_drawn_the_same_every_time() {
+    let damaged = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8)
+        .theme
+        .cactus
+        .damaged;
+    let fill = draw(HitFlash::DEFAULT, 0.);
+    assert!(fill.contains(damaged.css()));
+    assert_eq!(draw(HitFlash::DEFAULT, 0.), fill);
+    // the flash is over
+    assert!(!draw(HitFlash::DEFAULT, 1000.).contains(damaged.css()));
+
+    let outline = HitFlash {
+        style: Style::Outline,
+        ..HitFlash::DEFAULT
+    };
+    assert_ne!(draw(outline, 0.), fill);
+    assert_eq!(draw(outline, 0.), draw(outline, 0.));
+
+    let off = HitFlash {
+        style: Style::Off,
+        ..HitFlash::DEFAULT
+    };
+    assert_eq!(draw(off, 0.), draw(off, f64::INFINITY));
+}
+
+#[test]
+fn flashes_follow_simulation_time() {
+    let mut shape = cactus(0.);
+    for _ in 0..6 {
+        shape.update(1.);
+    }
+    assert!((shape.since_hit - 6. * STEP_MS).abs() < 1e-9);
+    let mut other = cactus(0.);
+    for _ in 0..3 {
+        other.update(2.);
+    }
+    assert!((other.since_hit - shape.since_hit).abs() < 1e-9);
+}
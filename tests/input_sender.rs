@@ -0,0 +1,80 @@
+use cactuswar_client::engine::Input;
+use cactuswar_client::input_sender::{InputSender, HEARTBEAT, MAX_RATE};
+use cactuswar_client::util::Vector2;
+
+fn aiming_at(x: i16, y: i16) -> Input {
+    let mut input = Input::new();
+    input.mouse_position = Vector2 { x, y };
+    input
+}
+
+/// The time between two sends at the highest rate, in milliseconds.
+const INTERVAL: f64 = 1000. / MAX_RATE;
+
+#[test]
+fn unchanged_input_is_not_sent() {
+    let mut sender = InputSender::new();
+    assert_eq!(sender.poll(0., Input::new()), Some(Input::new()));
+    assert_eq!(sender.poll(INTERVAL, Input::new()), None);
+    assert_eq!(sender.poll(500., Input::new()), None);
+}
+
+#[test]
+fn changes_are_sent() {
+    let mut sender = InputSender::new();
+    sender.poll(0., Input::new());
+    // the mouse is snapped to the grid, so these are no change
+    assert_eq!(sender.poll(INTERVAL, aiming_at(1, -1)), None);
+    assert_eq!(sender.poll(2. * INTERVAL, aiming_at(-1, 1)), None);
+    assert_eq!(
+        sender.poll(3. * INTERVAL, aiming_at(3, 7)),
+        Some(aiming_at(4, 8))
+    );
+
+    let mut input = aiming_at(4, 8);
+    input.W = true;
+    assert_eq!(sender.poll(4. * INTERVAL, input), Some(input));
+}
+
+#[test]
+fn changes_are_sent_at_most_max_rate_times_a_second() {
+    let mut sender = InputSender::new();
+    let mut sent = 0;
+    // a new mouse position every millisecond for a second
+    for time in 0..1000 {
+        if sender.poll(time as f64, aiming_at(time * 4, 0)).is_some() {
+            sent += 1;
+        }
+    }
+    assert_eq!(sent, MAX_RATE as usize);
+
+    // a change held back by the cap goes out once it's allowed
+    let mut sender = InputSender::new();
+    sender.poll(0., aiming_at(0, 0));
+    assert_eq!(sender.poll(1., aiming_at(100, 0)), None);
+    assert_eq!(
+        sender.poll(INTERVAL, aiming_at(100, 0)),
+        Some(aiming_at(100, 0))
+    );
+}
+
+#[test]
+fn idle_input_is_sent_as_a_heartbeat() {
+    let mut sender = InputSender::new();
+    sender.poll(0., Input::new());
+    assert_eq!(sender.poll(HEARTBEAT - 1., Input::new()), None);
+    assert_eq!(sender.poll(HEARTBEAT, Input::new()), Some(Input::new()));
+    assert_eq!(sender.poll(HEARTBEAT + 1., Input::new()), None);
+    assert_eq!(
+        sender.poll(2. * HEARTBEAT, Input::new()),
+        Some(Input::new())
+    );
+}
+
+#[test]
+fn resetting_sends_straight_away() {
+    let mut sender = InputSender::new();
+    sender.poll(0., Input::new());
+    sender.reset();
+    assert_eq!(sender.poll(1., Input::new()), Some(Input::new()));
+}
@@ -0,0 +1,103 @@
+use cactuswar_client::inspect;
+use cactuswar_client::protocol;
+use cactuswar_client::replay::Recorder;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// A handshake packet for player 7, with no mockups.
+const HANDSHAKE: [u8; 6] = [3, 0, 0, 0, 7, 0];
+
+/// Run the inspector on `data` and return whether it succeeded, its output and its errors.
+fn inspect(data: &[u8]) -> (bool, String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_inspect"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(data).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn replays_are_listed() {
+    let mut recorder = Recorder::new(0., 0., &HANDSHAKE);
+    recorder.record_packet(10., &[6]);
+    let (success, output, _) = inspect(&recorder.finish());
+    assert!(success);
+    assert!(
+        output.contains("-#0 @ 0.000ms: handshake (id 3, 6 bytes)"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("-#1 @ 10.000ms: respawn (id 6, 1 bytes)"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn corrupt_replays_are_errors() {
+    let mut data = Recorder::new(0., 0., &HANDSHAKE).finish();
+    let offset = data.len() + 1;
+    data.extend_from_slice(&[0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1, 7]);
+    let (success, _, errors) = inspect(&data);
+    assert!(!success);
+    assert_eq!(errors, format!("-: replay is corrupt at byte {}\n", offset));
+}
+
+#[test]
+fn truncated_replays_are_errors() {
+    let mut recorder = Recorder::new(0., 0., &HANDSHAKE);
+    recorder.record_packet(1000., &[6]);
+    let data = recorder.finish();
+    let (success, _, errors) = inspect(&data[..data.len() - 3]);
+    assert!(!success);
+    assert_eq!(
+        errors,
+        format!("-: replay is truncated at byte {}\n", data.len() - 3)
+    );
+}
+
+#[test]
+fn malformed_packets_are_errors() {
+    // a census of one entity, cut off in the middle of it, then an unknown packet
+    let (success, output, _) = inspect(b"02 0001 0100 3f800000 00 0000\nff\n");
+    assert!(!success);
+    assert!(
+        output.contains("!! decode error: 4 byte read runs past the end of the data at byte 10"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("!! decode error: unknown packet id 255 at byte 0"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn dissections_point_at_the_problem() {
+    let dissection = inspect::dissect(&[2, 0, 1, 1, 0, 0x3f, 0x80, 0, 0, 9]);
+    let error = dissection.error.unwrap();
+    assert_eq!(error.offset, 9);
+    assert_eq!(error.message, "unknown entity type 9");
+
+    // nothing after an unknown entity is read, as it can't be found
+    let dissection = inspect::dissect(&[2, 0, 2, 1, 0, 0x3f, 0x80, 0, 0, 9, 1, 0, 0, 0, 0]);
+    assert_eq!(dissection.error.unwrap().offset, 9);
+    match dissection.packet {
+        Some(protocol::Decoded::Census(census)) => assert!(census.entities.is_empty()),
+        _ => panic!("a census was expected"),
+    }
+
+    let error = inspect::dissect(&[]).error.unwrap();
+    assert_eq!(error.message, "empty packet");
+}
@@ -0,0 +1,116 @@
+use cactuswar_client::killcam::{Killcam, BUFFER_LENGTH};
+use cactuswar_client::protocol::{BulletPacket, Census, Entity, Protocol, TankPacket};
+use cactuswar_client::util::Vector2;
+
+/// A census of `tanks` (id, x) and `bullets` (id, x, owner), all on the x axis.
+fn census(tanks: &[(u32, i16)], bullets: &[(u32, i16, u32)]) -> Vec<u8> {
+    let tanks = tanks.iter().map(|(id, x)| {
+        Entity::Tank(TankPacket {
+            id: *id,
+            position: Vector2 { x: *x, y: 0 },
+            rotation: 0.,
+            velocity: Vector2 { x: 0, y: 0 },
+            mockup: 0,
+            health: 1.,
+            radius: 50,
+            name: String::new(),
+            message: String::new(),
+        })
+    });
+    let bullets = bullets.iter().map(|(id, x, owner)| {
+        Entity::Bullet(BulletPacket {
+            id: *id,
+            position: Vector2 { x: *x, y: 0 },
+            radius: 20,
+            velocity: Vector2 { x: 0, y: 0 },
+            owner: *owner,
+        })
+    });
+    Census::new(5000, 1., tanks.chain(bullets).collect())
+        .encode()
+        .cursor
+        .into_inner()
+}
+
+/// The killer picked from `censuses`, received 100ms apart, when we're tank 1.
+fn killer(censuses: &[Vec<u8>]) -> Option<u32> {
+    let mut killcam = Killcam::new();
+    for (index, data) in censuses.iter().enumerate() {
+        killcam.push(index as f64 * 100., data);
+    }
+    assert!(killcam.start(1));
+    let replay = killcam.replay.as_ref().unwrap();
+    assert_eq!(replay.wide, replay.killer.is_none());
+    replay.killer
+}
+
+#[test]
+fn only_the_last_seconds_are_kept() {
+    let mut killcam = Killcam::new();
+    for second in 0..=8 {
+        killcam.push(second as f64 * 1000., &census(&[(1, 0)], &[]));
+    }
+    assert!(killcam.start(1));
+    assert_eq!(killcam.replay.as_ref().unwrap().duration(), BUFFER_LENGTH);
+
+    // the buffer went into the killcam
+    killcam.replay = None;
+    assert!(!killcam.start(1));
+}
+
+#[test]
+fn nothing_to_play_after_a_clear() {
+    let mut killcam = Killcam::new();
+    killcam.push(0., &census(&[(1, 0)], &[]));
+    killcam.clear();
+    assert!(!killcam.start(1));
+    assert!(!killcam.is_playing());
+}
+
+#[test]
+fn the_nearest_enemy_bullet_gives_the_killer_away() {
+    let tanks = [(1, 0), (2, 100), (3, 1000)];
+    // our own bullet is nearer, but doesn't count
+    let bullets = [(10, 10, 1), (11, 50, 3), (12, 400, 2)];
+    assert_eq!(killer(&[census(&tanks, &bullets)]), Some(3));
+}
+
+#[test]
+fn without_bullets_the_nearest_tank_is_the_killer() {
+    assert_eq!(
+        killer(&[census(&[(1, 0), (2, 100), (3, -90)], &[])]),
+        Some(3)
+    );
+    assert_eq!(killer(&[census(&[(1, 0)], &[])]), None);
+}
+
+#[test]
+fn the_killer_is_found_in_the_last_census_we_are_in() {
+    let censuses = [
+        census(&[(1, 0), (2, 100)], &[]),
+        census(&[(1, 0), (3, 100)], &[]),
+        // after we died
+        census(&[(2, 0)], &[]),
+    ];
+    assert_eq!(killer(&censuses), Some(3));
+}
+
+#[test]
+fn killcams_end_half_a_second_after_the_last_census() {
+    let mut killcam = Killcam::new();
+    killcam.push(1000., &census(&[(1, 0), (2, 100)], &[]));
+    killcam.push(1300., &census(&[(1, 20), (2, 100)], &[]));
+    assert!(killcam.start(1));
+    assert!(killcam.is_playing());
+
+    killcam.advance(0.);
+    let replay = killcam.replay.as_ref().unwrap();
+    assert_eq!(replay.duration(), 300.);
+    assert_eq!(replay.entities.len(), 2);
+    assert_eq!(replay.focus_tank().map(|t| t.id), Some(2));
+
+    killcam.advance(799.);
+    assert!(killcam.is_playing());
+    killcam.advance(1.);
+    assert!(!killcam.is_playing());
+}
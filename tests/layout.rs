@@ -0,0 +1,153 @@
+use cactuswar_client::engine::{PlayerState, World};
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::hud;
+use cactuswar_client::layout::{Anchor, Layout};
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::protocol::{LeaderboardEntry, TankMockup};
+use cactuswar_client::svg::SvgRenderer;
+use cactuswar_client::util::{Scalar, Vector2};
+
+fn rect(x: f64, y: f64, width: f64, height: f64) -> Aabb {
+    Aabb::new(
+        Vector2 { x, y },
+        Vector2 {
+            x: x + width,
+            y: y + height,
+        },
+    )
+}
+
+#[test]
+fn panels_go_to_their_anchors() {
+    let mut layout = Layout::new(Vector2::new(1000., 800.), 20., 10.);
+    let size = Vector2::new(100., 50.);
+    assert_eq!(
+        layout.place(Anchor::TopLeft, size),
+        rect(20., 20., 100., 50.)
+    );
+    assert_eq!(layout.place(Anchor::Top, size), rect(450., 20., 100., 50.));
+    assert_eq!(
+        layout.place(Anchor::TopRight, size),
+        rect(880., 20., 100., 50.)
+    );
+    assert_eq!(layout.place(Anchor::Left, size), rect(20., 375., 100., 50.));
+    assert_eq!(
+        layout.place(Anchor::Center, size),
+        rect(450., 375., 100., 50.)
+    );
+    assert_eq!(
+        layout.place(Anchor::Right, size),
+        rect(880., 375., 100., 50.)
+    );
+    assert_eq!(
+        layout.place(Anchor::BottomLeft, size),
+        rect(20., 730., 100., 50.)
+    );
+    assert_eq!(
+        layout.place(Anchor::Bottom, size),
+        rect(450., 730., 100., 50.)
+    );
+    assert_eq!(
+        layout.place(Anchor::BottomRight, size),
+        rect(880., 730., 100., 50.)
+    );
+    assert_eq!(layout.panels().len(), 9);
+}
+
+#[test]
+fn panels_stack_away_from_their_edge() {
+    let mut layout = Layout::new(Vector2::new(1000., 800.), 20., 10.);
+    let size = Vector2::new(100., 50.);
+    layout.place(Anchor::TopLeft, size);
+    assert_eq!(
+        layout.place(Anchor::TopLeft, size),
+        rect(20., 80., 100., 50.)
+    );
+    layout.place(Anchor::BottomRight, size);
+    assert_eq!(
+        layout.place(Anchor::BottomRight, size),
+        rect(880., 670., 100., 50.)
+    );
+    layout.place(Anchor::Center, size);
+    assert_eq!(
+        layout.place(Anchor::Center, size),
+        rect(450., 435., 100., 50.)
+    );
+}
+
+#[test]
+fn panels_move_out_of_the_way() {
+    // too narrow for both panels side by side
+    let mut layout = Layout::new(Vector2::new(500., 800.), 20., 10.);
+    let wide = Vector2::new(300., 100.);
+    layout.place(Anchor::TopLeft, wide);
+    assert_eq!(
+        layout.place(Anchor::TopRight, wide),
+        rect(180., 130., 300., 100.)
+    );
+    assert_eq!(
+        layout.place(Anchor::Top, wide),
+        rect(100., 240., 300., 100.)
+    );
+    // and from the bottom, upwards
+    layout.place(Anchor::BottomLeft, wide);
+    assert_eq!(
+        layout.place(Anchor::BottomRight, wide),
+        rect(180., 570., 300., 100.)
+    );
+}
+
+fn world() -> World {
+    let mut world = World::new(String::from("me"), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.mockups = Some(vec![TankMockup {
+        name: String::from("Basic"),
+        fov: 20,
+        barrels: Vec::new(),
+    }]);
+    world.leaderboard.entries = (0..10)
+        .map(|index| LeaderboardEntry {
+            name: format!("tank {}", index),
+            level: 40. - index as f32,
+            mockup: 0,
+        })
+        .collect();
+    world
+        .netsim
+        .set_conditions(Conditions::parse("bad").unwrap());
+    world.netstats.visible = true;
+    world.state.player_state = PlayerState::Dead(30.);
+    world.state.death_animation_completion = Scalar::new(1.);
+    world
+}
+
+#[test]
+fn the_hud_fits_portrait_windows() {
+    for (width, height, ui_scale) in [(1920., 1080., 1.), (400., 900., 1.), (720., 1280., 1.5)] {
+        let world = world();
+        let scale = hud::scale(width, height, ui_scale);
+        let (width, height) = (width / scale, height / scale);
+        let mut ctx = SvgRenderer::new(
+            width,
+            height,
+            Box::new(|_: &str, text: &str| text.len() as f64 * 20.),
+        );
+        let layout = hud::draw(&mut ctx, &world, 0, width, height);
+        let panels = layout.panels();
+        assert_eq!(panels.len(), 6);
+        for (index, panel) in panels.iter().enumerate() {
+            assert!(panel.min.x >= 0. && panel.max.x <= width, "{:?}", panel);
+            for other in &panels[index + 1..] {
+                assert!(!panel.intersects_aabb(other), "{:?} {:?}", panel, other);
+            }
+        }
+    }
+}
+
+#[test]
+fn narrow_windows_shrink_the_hud() {
+    assert_eq!(hud::scale(2000., 2000., 1.), 1.);
+    assert_eq!(hud::scale(2000., 2000., 1.5), 1.5);
+    // 1300 design units across, whatever the UI scale
+    assert_eq!(hud::scale(650., 2000., 1.), 0.5);
+    assert_eq!(hud::scale(650., 2000., 2.), 0.5);
+}
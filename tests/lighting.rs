@@ -0,0 +1,59 @@
+use cactuswar_client::color::Color;
+use cactuswar_client::engine::Light;
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::lighting::{select, Priority};
+use cactuswar_client::util::Vector2;
+
+fn light(x: f64, r: f64) -> Light {
+    Light {
+        x,
+        y: 0.,
+        r,
+        color: Color::rgba(252, 250, 157, 0.3),
+    }
+}
+
+/// Where the lights are, which tells them apart.
+fn positions(lights: &[&Light]) -> Vec<f64> {
+    lights.iter().map(|l| l.x).collect()
+}
+
+fn view() -> Aabb {
+    Aabb::from_center(Vector2::new(0., 0.), Vector2::new(1000., 1000.))
+}
+
+#[test]
+fn lights_outside_the_view_are_skipped() {
+    let lights = [
+        // reaches into the view
+        (Priority::Tank, light(1500., 600.)),
+        // too far away
+        (Priority::Tank, light(2500., 600.)),
+    ];
+    let selected = select(&lights, Vector2::new(0., 0.), &view(), 16);
+    assert_eq!(positions(&selected), vec![1500.]);
+}
+
+#[test]
+fn budget_keeps_the_most_important_lights() {
+    let lights = [
+        // a bullet near us
+        (Priority::Bullet, light(10., 80.)),
+        // a tank far from us
+        (Priority::Tank, light(900., 1300.)),
+        // us
+        (Priority::Viewpoint, light(500., 1300.)),
+        // a tank near us
+        (Priority::Tank, light(300., 1300.)),
+    ];
+    let viewpoint = Vector2::new(500., 0.);
+    assert_eq!(
+        positions(&select(&lights, viewpoint, &view(), 16)),
+        vec![500., 300., 900., 10.]
+    );
+    assert_eq!(
+        positions(&select(&lights, viewpoint, &view(), 2)),
+        vec![500., 300.]
+    );
+    assert!(select(&lights, viewpoint, &view(), 0).is_empty());
+}
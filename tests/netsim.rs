@@ -0,0 +1,123 @@
+use cactuswar_client::netsim::{Conditions, Link, NetSim, PRESETS};
+
+fn conditions(latency: f64, jitter: f64) -> Conditions {
+    Conditions {
+        latency,
+        jitter,
+        ..Conditions::PERFECT
+    }
+}
+
+#[test]
+fn latency_delays_delivery() {
+    let mut link = Link::new(conditions(100., 0.), 1);
+    link.send(0., vec![1]);
+    assert!(link.poll(99.).is_empty());
+    assert_eq!(link.poll(100.), vec![vec![1]]);
+    assert_eq!(link.in_flight(), 0);
+}
+
+#[test]
+fn jitter_alone_keeps_packets_in_order() {
+    let mut link = Link::new(conditions(50., 80.), 7);
+    for i in 0..200u8 {
+        link.send(i as f64, vec![i]);
+    }
+    let delivered: Vec<u8> = link.poll(10_000.).into_iter().map(|p| p[0]).collect();
+    assert_eq!(delivered, (0..200).collect::<Vec<u8>>());
+}
+
+#[test]
+fn reordering_lets_later_packets_overtake() {
+    let mut link = Link::new(
+        Conditions {
+            reorder: 0.5,
+            ..conditions(50., 10.)
+        },
+        3,
+    );
+    for i in 0..100u8 {
+        link.send(i as f64, vec![i]);
+    }
+    let delivered: Vec<u8> = link.poll(10_000.).into_iter().map(|p| p[0]).collect();
+    assert_eq!(delivered.len(), 100);
+    assert!(delivered.windows(2).any(|w| w[0] > w[1]));
+}
+
+#[test]
+fn duplication_delivers_twice() {
+    let mut link = Link::new(
+        Conditions {
+            duplicate: 1.,
+            ..conditions(20., 0.)
+        },
+        5,
+    );
+    link.send(0., vec![9]);
+    assert_eq!(link.poll(1_000.), vec![vec![9], vec![9]]);
+}
+
+#[test]
+fn bandwidth_cap_paces_packets() {
+    let mut link = Link::new(
+        Conditions {
+            bandwidth: 1000.,
+            ..Conditions::PERFECT
+        },
+        1,
+    );
+    for i in 0..10u8 {
+        link.send(0., vec![i; 100]);
+    }
+    // 100 bytes take 100ms at 1000 bytes per second
+    assert_eq!(link.poll(99.).len(), 0);
+    assert_eq!(link.poll(500.).len(), 5);
+    assert_eq!(link.poll(1_000.).len(), 5);
+}
+
+#[test]
+fn same_seed_same_delays() {
+    let mut a = Link::new(PRESETS[2].1, 42);
+    let mut b = Link::new(PRESETS[2].1, 42);
+    for i in 0..50u8 {
+        a.send(i as f64 * 16., vec![i]);
+        b.send(i as f64 * 16., vec![i]);
+    }
+    for now in (0..2_000).step_by(16) {
+        assert_eq!(a.poll(now as f64), b.poll(now as f64));
+    }
+}
+
+#[test]
+fn parse_presets_and_settings() {
+    assert_eq!(Conditions::parse(""), Ok(Conditions::PERFECT));
+    assert_eq!(Conditions::parse("bad"), Ok(PRESETS[2].1));
+    assert_eq!(
+        Conditions::parse("latency:100,jitter:30,reorder:5,duplicate:1,bandwidth:32"),
+        Ok(Conditions {
+            latency: 100.,
+            jitter: 30.,
+            reorder: 0.05,
+            duplicate: 0.01,
+            bandwidth: 32. * 1024.,
+        })
+    );
+    assert!(Conditions::parse("latency").is_err());
+    assert!(Conditions::parse("latency:-5").is_err());
+    assert!(Conditions::parse("loss:5").is_err());
+}
+
+#[test]
+fn simulator_is_bypassed_once_perfect_and_drained() {
+    let mut netsim = NetSim::new(Conditions::PERFECT, 1);
+    assert!(!netsim.is_active());
+
+    assert_eq!(netsim.next_preset(), "200ms");
+    assert!(netsim.is_active());
+    netsim.outgoing.send(0., vec![1]);
+
+    while netsim.next_preset() != "off" {}
+    assert!(netsim.is_active(), "a packet is still in flight");
+    assert_eq!(netsim.outgoing.poll(1_000.), vec![vec![1]]);
+    assert!(!netsim.is_active());
+}
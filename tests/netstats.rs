@@ -0,0 +1,131 @@
+use cactuswar_client::binary::StreamPeerBuffer;
+use cactuswar_client::engine::World;
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::netstats::{Counter, NetStats, Totals, MAX_WINDOW};
+use cactuswar_client::protocol::{
+    Census, Entity, EntityType, Packet, Protocol, ShapePacket, TankPacket,
+};
+use cactuswar_client::util::Vector2;
+
+fn totals(count: u64, bytes: u64) -> Totals {
+    Totals {
+        count,
+        bytes,
+        decode_time: 0.,
+    }
+}
+
+fn counts(totals: Totals) -> (u64, u64) {
+    (totals.count, totals.bytes)
+}
+
+#[test]
+fn windows_add_up_whole_buckets() {
+    let mut counter = Counter::default();
+    counter.record(0., totals(1, 100));
+    counter.record(50., totals(1, 10));
+    counter.record(150., totals(1, 1));
+
+    assert_eq!(counts(counter.window(150., 1000.)), (3, 111));
+    assert_eq!(counts(counter.window(150., 100.)), (1, 1));
+    // the first bucket has left the last second
+    assert_eq!(counts(counter.window(1050., 1000.)), (1, 1));
+    assert_eq!(counts(counter.window(1200., 1000.)), (0, 0));
+    assert_eq!(counts(counter.total), (3, 111));
+}
+
+#[test]
+fn buckets_older_than_the_longest_window_are_dropped() {
+    let mut counter = Counter::default();
+    counter.record(0., totals(1, 100));
+    counter.record(MAX_WINDOW - 1., totals(1, 10));
+    assert_eq!(
+        counts(counter.window(MAX_WINDOW - 1., MAX_WINDOW)),
+        (2, 110)
+    );
+
+    counter.record(MAX_WINDOW + 150., totals(1, 1));
+    assert_eq!(
+        counts(counter.window(MAX_WINDOW + 150., MAX_WINDOW)),
+        (2, 11)
+    );
+    // even a longer window can't bring them back
+    assert_eq!(
+        counts(counter.window(MAX_WINDOW + 150., 2. * MAX_WINDOW)),
+        (2, 11)
+    );
+    assert_eq!(counts(counter.total), (3, 111));
+}
+
+/// A census packet with a tank named "me" and two cacti.
+fn census() -> Vec<u8> {
+    let mut entities = vec![Entity::Tank(TankPacket {
+        id: 1,
+        position: Vector2 { x: 0, y: 0 },
+        rotation: 0.,
+        velocity: Vector2 { x: 0, y: 0 },
+        mockup: 0,
+        health: 1.,
+        radius: 50,
+        name: String::from("me"),
+        message: String::new(),
+    })];
+    for id in 2..4 {
+        entities.push(Entity::Shape(ShapePacket {
+            id,
+            position: Vector2 { x: 100, y: 100 },
+            health: 1.,
+            radius: 80,
+        }));
+    }
+    Census::new(5000, 1., entities).encode().cursor.into_inner()
+}
+
+#[test]
+fn census_entities_are_counted_by_type() {
+    let data = census();
+    let mut buf = StreamPeerBuffer::new();
+    buf.set_data_array(data[1..].to_vec());
+    let census = Census::decode(&mut buf);
+
+    let mut stats = NetStats::new();
+    stats.record_census(0., &census);
+    let tanks = stats.entities[&(EntityType::Tank as u8)].total;
+    let shapes = stats.entities[&(EntityType::Shape as u8)].total;
+    assert_eq!(counts(tanks), (1, 30));
+    assert_eq!(counts(shapes), (2, 30));
+    // everything but the census header is in an entity
+    assert_eq!(tanks.bytes + shapes.bytes, data.len() as u64 - 9);
+}
+
+#[test]
+fn replayed_packets_are_not_counted() {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.receive(0., census());
+    world.handle_packet(census());
+    let counter = &world.netstats.packets[&(Packet::Census as u8)];
+    assert_eq!(counts(counter.total), (1, census().len() as u64));
+    assert_eq!(
+        world.netstats.entities[&(EntityType::Shape as u8)]
+            .total
+            .count,
+        2
+    );
+}
+
+#[test]
+fn malformed_packets_are_not_applied() {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    // the cacti are followed by an entity of an unknown type
+    let mut data = census();
+    data[2] = 4;
+    data.push(9);
+    world.receive(0., data.clone());
+    assert!(world.entities.is_empty());
+    // but they still count as traffic
+    let counter = &world.netstats.packets[&(Packet::Census as u8)];
+    assert_eq!(counts(counter.total), (1, data.len() as u64));
+
+    world.receive(100., census());
+    assert_eq!(world.entities.len(), 3);
+}
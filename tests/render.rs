@@ -0,0 +1,227 @@
+//! Golden image tests: scenes drawn with the software renderer, compared against the PNGs in
+//! `tests/golden`. After a change that's meant to look different, look at the images written
+//! to `target/render` and rerun with `UPDATE_GOLDEN=1` to accept them.
+
+use cactuswar_client::engine::{Bullet, Entity, PlayerState, Shape, Tank, World};
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::protocol::{BarrelMockup, LeaderboardEntry, TankMockup};
+use cactuswar_client::raster::{self, Font};
+use cactuswar_client::util::{Scalar, Vector2};
+use cactuswar_client::widget::Key;
+use std::path::PathBuf;
+use tiny_skia::Pixmap;
+
+const WIDTH: u32 = 640;
+const HEIGHT: u32 = 360;
+
+/// How far a channel may be off before the pixel counts as different.
+const TOLERANCE: u8 = 8;
+/// How many pixels may be different, out of a thousand.
+const MAX_DIFFERENT: usize = 2;
+
+fn font() -> Font {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fonts/FiraSans-Medium.ttf");
+    Font::new(std::fs::read(path).unwrap()).unwrap()
+}
+
+fn barrel(width: f32, length: f32, angle: f32) -> BarrelMockup {
+    BarrelMockup {
+        width,
+        length,
+        angle,
+    }
+}
+
+/// A few of the tanks the server sends in its handshake.
+fn mockups() -> Vec<TankMockup> {
+    use std::f32::consts::PI;
+    vec![
+        TankMockup {
+            name: String::from("Basic"),
+            fov: 20,
+            barrels: vec![barrel(0.4, 1., 0.)],
+        },
+        TankMockup {
+            name: String::from("Twin Flank"),
+            fov: 20,
+            barrels: vec![barrel(0.4, 1., 0.), barrel(0.4, 1., PI)],
+        },
+        TankMockup {
+            name: String::from("Triple"),
+            fov: 20,
+            barrels: vec![
+                barrel(0.4, 1., 0.),
+                barrel(0.4, 0.9, PI / 4.),
+                barrel(0.4, 0.9, -PI / 4.),
+            ],
+        },
+        TankMockup {
+            name: String::from("Octo"),
+            fov: 22,
+            barrels: (0..8)
+                .map(|i| barrel(0.35, 0.9, i as f32 * PI / 4.))
+                .collect(),
+        },
+    ]
+}
+
+fn world() -> World {
+    let mut world = World::new(
+        String::from("golden"),
+        NetSim::new(Conditions::PERFECT, 0),
+        8,
+    );
+    world.mockups = Some(mockups());
+    world.zoom = Scalar::new(0.35);
+    world.state.level = Scalar::new(3.4);
+    world
+}
+
+fn tank(id: u32, mockup: u8, x: f64, y: f64) -> Tank {
+    Tank {
+        net_rotation: 0.5,
+        rotation: 0.5,
+        previous_rotation: 0.5,
+        mockup,
+        health: Scalar::new(0.75),
+        ..Tank::new(id, String::new(), Vector2 { x, y })
+    }
+}
+
+fn shape(id: u32, x: f64, y: f64, damaged: bool) -> Shape {
+    Shape {
+        rotation: 0.3,
+        health: 0.6,
+        // just hit, at the height of its flash
+        since_hit: if damaged { 0. } else { f64::INFINITY },
+        ..Shape::new(id, Vector2 { x, y }, 7, 80)
+    }
+}
+
+fn bullet(id: u32, x: f64, y: f64, own: bool) -> Bullet {
+    Bullet::new(id, Vector2 { x, y }, 20, own)
+}
+
+/// Draw `world` and compare it against `tests/golden/<name>.png`.
+fn check(name: &str, world: &mut World) {
+    let image = raster::draw_world(world, WIDTH, HEIGHT, 1., 0, Some(font()));
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let golden = root.join("tests/golden").join(format!("{}.png", name));
+    let actual = image.pixmap();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        actual.save_png(&golden).unwrap();
+        return;
+    }
+    let output = root.join("target/render");
+    std::fs::create_dir_all(&output).unwrap();
+    actual
+        .save_png(output.join(format!("{}.png", name)))
+        .unwrap();
+
+    let expected = Pixmap::load_png(&golden)
+        .unwrap_or_else(|e| panic!("{}: {} (run with UPDATE_GOLDEN=1)", golden.display(), e));
+    assert_eq!(
+        (expected.width(), expected.height()),
+        (actual.width(), actual.height()),
+        "{} changed size",
+        name
+    );
+    let different = expected
+        .data()
+        .chunks(4)
+        .zip(actual.data().chunks(4))
+        .filter(|(a, b)| a.iter().zip(*b).any(|(a, b)| a.abs_diff(*b) > TOLERANCE))
+        .count();
+    let allowed = (WIDTH * HEIGHT) as usize * MAX_DIFFERENT / 1000;
+    assert!(
+        different <= allowed,
+        "{} pixels of {} differ from {}, see target/render/{}.png",
+        different,
+        name,
+        golden.display(),
+        name
+    );
+}
+
+#[test]
+fn barrel_layouts() {
+    let mut world = world();
+    for mockup in 0..4 {
+        let x = -390. + mockup as f64 * 260.;
+        let mut tank = tank(mockup + 1, mockup as u8, x, 0.);
+        tank.name = mockups()[mockup as usize].name.clone();
+        world.add_entity(Entity::Tank(tank));
+    }
+    world.yourself.position = Vector2 { x: 0., y: 10000. };
+    world.yourself.previous_position = world.yourself.position;
+    check("barrel_layouts", &mut world);
+}
+
+#[test]
+fn damaged_cactus() {
+    let mut world = world();
+    world.add_entity(Entity::Shape(shape(1, -200., 0., false)));
+    world.add_entity(Entity::Shape(shape(2, 200., 0., true)));
+    world.yourself.position = Vector2 { x: 0., y: 10000. };
+    world.yourself.previous_position = world.yourself.position;
+    check("damaged_cactus", &mut world);
+}
+
+#[test]
+fn bullets() {
+    let mut world = world();
+    world.add_entity(Entity::Bullet(bullet(1, 200., -50., true)));
+    world.add_entity(Entity::Bullet(bullet(2, 300., 50., false)));
+    check("bullets", &mut world);
+}
+
+#[test]
+fn hud() {
+    let mut world = world();
+    world.yourself.mockup = 2;
+    check("hud", &mut world);
+}
+
+#[test]
+fn leaderboard() {
+    let mut world = world();
+    world.leaderboard.entries = vec![
+        LeaderboardEntry {
+            name: String::from("prickly"),
+            level: 45.,
+            mockup: 3,
+        },
+        LeaderboardEntry {
+            name: String::new(),
+            level: 30.,
+            mockup: 1,
+        },
+        LeaderboardEntry {
+            name: String::from("golden"),
+            level: 3.,
+            mockup: 0,
+        },
+    ];
+    check("leaderboard", &mut world);
+}
+
+#[test]
+fn death_screen() {
+    let mut world = world();
+    world.state.player_state = PlayerState::Dead(125.);
+    world.state.death_animation_completion = Scalar::new(1.);
+    check("death_screen", &mut world);
+}
+
+#[test]
+fn menu() {
+    let mut world = world();
+    world.menu.open = true;
+    // focus the theme list, which takes a frame with the menu in it
+    raster::draw_world(&mut world, WIDTH, HEIGHT, 1., 0, None);
+    for _ in 0..4 {
+        world.ui.key(Key::Next);
+    }
+    check("menu", &mut world);
+}
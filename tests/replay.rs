@@ -0,0 +1,207 @@
+use cactuswar_client::engine::Input;
+use cactuswar_client::replay::{
+    Entry, Player, Record, Recorder, Replay, ReplayError, KEYFRAME_INTERVAL, MAGIC, SPEEDS, VERSION,
+};
+use cactuswar_client::util::Vector2;
+
+/// A handshake packet for player 7, with no mockups.
+const HANDSHAKE: [u8; 6] = [3, 0, 0, 0, 7, 0];
+
+fn input() -> Input {
+    Input {
+        W: true,
+        A: false,
+        S: false,
+        D: true,
+        mouse_down: true,
+        mouse_position: Vector2 { x: -120, y: 300 },
+    }
+}
+
+/// A recording started at 500ms, with a packet, an input a second later and another packet.
+fn recording() -> Vec<u8> {
+    let mut recorder = Recorder::new(500., 1_700_000_000_000., &HANDSHAKE);
+    recorder.record_packet(500.25, &[2, 0, 0, 7, 208]);
+    recorder.record_input(1500., input());
+    recorder.record_packet(1500., &[7]);
+    recorder.finish()
+}
+
+#[test]
+fn recordings_round_trip() {
+    let replay = Replay::decode(recording()).unwrap();
+    assert_eq!(replay.version, VERSION);
+    assert_eq!(replay.recorded_at, 1_700_000_000_000.);
+    assert_eq!(replay.handshake, HANDSHAKE);
+    assert_eq!(replay.handshake().id, 7);
+
+    let times: Vec<f64> = replay.entries.iter().map(|entry| entry.time).collect();
+    assert_eq!(times, vec![0.25, 1000., 1000.]);
+    assert_eq!(replay.duration(), 1000.);
+    assert!(
+        matches!(&replay.entries[0].record, Record::Packet(data) if data == &[2, 0, 0, 7, 208])
+    );
+    assert!(matches!(&replay.entries[1].record, Record::Input(recorded) if *recorded == input()));
+    assert!(matches!(&replay.entries[2].record, Record::Packet(data) if data == &[7]));
+}
+
+#[test]
+fn empty_recordings_have_no_entries() {
+    let replay = Replay::decode(Recorder::new(0., 0., &HANDSHAKE).finish()).unwrap();
+    assert!(replay.entries.is_empty());
+    assert_eq!(replay.duration(), 0.);
+}
+
+#[test]
+fn other_files_are_rejected() {
+    let mut data = recording();
+    data[..4].copy_from_slice(b"RIFF");
+    assert_eq!(Replay::decode(data).unwrap_err(), ReplayError::BadMagic);
+    assert_eq!(
+        Replay::decode(b"CW".to_vec()).unwrap_err(),
+        ReplayError::Truncated(0)
+    );
+
+    let mut data = recording();
+    data[MAGIC.len()] = VERSION + 1;
+    assert_eq!(
+        Replay::decode(data).unwrap_err(),
+        ReplayError::UnsupportedVersion(VERSION + 1)
+    );
+}
+
+#[test]
+fn truncated_recordings_are_errors() {
+    let data = recording();
+    // the header, then each record: kind, time, and the packet length and packet or the input
+    let header = MAGIC.len() + 1 + 8 + 4 + HANDSHAKE.len();
+    let boundaries = [header, header + 9, header + 18, data.len()];
+    for len in 0..data.len() {
+        let result = Replay::decode(data[..len].to_vec());
+        if let Some(records) = boundaries.iter().position(|boundary| *boundary == len) {
+            assert_eq!(result.unwrap().entries.len(), records, "{} bytes", len);
+        } else {
+            assert!(
+                matches!(result, Err(ReplayError::Truncated(_))),
+                "{} bytes: {:?}",
+                len,
+                result
+            );
+        }
+    }
+}
+
+#[test]
+fn a_trailing_varint_that_is_cut_short_is_an_error() {
+    let mut recorder = Recorder::new(0., 0., &HANDSHAKE);
+    // 1 second is 1000000 microseconds, which takes three bytes
+    recorder.record_packet(1000., &[7]);
+    let data = recorder.finish();
+    let header = MAGIC.len() + 1 + 8 + 4 + HANDSHAKE.len();
+    assert_eq!(
+        Replay::decode(data[..header + 2].to_vec()).unwrap_err(),
+        ReplayError::Truncated(header + 2)
+    );
+}
+
+/// 70 ticks, 100ms apart, each followed by an input 50ms later. The input after tick 20 is a
+/// leaderboard instead.
+fn player() -> Player {
+    let mut entries = Vec::new();
+    for tick in 0..70 {
+        let time = tick as f64 * 100.;
+        entries.push(Entry {
+            time,
+            record: Record::Packet(vec![2]),
+        });
+        let record = if tick == 20 {
+            Record::Packet(vec![7, 0])
+        } else {
+            Record::Input(input())
+        };
+        entries.push(Entry {
+            time: time + 50.,
+            record,
+        });
+    }
+    Player::new(Replay {
+        version: VERSION,
+        recorded_at: 0.,
+        handshake: HANDSHAKE.to_vec(),
+        entries,
+    })
+}
+
+#[test]
+fn playback_follows_the_speed() {
+    let mut player = player();
+    assert_eq!(player.tick_count(), 70);
+    assert_eq!(player.speed(), 1.);
+    assert_eq!(player.advance(150.), 0..4);
+    assert_eq!(player.tick(), 1);
+
+    player.faster();
+    assert_eq!(player.advance(100.), 4..8);
+    assert_eq!(player.time, 350.);
+
+    player.paused = true;
+    assert_eq!(player.advance(1000.), 8..8);
+    assert_eq!(player.time, 350.);
+}
+
+#[test]
+fn speeds_stop_at_either_end() {
+    let mut player = player();
+    for _ in 0..SPEEDS.len() + 1 {
+        player.slower();
+    }
+    assert_eq!(player.speed(), SPEEDS[0]);
+    for _ in 0..SPEEDS.len() + 1 {
+        player.faster();
+    }
+    assert_eq!(player.speed(), SPEEDS[SPEEDS.len() - 1]);
+}
+
+#[test]
+fn seeking_replays_from_the_last_keyframe() {
+    let mut player = player();
+    assert_eq!(KEYFRAME_INTERVAL, 30);
+    // tick 45 is entry 90, and the keyframe before it is tick 30
+    assert_eq!(player.seek(45), 60..91);
+    assert_eq!(player.tick(), 45);
+    assert_eq!(player.time, 4500.);
+    assert_eq!(player.leaderboard_before(60), Some(&[7, 0][..]));
+    assert_eq!(player.leaderboard_before(40), None);
+
+    // backwards works the same way
+    assert_eq!(player.seek(10), 0..21);
+    assert_eq!(player.tick(), 10);
+}
+
+#[test]
+fn seeking_past_the_end_stops_at_the_last_tick() {
+    let mut player = player();
+    assert_eq!(player.seek(1000), 120..139);
+    assert_eq!(player.tick(), 69);
+    assert!(!player.finished());
+    assert_eq!(player.advance(100.), 139..140);
+    assert!(player.finished());
+    assert_eq!(player.advance(100.), 140..140);
+
+    let mut empty =
+        Player::new(Replay::decode(Recorder::new(0., 0., &HANDSHAKE).finish()).unwrap());
+    assert_eq!(empty.seek(3), 0..0);
+    assert!(empty.finished());
+}
+
+#[test]
+fn a_varint_that_never_ends_is_corrupt() {
+    let mut data = Recorder::new(0., 0., &HANDSHAKE).finish();
+    let header = data.len();
+    // a packet record whose time has its continuation bit set on every byte
+    data.extend_from_slice(&[0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1, 7]);
+    assert_eq!(
+        Replay::decode(data).unwrap_err(),
+        ReplayError::Corrupt { offset: header + 1 }
+    );
+}
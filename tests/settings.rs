@@ -0,0 +1,192 @@
+use cactuswar_client::engine::World;
+use cactuswar_client::lighting;
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::settings::{
+    Action, KeyBindings, Migration, Quality, Settings, RESERVED, VERSION,
+};
+use serde_json::Value;
+
+#[test]
+fn settings_round_trip_through_json() {
+    let mut settings = Settings::default();
+    assert_eq!(Settings::parse(&settings.to_json()), Ok(settings.clone()));
+
+    settings.quality = Quality::Medium;
+    settings.show_chat = false;
+    settings.ui_scale = 1.5;
+    settings.theme = String::from("night");
+    settings.keys.bind(Action::Chat, 84);
+    assert_eq!(Settings::parse(&settings.to_json()), Ok(settings));
+}
+
+#[test]
+fn missing_settings_are_the_defaults() {
+    let settings = Settings::parse(r#"{"version": 1, "quality": "low", "keys": {"up": 38}}"#);
+    let mut expected = Settings {
+        quality: Quality::Low,
+        ..Settings::default()
+    };
+    expected.keys.up = 38;
+    assert_eq!(settings, Ok(expected));
+}
+
+#[test]
+fn only_versioned_settings_are_read() {
+    let newer = format!(r#"{{"version": {}}}"#, VERSION + 1);
+    assert_eq!(
+        Settings::parse(&newer),
+        Err(format!("Settings from a newer version ({})", VERSION + 1))
+    );
+    for text in [r#"{}"#, r#"{"version": 0}"#, r#"{"version": "1"}"#] {
+        assert_eq!(
+            Settings::parse(text),
+            Err(String::from("Settings without a version"))
+        );
+    }
+    for text in [r#"{"version": 1, "quality": "ultra"}"#, "{"] {
+        let error = Settings::parse(text).unwrap_err();
+        assert!(error.starts_with("Invalid settings: "), "{}", error);
+    }
+}
+
+#[test]
+fn binding_a_taken_key_swaps_it() {
+    let mut keys = KeyBindings::default();
+    assert_eq!(keys.action(87), Some(Action::Up));
+    assert_eq!(keys.action(27), None);
+
+    // D moves up, and W takes its place moving right
+    keys.bind(Action::Up, 68);
+    assert_eq!(keys.key(Action::Up), 68);
+    assert_eq!(keys.key(Action::Right), 87);
+    keys.bind(Action::Screenshot, 80);
+    assert_eq!(keys.action(80), Some(Action::Screenshot));
+    assert_eq!(keys.action(118), None);
+}
+
+#[test]
+fn reserved_keys_cant_be_bound() {
+    let mut keys = KeyBindings::default();
+    for key_code in RESERVED {
+        assert!(!keys.bind(Action::Chat, key_code));
+        assert_eq!(keys, KeyBindings::default());
+    }
+    for action in Action::ALL {
+        assert!(!RESERVED.contains(&keys.key(action)));
+    }
+}
+
+/// Version 1 of some pretend settings called `show_names` just `names`.
+fn rename_names(value: &mut Value) {
+    if let Some(names) = value.get("names").cloned() {
+        value["show_names"] = names;
+    }
+}
+
+/// Version 2 of them kept the UI scale in percent.
+fn scale_from_percent(value: &mut Value) {
+    if let Some(percent) = value.get("ui_scale").and_then(Value::as_f64) {
+        value["ui_scale"] = Value::from(percent / 100.);
+    }
+}
+
+const PRETEND_MIGRATIONS: [Migration; 2] = [rename_names, scale_from_percent];
+
+#[test]
+fn older_settings_are_migrated() {
+    let expected = Settings {
+        show_names: false,
+        ui_scale: 1.5,
+        ..Settings::default()
+    };
+    for text in [
+        r#"{"version": 1, "names": false, "ui_scale": 150}"#,
+        r#"{"version": 2, "show_names": false, "ui_scale": 150}"#,
+        r#"{"version": 3, "show_names": false, "ui_scale": 1.5}"#,
+    ] {
+        assert_eq!(
+            Settings::parse_with(text, &PRETEND_MIGRATIONS),
+            Ok(expected.clone()),
+            "{}",
+            text
+        );
+    }
+
+    assert_eq!(
+        Settings::parse_with(r#"{"version": 4}"#, &PRETEND_MIGRATIONS),
+        Err(String::from("Settings from a newer version (4)"))
+    );
+    assert_eq!(
+        Settings::parse_with(r#"{"names": false}"#, &PRETEND_MIGRATIONS),
+        Err(String::from("Settings without a version"))
+    );
+
+    // and the real migrations take every version there's been up to this one
+    for version in 1..=VERSION {
+        let text = format!(r#"{{"version": {}}}"#, version);
+        assert_eq!(Settings::parse(&text), Ok(Settings::default()));
+    }
+}
+
+#[test]
+fn settings_change_the_world() {
+    let mut world = World::new(
+        String::new(),
+        NetSim::new(Conditions::PERFECT, 0),
+        lighting::DEFAULT_BUDGET,
+    );
+    let defaults = Settings::default();
+    world.apply_settings(defaults.clone());
+    assert_eq!(world.light_budget, lighting::DEFAULT_BUDGET);
+
+    world.apply_settings(Settings {
+        quality: Quality::Low,
+        ui_scale: 5.,
+        show_netstats: true,
+        theme: String::from("night"),
+        ..defaults.clone()
+    });
+    assert_eq!(world.light_budget, 4);
+    assert_eq!(world.ui_scale, 2.);
+    assert_eq!(world.settings.ui_scale, 2.);
+    assert!(world.netstats.visible);
+    assert_eq!(world.theme.name, "night");
+
+    world.apply_settings(Settings {
+        lights: false,
+        theme: String::from("sunset"),
+        ..defaults
+    });
+    assert_eq!(world.light_budget, 0);
+    assert_eq!(world.theme.name, "night");
+    assert_eq!(world.settings.theme, "sunset");
+}
+
+#[test]
+fn only_changed_settings_undo_session_overrides() {
+    let mut world = World::new(
+        String::new(),
+        NetSim::new(Conditions::PERFECT, 0),
+        lighting::DEFAULT_BUDGET,
+    );
+    let defaults = Settings::default();
+    world.apply_settings(defaults.clone());
+    // set from the URL, for this session only
+    world.light_budget = 2;
+    world.ui_scale = 1.5;
+
+    world.apply_settings(Settings {
+        show_names: false,
+        ..defaults.clone()
+    });
+    assert_eq!(world.light_budget, 2);
+    assert_eq!(world.ui_scale, 1.5);
+
+    world.apply_settings(Settings {
+        show_names: false,
+        quality: Quality::Medium,
+        ..defaults
+    });
+    assert_eq!(world.light_budget, 8);
+    assert_eq!(world.ui_scale, 1.5);
+}
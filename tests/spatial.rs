@@ -0,0 +1,173 @@
+use cactuswar_client::engine::{update_entity_cache, Entity};
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::protocol::{self, Census, EntityType, ShapePacket};
+use cactuswar_client::spatial::SpatialHash;
+use cactuswar_client::util::Vector2;
+use std::collections::HashMap;
+
+/// A few hundred entities scattered over an arena, some of them at negative coordinates, in
+/// the same place every run.
+fn scatter() -> Vec<(u32, EntityType, Vector2<f64>)> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % 10_000) as f64 / 10_000.
+    };
+    (0..300)
+        .map(|id| {
+            let entity_type = match id % 3 {
+                0 => EntityType::Tank,
+                1 => EntityType::Shape,
+                _ => EntityType::Bullet,
+            };
+            let position = Vector2 {
+                x: next() * 6000. - 1000.,
+                y: next() * 6000. - 1000.,
+            };
+            (id, entity_type, position)
+        })
+        .collect()
+}
+
+fn index(entities: &[(u32, EntityType, Vector2<f64>)]) -> SpatialHash {
+    let mut index = SpatialHash::new(500.);
+    for (id, entity_type, position) in entities {
+        index.insert(*id, *entity_type, *position);
+    }
+    index
+}
+
+fn sorted(mut ids: Vec<u32>) -> Vec<u32> {
+    ids.sort_unstable();
+    ids
+}
+
+#[test]
+fn rect_queries_find_what_is_inside() {
+    let entities = scatter();
+    let index = index(&entities);
+    for rect in [
+        Aabb::new(Vector2::new(0., 0.), Vector2::new(1920., 1080.)),
+        Aabb::new(Vector2::new(-1200., -300.), Vector2::new(-400., 2500.)),
+        Aabb::new(Vector2::new(3999., 3999.), Vector2::new(4001., 4001.)),
+    ] {
+        let expected: Vec<u32> = entities
+            .iter()
+            .filter(|(_, _, position)| rect.contains(*position))
+            .map(|(id, _, _)| *id)
+            .collect();
+        assert_eq!(sorted(index.query_rect(&rect)), expected);
+    }
+}
+
+#[test]
+fn radius_queries_find_what_is_within_reach() {
+    let entities = scatter();
+    let index = index(&entities);
+    for (center, radius) in [
+        (Vector2::new(2000., 2000.), 800.),
+        (Vector2::new(-500., 100.), 1300.),
+        (Vector2::new(10000., 10000.), 100.),
+    ] {
+        let expected: Vec<u32> = entities
+            .iter()
+            .filter(|(_, _, position)| position.distance(center) <= radius)
+            .map(|(id, _, _)| *id)
+            .collect();
+        assert_eq!(sorted(index.within_radius(center, radius)), expected);
+    }
+}
+
+#[test]
+fn nearest_entities_come_first() {
+    let entities = scatter();
+    let index = index(&entities);
+    for (point, entity_type) in [
+        (Vector2::new(2000., 2000.), None),
+        (Vector2::new(-3000., 7000.), Some(EntityType::Tank)),
+        (Vector2::new(4500., -900.), Some(EntityType::Bullet)),
+    ] {
+        let mut expected: Vec<(f64, u32)> = entities
+            .iter()
+            .filter(|(_, t, _)| entity_type.is_none_or(|entity_type| *t == entity_type))
+            .map(|(id, _, position)| (position.distance(point), *id))
+            .collect();
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let expected: Vec<u32> = expected.into_iter().take(5).map(|(_, id)| id).collect();
+        assert_eq!(index.nearest(point, 5, entity_type), expected);
+    }
+
+    // asking for more than there are gives all of them
+    assert_eq!(index.nearest(Vector2::new(0., 0.), 1000, None).len(), 300);
+    assert!(index.nearest(Vector2::new(0., 0.), 0, None).is_empty());
+    assert!(SpatialHash::default()
+        .nearest(Vector2::new(0., 0.), 3, None)
+        .is_empty());
+}
+
+#[test]
+fn entities_move_between_cells() {
+    let mut index = SpatialHash::new(100.);
+    index.insert(1, EntityType::Bullet, Vector2::new(50., 50.));
+    index.insert(2, EntityType::Tank, Vector2::new(60., 50.));
+    index.insert(1, EntityType::Bullet, Vector2::new(950., 950.));
+    assert_eq!(index.len(), 2);
+    assert_eq!(index.position(1), Some(Vector2::new(950., 950.)));
+
+    let corner = Aabb::new(Vector2::new(0., 0.), Vector2::new(100., 100.));
+    assert_eq!(index.query_rect(&corner), vec![2]);
+    assert_eq!(index.within_radius(Vector2::new(900., 900.), 100.), vec![1]);
+
+    index.remove(2);
+    assert!(index.query_rect(&corner).is_empty());
+    assert_eq!(index.nearest(Vector2::new(0., 0.), 2, None), vec![1]);
+    index.remove(1);
+    assert!(index.is_empty());
+}
+
+fn census(shapes: &[(u32, i16)]) -> Census {
+    Census {
+        entity_count: shapes.len() as u16,
+        arena_size: 10000,
+        level: 1.,
+        entities: shapes
+            .iter()
+            .map(|(id, x)| {
+                let shape = ShapePacket {
+                    id: *id,
+                    position: Vector2 { x: *x, y: 0 },
+                    health: 1.,
+                    radius: 80,
+                };
+                (*id, protocol::Entity::Shape(shape))
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn censuses_keep_the_index_up_to_date() {
+    let mut entities = HashMap::new();
+    let mut index = SpatialHash::default();
+    update_entity_cache(
+        &mut entities,
+        &mut index,
+        &census(&[(1, 100), (2, 200)]),
+        0,
+        false,
+    );
+    assert_eq!(index.len(), 2);
+
+    update_entity_cache(&mut entities, &mut index, &census(&[(1, 3000)]), 0, false);
+    assert_eq!(index.position(1), Some(Vector2::new(3000., 0.)));
+    // entities fade out before they're gone from the cache, and the index
+    assert_eq!(index.len(), 2);
+    if let Some(Entity::Shape(shape)) = entities.get_mut(&2) {
+        shape.opacity.value = 0.;
+    }
+    update_entity_cache(&mut entities, &mut index, &census(&[(1, 3000)]), 0, false);
+    assert_eq!(index.len(), 1);
+    assert_eq!(index.position(2), None);
+}
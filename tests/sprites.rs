@@ -0,0 +1,180 @@
+use cactuswar_client::color::Color;
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::raster::SoftwareRenderer;
+use cactuswar_client::renderer::Renderer;
+use cactuswar_client::sprites::{SpriteCache, SpriteKey, SpriteKind};
+use cactuswar_client::svg::SvgRenderer;
+use cactuswar_client::util::Vector2;
+
+/// Sprites this big take a quarter of a 64 pixel page, padding included.
+const SIZE: u32 = 28;
+
+fn key(kind: SpriteKind, radius: u16, color: u32) -> SpriteKey {
+    SpriteKey {
+        kind,
+        radius: SpriteKey::bucket(radius),
+        color: Color::hex(color),
+        sides: 8,
+        flash: 0,
+    }
+}
+
+fn at(x: f64, y: f64, size: f64) -> Aabb {
+    Aabb::new(
+        Vector2 { x, y },
+        Vector2 {
+            x: x + size,
+            y: y + size,
+        },
+    )
+}
+
+/// Draw the sprite for `key` as a square of its color at (`x`, 0), and return whether it had
+/// to be rendered.
+fn draw(sprites: &mut SpriteCache, ctx: &mut dyn Renderer, key: &SpriteKey, x: f64) -> bool {
+    let mut rendered = false;
+    let color = key.color.clone();
+    sprites.draw(ctx, key, SIZE, at(x, 0., SIZE as f64), &mut |sprite| {
+        rendered = true;
+        sprite.set_fill_color(color.css());
+        sprite.fill_rect(0., 0., SIZE as f64, SIZE as f64);
+    });
+    rendered
+}
+
+#[test]
+fn sprites_are_rendered_once() {
+    let mut ctx = SoftwareRenderer::new(100, 100, None);
+    let mut sprites = SpriteCache::new(1);
+    let cactus = key(SpriteKind::Cactus, 40, 0x002606);
+    assert!(draw(&mut sprites, &mut ctx, &cactus, 0.));
+    assert!(!draw(&mut sprites, &mut ctx, &cactus, 50.));
+    assert_eq!(sprites.len(), 1);
+    assert_eq!(sprites.pages(), 1);
+
+    // both copies are drawn, and nothing around them
+    let pixmap = ctx.pixmap();
+    for x in [14, 64] {
+        let pixel = pixmap.pixel(x, 14).unwrap();
+        assert_eq!(
+            (pixel.red(), pixel.green(), pixel.blue()),
+            (0x00, 0x26, 0x06)
+        );
+    }
+    assert_eq!(pixmap.pixel(14, 40).unwrap().alpha(), 0);
+}
+
+#[test]
+fn keys_tell_sprites_apart() {
+    assert_eq!(SpriteKey::bucket(21), 24);
+    assert_eq!(SpriteKey::bucket(24), 24);
+    assert_eq!(SpriteKey::bucket(0), 4);
+
+    let mut ctx = SoftwareRenderer::new(100, 100, None);
+    let mut sprites = SpriteCache::new(1);
+    assert!(draw(
+        &mut sprites,
+        &mut ctx,
+        &key(SpriteKind::Bullet, 21, 0x00e6f2),
+        0.
+    ));
+    // a radius in the same bucket shares the sprite
+    assert!(!draw(
+        &mut sprites,
+        &mut ctx,
+        &key(SpriteKind::Bullet, 24, 0x00e6f2),
+        0.
+    ));
+    assert!(draw(
+        &mut sprites,
+        &mut ctx,
+        &key(SpriteKind::Bullet, 25, 0x00e6f2),
+        0.
+    ));
+    assert!(draw(
+        &mut sprites,
+        &mut ctx,
+        &key(SpriteKind::Bullet, 21, 0xf28900),
+        0.
+    ));
+    assert!(draw(
+        &mut sprites,
+        &mut ctx,
+        &key(SpriteKind::Cactus, 21, 0x00e6f2),
+        0.
+    ));
+    assert_eq!(sprites.len(), 4);
+}
+
+#[test]
+fn the_least_recently_used_sprite_makes_room() {
+    let mut ctx = SoftwareRenderer::new(100, 100, None);
+    let mut sprites = SpriteCache::with_page_size(1, 64);
+    let keys: Vec<SpriteKey> = (0..5)
+        .map(|i| key(SpriteKind::Cactus, 40, 0x100000 * (i + 1)))
+        .collect();
+    for key in &keys[..4] {
+        assert!(draw(&mut sprites, &mut ctx, key, 0.));
+    }
+    assert!(!draw(&mut sprites, &mut ctx, &keys[0], 0.));
+
+    assert!(draw(&mut sprites, &mut ctx, &keys[4], 0.));
+    assert_eq!(sprites.len(), 4);
+    assert_eq!(sprites.pages(), 1);
+    assert!(sprites.contains(&keys[0]));
+    assert!(!sprites.contains(&keys[1]));
+
+    // the new sprite is drawn where the evicted one was, and looks like itself
+    draw(&mut sprites, &mut ctx, &keys[4], 0.);
+    let pixel = ctx.pixmap().pixel(14, 14).unwrap();
+    assert_eq!(pixel.red(), 0x50);
+}
+
+#[test]
+fn a_bigger_sprite_starts_a_page_over() {
+    let mut ctx = SoftwareRenderer::new(100, 100, None);
+    let mut sprites = SpriteCache::with_page_size(1, 64);
+    for i in 0..4 {
+        draw(
+            &mut sprites,
+            &mut ctx,
+            &key(SpriteKind::Cactus, 40, 0x100000 * (i + 1)),
+            0.,
+        );
+    }
+    let big = key(SpriteKind::Bullet, 60, 0x00e6f2);
+    sprites.draw(&mut ctx, &big, 50, at(0., 0., 50.), &mut |_| {});
+    assert!(sprites.contains(&big));
+    assert_eq!(sprites.len(), 1);
+
+    // too big for any page: drawn without being cached
+    let huge = key(SpriteKind::Bullet, 80, 0x00e6f2);
+    sprites.draw(&mut ctx, &huge, 100, at(0., 0., 100.), &mut |_| {});
+    assert!(!sprites.contains(&huge));
+}
+
+#[test]
+fn without_a_budget_nothing_is_cached() {
+    let mut ctx = SoftwareRenderer::new(100, 100, None);
+    let mut sprites = SpriteCache::new(0);
+    let cactus = key(SpriteKind::Cactus, 40, 0x002606);
+    assert!(draw(&mut sprites, &mut ctx, &cactus, 0.));
+    assert!(draw(&mut sprites, &mut ctx, &cactus, 0.));
+    assert!(sprites.is_empty());
+    assert_eq!(sprites.pages(), 0);
+    assert_eq!(ctx.pixmap().pixel(14, 14).unwrap().green(), 0x26);
+}
+
+#[test]
+fn another_renderer_starts_over() {
+    let mut sprites = SpriteCache::new(1);
+    let cactus = key(SpriteKind::Cactus, 40, 0x002606);
+    let mut raster = SoftwareRenderer::new(100, 100, None);
+    assert!(draw(&mut sprites, &mut raster, &cactus, 0.));
+
+    let mut svg = SvgRenderer::new(100., 100., Box::new(|_: &str, _: &str| 0.));
+    assert!(draw(&mut sprites, &mut svg, &cactus, 0.));
+    assert!(!draw(&mut sprites, &mut svg, &cactus, 0.));
+    assert_eq!(sprites.pages(), 1);
+    assert!(svg.to_svg().contains("viewBox=\"2 2 28 28\""));
+}
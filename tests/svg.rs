@@ -0,0 +1,138 @@
+use cactuswar_client::engine::{Entity, Shape, Tank, World};
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::protocol::{BarrelMockup, TankMockup};
+use cactuswar_client::renderer::Renderer;
+use cactuswar_client::svg::{self, SvgRenderer};
+use cactuswar_client::util::Vector2;
+
+/// Every character is 10 pixels wide.
+fn measure(_font: &str, text: &str) -> f64 {
+    text.chars().count() as f64 * 10.
+}
+
+/// Panic unless every tag in `svg` is closed, in order.
+fn assert_balanced(svg: &str) {
+    let mut open = Vec::new();
+    let mut rest = svg;
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>').expect("unterminated tag");
+        let tag = &rest[start + 1..end];
+        assert!(!tag.contains('<'), "'<' inside a tag: {}", tag);
+        if let Some(name) = tag.strip_prefix('/') {
+            assert_eq!(open.pop(), Some(name), "mismatched </{}>", name);
+        } else if !tag.ends_with('/') {
+            open.push(tag.split(' ').next().unwrap());
+        }
+        rest = &rest[end + 1..];
+    }
+    assert!(open.is_empty(), "unclosed {:?}", open);
+}
+
+fn world() -> World {
+    let mut world = World::new(String::from("me"), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.mockups = Some(vec![TankMockup {
+        name: String::from("Basic"),
+        fov: 20,
+        barrels: vec![BarrelMockup {
+            width: 0.4,
+            length: 1.,
+            angle: 0.,
+        }],
+    }]);
+    world.add_entity(Entity::Tank(Tank::new(
+        1,
+        String::from("<b>&\"friends\""),
+        Vector2 { x: 300., y: 0. },
+    )));
+    world.add_entity(Entity::Shape(Shape::new(
+        2,
+        Vector2 { x: -300., y: 0. },
+        7,
+        80,
+    )));
+    world
+}
+
+#[test]
+fn records_a_frame() {
+    let svg = svg::draw_world(&mut world(), 1280., 720., 1., 0, Box::new(measure));
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1280\""));
+    assert_balanced(&svg);
+    // names are escaped
+    assert!(svg.contains("&lt;b&gt;&amp;&quot;friends&quot;</text>"));
+    // lights add up and are clipped to what they can see
+    assert!(svg.contains("mix-blend-mode:plus-lighter"));
+    assert!(svg.contains("<clipPath"));
+    assert!(svg.contains("<radialGradient"));
+    // the title glows
+    assert!(svg.contains("<feDropShadow"));
+    assert!(svg.contains(">CactusWar.io</text>"));
+}
+
+#[test]
+fn textures_are_drawn_as_vectors() {
+    let mut renderer = SvgRenderer::new(100., 100., Box::new(measure));
+    let texture = renderer.create_texture(10, 10);
+    assert!(renderer.owns_texture(&texture));
+    renderer.draw_to_texture(&texture, &mut |ctx| {
+        ctx.set_fill_color("#ff0000");
+        ctx.fill_rect(0., 0., 10., 10.);
+    });
+    renderer.draw_texture(&texture, 20., 30., 40., 50.);
+    assert!(renderer.to_svg().contains(
+        "<g transform=\"matrix(4 0 0 5 20 30)\"><rect x=\"0\" y=\"0\" width=\"10\" \
+         height=\"10\" fill=\"#ff0000\"/></g>"
+    ));
+}
+
+#[test]
+fn paths_keep_their_transform() {
+    let mut renderer = SvgRenderer::new(100., 100., Box::new(measure));
+    renderer.translate(10., 20.);
+    renderer.scale(2., 2.);
+    renderer.begin_path();
+    renderer.move_to(0., 0.);
+    renderer.line_to(5., 0.);
+    renderer.line_to(5., 5.);
+    renderer.close_path();
+    renderer.set_line_width(3.);
+    renderer.stroke();
+    assert!(renderer.to_svg().contains(
+        "<path d=\"M0 0L5 0L5 5Z\" transform=\"matrix(2 0 0 2 10 20)\" fill=\"none\" \
+         stroke=\"#000000\" stroke-width=\"3\" stroke-linejoin=\"miter\"/>"
+    ));
+}
+
+#[test]
+fn clearing_the_whole_document_starts_over() {
+    let mut renderer = SvgRenderer::new(100., 100., Box::new(measure));
+    renderer.fill_rect(0., 0., 10., 10.);
+    renderer.clear_rect(10., 10., 20., 20.);
+    assert!(renderer.to_svg().contains("<rect"));
+    renderer.clear_rect(0., 0., 100., 100.);
+    assert!(!renderer.to_svg().contains("<rect"));
+}
+
+#[test]
+fn measures_text_in_the_current_font() {
+    let mut renderer = SvgRenderer::new(
+        100.,
+        100.,
+        Box::new(|font: &str, text: &str| {
+            assert_eq!(font, "bold 48px \"Fira Sans\"");
+            text.len() as f64
+        }),
+    );
+    renderer.set_font("bold 48px \"Fira Sans\"");
+    assert_eq!(renderer.measure_text("abc"), 3.);
+}
+
+#[test]
+fn names_can_be_hidden() {
+    let mut world = world();
+    let mut settings = world.settings.clone();
+    settings.show_names = false;
+    world.apply_settings(settings);
+    let svg = svg::draw_world(&mut world, 1280., 720., 1., 0, Box::new(measure));
+    assert!(!svg.contains("friends"));
+}
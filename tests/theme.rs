@@ -0,0 +1,133 @@
+use cactuswar_client::color::Color;
+use cactuswar_client::engine::{Bullet, Entity, Tank, World};
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::svg::{self, SvgRenderer};
+use cactuswar_client::theme::{Theme, BUILT_IN};
+use cactuswar_client::util::Vector2;
+
+#[test]
+fn built_in_themes_are_found_by_name() {
+    for name in BUILT_IN {
+        let theme = Theme::parse(name).unwrap();
+        assert_eq!(theme.name, name);
+        assert_eq!(Theme::built_in(name), Some(theme));
+    }
+    assert_eq!(Theme::default(), Theme::desert());
+    assert_eq!(
+        Theme::parse("sunset"),
+        Err(String::from("Unknown theme: sunset"))
+    );
+}
+
+#[test]
+fn themes_cycle_through_the_built_in_ones() {
+    let night = Theme::desert().next();
+    assert_eq!(night, Theme::night());
+    assert_eq!(night.next(), Theme::high_contrast());
+    assert_eq!(Theme::tritanopia().next(), Theme::desert());
+
+    let custom = Theme::parse(r#"{"name": "mine"}"#).unwrap();
+    assert_eq!(custom.next(), Theme::desert());
+}
+
+#[test]
+fn themes_round_trip_through_json() {
+    for name in BUILT_IN {
+        let theme = Theme::built_in(name).unwrap();
+        assert_eq!(Theme::parse(&theme.to_json()), Ok(theme));
+    }
+}
+
+#[test]
+fn json_themes_fall_back_to_the_desert() {
+    let theme = Theme::parse(
+        r##"{
+            "name": "mine",
+            "arena": "#102030",
+            "cactus": { "body": "rgba(0,90,0,1)" }
+        }"##,
+    )
+    .unwrap();
+    assert_eq!(theme.name, "mine");
+    assert_eq!(theme.arena, Color::hex(0x102030));
+    assert_eq!(theme.cactus.body, Color::rgba(0, 90, 0, 1.));
+
+    let desert = Theme::desert();
+    assert_eq!(theme.cactus.core, desert.cactus.core);
+    assert_eq!(theme.grid, desert.grid);
+    assert_eq!(theme.hud, desert.hud);
+}
+
+#[test]
+fn broken_json_themes_are_errors() {
+    for text in [
+        r#"{"arena": "sandy"}"#,
+        r##"{"tank": {"colour": "#ffffff"}}"##,
+        r##"{"arena": "#102030""##,
+    ] {
+        let error = Theme::parse(text).unwrap_err();
+        assert!(error.starts_with("Invalid theme: "), "{}", error);
+    }
+}
+
+#[test]
+fn frames_are_drawn_in_the_theme() {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.set_theme(Theme::night());
+    let frame = svg::draw_world(
+        &mut world,
+        400.,
+        300.,
+        1.,
+        0,
+        Box::new(|_: &str, text: &str| text.len() as f64),
+    );
+    let night = Theme::night();
+    for color in [
+        &night.page,
+        &night.arena,
+        &night.tank.body,
+        &night.hud.title,
+    ] {
+        assert!(frame.contains(color.css()), "{} is missing", color);
+    }
+    assert!(!frame.contains(Theme::desert().arena.css()));
+}
+
+#[test]
+fn lights_are_in_the_theme() {
+    let mut world = World::new(String::new(), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.add_entity(Entity::Tank(Tank::new(
+        1,
+        String::new(),
+        Vector2 { x: 300., y: 0. },
+    )));
+    world.add_entity(Entity::Bullet(Bullet::new(
+        2,
+        Vector2 { x: -300., y: 0. },
+        20,
+        false,
+    )));
+    let mut theme = Theme::deuteranopia();
+    theme.tank.light = Color::hex(0x102030);
+    world.set_theme(theme.clone());
+
+    let mut ctx = SvgRenderer::new(
+        1000.,
+        1000.,
+        Box::new(|_: &str, text: &str| text.len() as f64),
+    );
+    let view = Aabb::new(Vector2 { x: -500., y: -500. }, Vector2 { x: 500., y: 500. });
+    let scene = world.draw_entities(&mut ctx, 1., &view);
+    let colors: Vec<&Color> = scene.lights.iter().map(|(_, light)| &light.color).collect();
+    // the other tank, the bullet and ourselves
+    assert_eq!(
+        colors,
+        vec![
+            &theme.tank.light,
+            &theme.bullet.enemy_glow,
+            &theme.tank.light
+        ]
+    );
+}
@@ -0,0 +1,109 @@
+use cactuswar_client::engine::{Bullet, FixedTimestep, Shape, Tank, STEP_MS};
+use cactuswar_client::util::Vector2;
+
+fn tank() -> Tank {
+    Tank {
+        net_position: Vector2 { x: 800., y: -300. },
+        net_rotation: 2.,
+        velocity: Vector2 { x: 12., y: 4. },
+        ..Tank::new(1, String::from("cactus"), Vector2 { x: 0., y: 0. })
+    }
+}
+
+fn shape() -> Shape {
+    Shape {
+        net_position: Vector2 { x: -400., y: 250. },
+        ..Shape::new(2, Vector2 { x: 0., y: 0. }, 12, 80)
+    }
+}
+
+fn bullet() -> Bullet {
+    Bullet {
+        net_position: Vector2 { x: 100., y: 100. },
+        velocity: Vector2 { x: 20., y: -5. },
+        ..Bullet::new(3, Vector2 { x: 0., y: 0. }, 10, false)
+    }
+}
+
+/// Where the entities are drawn on every frame of one second at `fps` frames per second.
+fn run(fps: f64) -> Vec<(f64, [Vector2<f64>; 3], f64)> {
+    let (mut tank, mut shape, mut bullet) = (tank(), shape(), bullet());
+    let mut timestep = FixedTimestep::new();
+    let frame = 1000. / fps;
+    let mut frames = Vec::new();
+    for i in 1..=fps as usize {
+        for _ in 0..timestep.advance(frame) {
+            tank.update(1.);
+            shape.update(1.);
+            bullet.update(1.);
+        }
+        let alpha = timestep.alpha();
+        frames.push((
+            i as f64 * frame,
+            [
+                tank.render_position(alpha),
+                shape.render_position(alpha),
+                bullet.render_position(alpha),
+            ],
+            tank.render_rotation(alpha),
+        ));
+    }
+    frames
+}
+
+fn assert_close(a: Vector2<f64>, b: Vector2<f64>, time: f64) {
+    assert!(
+        (a.x - b.x).abs() < 1e-6 && (a.y - b.y).abs() < 1e-6,
+        "{:?} != {:?} at {}ms",
+        a,
+        b,
+        time
+    );
+}
+
+#[test]
+fn motion_is_independent_of_frame_rate() {
+    let reference = run(30.);
+    for fps in &[60., 240.] {
+        let frames = run(*fps);
+        // every frame at 30 fps lines up with a frame at the higher rates
+        for (time, positions, rotation) in &reference {
+            let (_, other, other_rotation) = frames[(*time / (1000. / fps)).round() as usize - 1];
+            for (a, b) in positions.iter().zip(other.iter()) {
+                assert_close(*a, *b, *time);
+            }
+            assert!((rotation - other_rotation).abs() < 1e-6);
+        }
+    }
+}
+
+#[test]
+fn velocity_damping_matches_sixty_steps_a_second() {
+    let mut tank = tank();
+    tank.net_position = tank.position;
+    tank.update(1.);
+    assert!((tank.velocity.x - 12. * 0.8).abs() < 1e-9);
+
+    // two half steps damp as much as one whole step
+    let mut halves = self::tank();
+    halves.net_position = halves.position;
+    halves.update(0.5);
+    halves.update(0.5);
+    assert!((halves.velocity.x - tank.velocity.x).abs() < 1e-9);
+}
+
+#[test]
+fn accumulator_steps_and_interpolates() {
+    let mut timestep = FixedTimestep::new();
+    assert_eq!(timestep.advance(STEP_MS / 2.), 0);
+    assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+    assert_eq!(timestep.advance(STEP_MS), 1);
+    assert!((timestep.alpha() - 0.5).abs() < 1e-9);
+
+    // a long pause isn't caught up on all at once
+    assert_eq!(
+        timestep.advance(10_000.),
+        cactuswar_client::engine::MAX_STEPS
+    );
+    assert!(timestep.alpha() < 1.);
+}
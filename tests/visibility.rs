@@ -0,0 +1,97 @@
+use cactuswar_client::geometry::{polygon_contains, visibility_polygon, Circle};
+use cactuswar_client::util::Vector2;
+
+fn v(x: f64, y: f64) -> Vector2<f64> {
+    Vector2::new(x, y)
+}
+
+const ORIGIN: Vector2<f64> = Vector2 { x: 0., y: 0. };
+
+#[test]
+fn polygon_containment() {
+    let square = [v(0., 0.), v(10., 0.), v(10., 10.), v(0., 10.)];
+    assert!(polygon_contains(&square, v(5., 5.)));
+    assert!(!polygon_contains(&square, v(15., 5.)));
+    assert!(!polygon_contains(&square, v(5., -1.)));
+    assert!(!polygon_contains(&[], v(0., 0.)));
+}
+
+#[test]
+fn unobstructed_light_is_a_circle() {
+    let lit = visibility_polygon(ORIGIN, 1000., &[]);
+    assert!(lit.len() >= 32);
+    for point in &lit {
+        assert!((point.length() - 1000.).abs() < 1e-6);
+    }
+    assert!(polygon_contains(&lit, v(900., 0.)));
+    assert!(polygon_contains(&lit, v(-600., 600.)));
+    assert!(!polygon_contains(&lit, v(1100., 0.)));
+}
+
+#[test]
+fn occluder_casts_a_shadow_behind_it() {
+    let lit = visibility_polygon(ORIGIN, 1000., &[Circle::new(v(300., 0.), 50.)]);
+    // in front of and beside the occluder is lit
+    assert!(polygon_contains(&lit, v(200., 0.)));
+    assert!(polygon_contains(&lit, v(600., 150.)));
+    assert!(polygon_contains(&lit, v(-600., 0.)));
+    // behind it isn't, and neither is the occluder itself
+    assert!(!polygon_contains(&lit, v(600., 0.)));
+    assert!(!polygon_contains(&lit, v(900., 100.)));
+    assert!(!polygon_contains(&lit, v(300., 0.)));
+    // the shadow widens with the tangents: 50/300 of the distance on either side
+    assert!(!polygon_contains(&lit, v(900., 145.)));
+    assert!(polygon_contains(&lit, v(900., 160.)));
+}
+
+#[test]
+fn overlapping_occluders_merge_their_shadows() {
+    let cluster = [
+        Circle::new(v(300., -40.), 50.),
+        Circle::new(v(320., 30.), 50.),
+        Circle::new(v(280., 0.), 50.),
+    ];
+    let lit = visibility_polygon(ORIGIN, 1000., &cluster);
+    for y in (-150..=150).step_by(10) {
+        assert!(
+            !polygon_contains(&lit, v(800., y as f64)),
+            "gap in the shadow at y {}",
+            y
+        );
+    }
+    assert!(polygon_contains(&lit, v(800., 400.)));
+    assert!(polygon_contains(&lit, v(200., 0.)));
+}
+
+#[test]
+fn light_passes_between_separate_occluders() {
+    let occluders = [
+        Circle::new(v(300., -120.), 50.),
+        Circle::new(v(300., 120.), 50.),
+    ];
+    let lit = visibility_polygon(ORIGIN, 1000., &occluders);
+    assert!(polygon_contains(&lit, v(800., 0.)));
+    assert!(!polygon_contains(&lit, v(800., 320.)));
+    assert!(!polygon_contains(&lit, v(800., -320.)));
+}
+
+#[test]
+fn nearer_occluder_wins() {
+    let occluders = [Circle::new(v(600., 0.), 50.), Circle::new(v(200., 0.), 50.)];
+    let lit = visibility_polygon(ORIGIN, 1000., &occluders);
+    // the far occluder is entirely in the shadow of the near one, so none of it is lit
+    assert!(!polygon_contains(&lit, v(555., 0.)));
+    assert!(polygon_contains(&lit, v(140., 0.)));
+}
+
+#[test]
+fn occluders_around_the_light_and_out_of_range_are_ignored() {
+    let occluders = [
+        // the tank carrying the light
+        Circle::new(v(10., 0.), 50.),
+        Circle::new(v(2000., 0.), 50.),
+    ];
+    let lit = visibility_polygon(ORIGIN, 1000., &occluders);
+    assert!(polygon_contains(&lit, v(900., 0.)));
+    assert!(polygon_contains(&lit, v(-900., 0.)));
+}
@@ -0,0 +1,274 @@
+use cactuswar_client::engine::World;
+use cactuswar_client::geometry::Aabb;
+use cactuswar_client::menu;
+use cactuswar_client::netsim::{Conditions, NetSim};
+use cactuswar_client::settings::Action;
+use cactuswar_client::svg::{self, SvgRenderer};
+use cactuswar_client::theme::Theme;
+use cactuswar_client::util::Vector2;
+use cactuswar_client::widget::{Frame, Key, Ui};
+
+fn rect(x: f64, y: f64, width: f64, height: f64) -> Aabb {
+    Aabb::new(
+        Vector2 { x, y },
+        Vector2 {
+            x: x + width,
+            y: y + height,
+        },
+    )
+}
+
+/// Draw a frame of widgets, 2 pixels to the design unit.
+fn frame<T>(ui: &mut Ui, draw: impl FnOnce(&mut Frame) -> T) -> T {
+    let mut ctx = SvgRenderer::new(
+        2000.,
+        1000.,
+        Box::new(|_: &str, text: &str| text.len() as f64 * 20.),
+    );
+    let colors = Theme::default().hud;
+    let mut frame = ui.frame(&mut ctx, &colors, 2.);
+    draw(&mut frame)
+}
+
+#[test]
+fn buttons_are_clicked() {
+    let mut ui = Ui::new();
+    let button = |frame: &mut Frame| frame.button("ok", rect(100., 100., 200., 80.), "OK");
+    assert!(!frame(&mut ui, button));
+
+    // in pixels, so on the button
+    assert!(ui.pointer_down(300., 280.));
+    assert!(!frame(&mut ui, button));
+    assert!(ui.pointer_up());
+    assert!(frame(&mut ui, button));
+    assert!(!frame(&mut ui, button));
+    assert!(ui.is_focused("ok"));
+
+    // letting go somewhere else doesn't click it
+    assert!(ui.pointer_down(300., 280.));
+    ui.pointer_move(900., 900.);
+    assert!(ui.pointer_up());
+    assert!(!frame(&mut ui, button));
+}
+
+#[test]
+fn clicks_that_miss_go_to_the_game() {
+    let mut ui = Ui::new();
+    assert!(!ui.is_shown());
+    assert!(!ui.pointer_down(300., 280.));
+    assert!(!ui.pointer_up());
+
+    frame(&mut ui, |frame| {
+        frame.panel(rect(0., 0., 500., 500.));
+        frame.button("ok", rect(100., 100., 200., 80.), "OK");
+    });
+    assert!(ui.is_shown());
+    // on the panel, but not the button
+    assert!(ui.pointer_down(50., 50.));
+    assert!(!ui.pointer_up());
+    assert!(!ui.pointer_down(1500., 50.));
+
+    frame(&mut ui, |_| {});
+    assert!(!ui.is_shown());
+    assert!(!ui.pointer_down(300., 280.));
+}
+
+#[test]
+fn sliders_follow_the_mouse() {
+    let mut ui = Ui::new();
+    let mut value = 2.;
+    let slider = |ui: &mut Ui, value: &mut f64| {
+        frame(ui, |frame| {
+            frame.slider("s", rect(0., 0., 500., 100.), "", value, 0., 10., 1.)
+        })
+    };
+    assert!(!slider(&mut ui, &mut value));
+
+    // the track goes from 40 to 460
+    ui.pointer_down(2. * 250., 2. * 50.);
+    assert!(slider(&mut ui, &mut value));
+    assert_eq!(value, 5.);
+    ui.pointer_move(2. * 1000., 0.);
+    assert!(slider(&mut ui, &mut value));
+    assert_eq!(value, 10.);
+    ui.pointer_up();
+    ui.pointer_move(0., 0.);
+    assert!(!slider(&mut ui, &mut value));
+    assert_eq!(value, 10.);
+
+    assert!(ui.key(Key::Left));
+    assert!(ui.key(Key::Left));
+    assert!(slider(&mut ui, &mut value));
+    assert_eq!(value, 8.);
+
+    // values from elsewhere aren't snapped to a step until the slider moves
+    value = 2.5;
+    assert!(!slider(&mut ui, &mut value));
+    assert_eq!(value, 2.5);
+}
+
+#[test]
+fn tab_moves_the_focus() {
+    let mut ui = Ui::new();
+    let buttons = |frame: &mut Frame| {
+        ["a", "b", "c"]
+            .iter()
+            .enumerate()
+            .map(|(index, name)| frame.button(name, rect(0., index as f64 * 100., 200., 80.), name))
+            .collect::<Vec<bool>>()
+    };
+    // nothing to focus yet
+    assert!(!ui.key(Key::Next));
+    frame(&mut ui, buttons);
+    assert!(!ui.key(Key::Press));
+
+    assert!(ui.key(Key::Next));
+    assert!(ui.is_focused("a"));
+    assert!(ui.key(Key::Next));
+    assert!(ui.is_focused("b"));
+    assert!(ui.key(Key::Previous));
+    assert!(ui.key(Key::Previous));
+    assert!(ui.is_focused("c"));
+
+    assert!(ui.key(Key::Press));
+    assert_eq!(frame(&mut ui, buttons), vec![false, false, true]);
+    assert_eq!(frame(&mut ui, buttons), vec![false, false, false]);
+
+    // the focus goes with the widget
+    frame(&mut ui, |frame| {
+        frame.button("a", rect(0., 0., 200., 80.), "a")
+    });
+    assert!(!ui.is_focused("c"));
+    assert!(!ui.key(Key::Press));
+}
+
+/// Draw a list and some tabs, and return whether each was changed.
+fn list_and_tabs(ui: &mut Ui, item: &mut usize, tab: &mut usize) -> (bool, bool) {
+    frame(ui, |frame| {
+        (
+            frame.list("list", rect(0., 0., 300., 300.), &["x", "y", "z"], item),
+            frame.tabs("tabs", rect(0., 400., 300., 80.), &["1", "2"], tab),
+        )
+    })
+}
+
+#[test]
+fn arrow_keys_move_lists_and_tabs() {
+    let mut ui = Ui::new();
+    let (mut item, mut tab) = (0, 0);
+    list_and_tabs(&mut ui, &mut item, &mut tab);
+
+    ui.key(Key::Next);
+    for _ in 0..3 {
+        ui.key(Key::Down);
+    }
+    assert_eq!(list_and_tabs(&mut ui, &mut item, &mut tab), (true, false));
+    assert_eq!(item, 2);
+    ui.key(Key::Next);
+    ui.key(Key::Left);
+    assert_eq!(list_and_tabs(&mut ui, &mut item, &mut tab), (false, false));
+    ui.key(Key::Right);
+    assert_eq!(list_and_tabs(&mut ui, &mut item, &mut tab), (false, true));
+    assert_eq!(tab, 1);
+}
+
+#[test]
+fn clicks_pick_the_row_or_tab_under_the_mouse() {
+    let mut ui = Ui::new();
+    let (mut item, mut tab) = (0, 0);
+    list_and_tabs(&mut ui, &mut item, &mut tab);
+    ui.pointer_down(2. * 150., 2. * 150.);
+    ui.pointer_up();
+    assert_eq!(list_and_tabs(&mut ui, &mut item, &mut tab), (true, false));
+    assert_eq!(item, 1);
+
+    ui.pointer_down(2. * 200., 2. * 420.);
+    ui.pointer_up();
+    assert_eq!(list_and_tabs(&mut ui, &mut item, &mut tab), (false, true));
+    assert_eq!(tab, 1);
+}
+
+/// Draw `world` on a 1920 by 1080 screen.
+fn draw(world: &mut World) -> String {
+    svg::draw_world(
+        world,
+        1920.,
+        1080.,
+        1.,
+        0,
+        Box::new(|_: &str, text: &str| text.len() as f64 * 20.),
+    )
+}
+
+#[test]
+fn the_menu_changes_the_world() {
+    let mut world = World::new(String::from("me"), NetSim::new(Conditions::PERFECT, 0), 8);
+    draw(&mut world);
+    assert!(!world.ui.is_shown());
+
+    world.menu.open = true;
+    draw(&mut world);
+    assert!(world.ui.is_shown());
+    // past the tabs, the quality and the lights, onto the themes
+    for _ in 0..4 {
+        world.ui.key(Key::Next);
+    }
+    assert!(world.ui.is_focused("theme"));
+    world.ui.key(Key::Down);
+    draw(&mut world);
+    assert_eq!(world.theme.name, "night");
+    assert_eq!(world.settings.theme, "night");
+
+    // Tab backwards round to the resume button
+    for _ in 0..4 {
+        world.ui.key(Key::Previous);
+    }
+    assert!(world.ui.is_focused("resume"));
+    world.ui.key(Key::Press);
+    draw(&mut world);
+    assert!(!world.menu.open);
+    draw(&mut world);
+    assert!(!world.ui.is_shown());
+}
+
+#[test]
+fn reserved_keys_are_not_bound_in_the_menu() {
+    let mut world = World::new(String::from("me"), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.menu.open = true;
+    draw(&mut world);
+    // over to the keys tab, and onto moving up
+    world.ui.key(Key::Next);
+    world.ui.key(Key::Right);
+    world.ui.key(Key::Right);
+    draw(&mut world);
+    world.ui.key(Key::Next);
+    world.ui.key(Key::Press);
+    draw(&mut world);
+
+    // Space is kept for replays, so the menu waits for another key
+    assert!(menu::key_down(&mut world, 32));
+    assert!(menu::key_up(&mut world, 32));
+    assert!(draw(&mut world).contains("Space is kept for replays and the killcam"));
+    assert_eq!(world.settings.keys.key(Action::Up), 87);
+
+    assert!(menu::key_down(&mut world, 73));
+    assert_eq!(world.settings.keys.key(Action::Up), 73);
+    assert!(!draw(&mut world).contains("is kept for replays"));
+    assert!(!menu::key_down(&mut world, 74));
+}
+
+#[test]
+fn opening_the_menu_lets_go_of_the_tank() {
+    let mut world = World::new(String::from("me"), NetSim::new(Conditions::PERFECT, 0), 8);
+    world.input.W = true;
+    world.input.D = true;
+    world.input.mouse_down = true;
+    world.input.mouse_position = Vector2 { x: 40, y: -20 };
+    menu::toggle(&mut world);
+    assert!(world.menu.open);
+    assert!(!world.input.W && !world.input.D && !world.input.mouse_down);
+    assert_eq!(world.input.mouse_position, Vector2 { x: 40, y: -20 });
+
+    menu::toggle(&mut world);
+    assert!(!world.menu.open);
+}